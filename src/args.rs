@@ -1,6 +1,9 @@
-use clap::ValueEnum;
+use clap::{Args, ValueEnum};
+use rand_chacha::ChaCha8Rng;
 use strum_macros::EnumVariantNames;
 
+use crate::utils::rng::seeded_rng;
+
 #[derive(Clone, Copy, ValueEnum, EnumVariantNames, strum_macros::Display)]
 pub enum JuliaFunction {
     Cos,
@@ -29,7 +32,7 @@ impl JuliaFunction {
     }
 }
 
-#[derive(Clone, ValueEnum, EnumVariantNames, strum_macros::Display)]
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum, EnumVariantNames, strum_macros::Display)]
 pub enum ColorScheme {
     Inferno,
     Viridis,
@@ -42,4 +45,115 @@ impl ColorScheme {
     pub fn subroutine_name(&self) -> String {
         format!("ColorMap{}", self)
     }
+
+    /// CPU version of the colormap subroutines in `shader_builder/includes/colors.glsl`. Maps
+    /// `t` in [0.0, 1.0] to an RGB color.
+    pub fn color(&self, t: f32) -> [f32; 3] {
+        // Computed in f64 since the coefficients carry more precision than f32 can hold
+        let t = t.clamp(0.0, 1.0) as f64;
+
+        let c: [[f64; 3]; 7] = match self {
+            ColorScheme::Inferno => [
+                [
+                    0.0002189403691192265,
+                    0.001651004631001012,
+                    -0.01948089843709184,
+                ],
+                [0.1065134194856116, 0.5639564367884091, 3.932712388889277],
+                [11.60249308247187, -3.972853965665698, -15.9423941062914],
+                [-41.70399613139459, 17.43639888205313, 44.35414519872813],
+                [77.162935699427, -33.40235894210092, -81.80730925738993],
+                [-71.31942824499214, 32.62606426397723, 73.20951985803202],
+                [25.13112622477341, -12.24266895238567, -23.07032500287172],
+            ],
+            ColorScheme::Viridis => [
+                [0.2777273272234177, 0.005407344544966578, 0.3340998053353061],
+                [0.1050930431085774, 1.404613529898575, 1.384590162594685],
+                [-0.3308618287255563, 0.214847559468213, 0.09509516302823659],
+                [-4.634230498983486, -5.799100973351585, -19.33244095627987],
+                [6.228269936347081, 14.17993336680509, 56.69055260068105],
+                [4.776384997670288, -13.74514537774601, -65.35303263337234],
+                [-5.435455855934631, 4.645852612178535, 26.3124352495832],
+            ],
+            ColorScheme::Plasma => [
+                [0.05873234392399702, 0.02333670892565664, 0.5433401826748754],
+                [2.176514634195958, 0.2383834171260182, 0.7539604599784036],
+                [-2.689460476458034, -7.455851135738909, 3.110799939717086],
+                [6.130348345893603, 42.3461881477227, -28.51885465332158],
+                [-11.10743619062271, -82.66631109428045, 60.13984767418263],
+                [10.02306557647065, 71.4136177009535, -54.07218655560067],
+                [-3.658713842777788, -22.93153465461149, 18.19190778539828],
+            ],
+            ColorScheme::Magma => [
+                [
+                    -0.002136485053939582,
+                    -0.000749655052795221,
+                    -0.005386127855323933,
+                ],
+                [0.2516605407371642, 0.6775232436837668, 2.494026599312351],
+                [8.353717279216625, -3.577719514958484, 0.3144679030132573],
+                [-27.66873308576866, 14.26473078096533, -13.64921318813922],
+                [52.17613981234068, -27.94360607168351, 12.94416944238394],
+                [-50.76852536473588, 29.04658282127291, 4.23415299384598],
+                [18.65570506591883, -11.48977351997711, -5.601961508734096],
+            ],
+            ColorScheme::Turbo => {
+                // Turbo is copyright 2019 Google LLC and uses a different polynomial form
+                let red = [
+                    0.13572138,
+                    4.61539260,
+                    -42.66032258,
+                    132.13108234,
+                    -152.94239396,
+                    59.28637943,
+                ];
+                let green = [
+                    0.09140261,
+                    2.19418839,
+                    4.84296658,
+                    -14.18503333,
+                    4.27729857,
+                    2.82956604,
+                ];
+                let blue = [
+                    0.10667330,
+                    12.64194608,
+                    -60.58204836,
+                    110.36276771,
+                    -89.90310912,
+                    27.34824973,
+                ];
+                let eval = |k: [f64; 6]| {
+                    k.iter()
+                        .rev()
+                        .fold(0.0, |acc, k| acc * t + k)
+                        .clamp(0.0, 1.0) as f32
+                };
+                return [eval(red), eval(green), eval(blue)];
+            }
+        };
+
+        let mut ret = [0.0; 3];
+        for (channel, out) in ret.iter_mut().enumerate() {
+            *out = c
+                .iter()
+                .rev()
+                .fold(0.0, |acc, k| acc * t + k[channel])
+                .clamp(0.0, 1.0) as f32;
+        }
+        ret
+    }
+}
+
+#[derive(Args)]
+pub struct SeedArgs {
+    /// Seed for the random number generator. Runs with the same seed draw the same picture.
+    #[arg(long)]
+    pub seed: Option<u64>,
+}
+
+impl SeedArgs {
+    pub fn rng(&self) -> ChaCha8Rng {
+        seeded_rng(self.seed)
+    }
 }
@@ -1,5 +1,10 @@
-use std::iter::{self};
+// Draws the Cantor set. With --svg it is written to a vector image instead of shown:
+//
+//   cargo run --bin=cantor -- --svg cantor.svg
 
+use std::path::PathBuf;
+
+use clap::Parser;
 use glium::glutin::dpi::LogicalSize;
 use glium::glutin::event::{Event, WindowEvent};
 use glium::glutin::event_loop::{ControlFlow, EventLoop};
@@ -7,43 +12,36 @@ use glium::glutin::window::WindowBuilder;
 use glium::glutin::ContextBuilder;
 use glium::index::{NoIndices, PrimitiveType};
 use glium::{Display, DrawParameters, Program, Surface, VertexBuffer};
-use rust_fractal_lab::vertex::Vertex;
+use rust_fractal_lab::cantor::cantor;
+use rust_fractal_lab::svg::Svg;
+use rust_fractal_lab::vertex::Line;
 
 use crate::ControlFlow::Wait;
 
-struct Line(Vertex, Vertex);
-
-impl Line {
-    fn into_vertices(self) -> impl Iterator<Item = Vertex> {
-        iter::once(self.0).chain(iter::once(self.1))
-    }
+#[derive(Parser)]
+struct Args {
+    /// Write an SVG file instead of opening a window
+    #[arg(long)]
+    svg: Option<PathBuf>,
 }
 
-fn cantor<L, R>(lines: &mut Vec<Line>, left: L, right: R, depth: u8)
-where
-    L: Into<Vertex>,
-    R: Into<Vertex>,
-{
-    let left = left.into();
-    let right = right.into();
-    lines.push(Line(left, right));
-
-    // Keep track of recursion depth
-    if depth >= 6 {
+fn main() {
+    let args = Args::parse();
+
+    let mut lines = vec![];
+    cantor(&mut lines, [-1.0, 0.95], [1.0, 0.95], 0, 6);
+
+    if let Some(path) = args.svg {
+        let mut svg = Svg::new(768, 768);
+        svg.set_background([1.0, 1.0, 1.0, 1.0]);
+        svg.add_lines(&lines, [0.0, 0.0, 0.0, 1.0], 4.0);
+        svg.save_svg(&path).unwrap_or_else(|e| {
+            eprintln!("failed to write '{}': {}", path.display(), e);
+            std::process::exit(1);
+        });
         return;
     }
 
-    // Shift subsequent lines down a bit
-    let y = left.y() - 0.05;
-    // Calculate third of line segment
-    let delta = (right.x() - left.x()) / 3.0;
-    // Draw left third
-    cantor(lines, [left.x(), y], [left.x() + delta, y], depth + 1);
-    // Draw right third
-    cantor(lines, [right.x() - delta, y], [right.x(), y], depth + 1);
-}
-
-fn main() {
     let event_loop = EventLoop::new();
 
     let wb = WindowBuilder::new()
@@ -54,9 +52,6 @@ fn main() {
 
     let display = Display::new(wb, cb, &event_loop).unwrap();
 
-    let mut lines = vec![];
-    cantor(&mut lines, [-1.0, 0.95], [1.0, 0.95], 0);
-
     let vertices: Vec<_> = lines.into_iter().flat_map(Line::into_vertices).collect();
 
     let vertex_buffer = VertexBuffer::new(&display, &vertices).unwrap();
@@ -0,0 +1,159 @@
+// Estimates the fractal dimension of the built-in fractals:
+//
+//   cargo run --release --bin=dimension -- ifs            (all bundled IFS definitions)
+//   cargo run --release --bin=dimension -- ifs fern
+//   cargo run --release --bin=dimension -- cantor
+//   cargo run --release --bin=dimension -- escape-time    (Mandelbrot set boundary)
+//   cargo run --release --bin=dimension -- escape-time --julia -0.123 0.745
+
+use clap::{Parser, Subcommand};
+use rust_fractal_lab::args::SeedArgs;
+use rust_fractal_lab::cantor::cantor;
+use rust_fractal_lab::dimension::{
+    box_counting, box_counting_mask, correlation, information, sample_lines, similarity_dimension,
+    DimensionEstimate,
+};
+use rust_fractal_lab::escape_time::boundary_mask;
+use rust_fractal_lab::ifs::format::{example_names, IfsDefinition, IfsStep};
+use rust_fractal_lab::ifs::{Bounds, IfsProgram};
+
+#[derive(Parser)]
+struct Args {
+    #[command(subcommand)]
+    fractal: Fractal,
+}
+
+#[derive(Subcommand)]
+enum Fractal {
+    /// Point set sampled from an IFS definition
+    Ifs {
+        /// Path to an .ifs file, or the name of a bundled example. Defaults to all examples.
+        input: Option<String>,
+
+        /// Chaos game iterations per layer, replacing the count in the definition
+        #[arg(short, long, default_value_t = 1_000_000)]
+        iterations: usize,
+
+        /// Number of points used for the correlation dimension
+        #[arg(long, default_value_t = 3000)]
+        correlation_points: usize,
+
+        #[command(flatten)]
+        seed: SeedArgs,
+    },
+    /// Cantor set line segments
+    Cantor {
+        #[arg(short, long, default_value_t = 12)]
+        depth: u8,
+    },
+    /// Boundary of the Mandelbrot set or a filled Julia set
+    EscapeTime {
+        /// Real and imaginary part of c for a Julia set
+        #[arg(long, num_args = 2, allow_negative_numbers = true)]
+        julia: Option<Vec<f64>>,
+
+        #[arg(long, default_value_t = 1024)]
+        size: u32,
+
+        #[arg(long, default_value_t = 1024)]
+        max_iterations: u32,
+    },
+}
+
+fn print_estimate(method: &str, estimate: Option<DimensionEstimate>) {
+    match estimate {
+        Some(estimate) => println!("  {:<12} {}", method, estimate),
+        None => println!("  {:<12} not enough data", method),
+    }
+}
+
+fn ifs(name: &str, iterations: usize, correlation_points: usize, seed: &SeedArgs) {
+    let mut definition = IfsDefinition::load(name).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    });
+
+    for step in &mut definition.steps {
+        if let IfsStep::Sample(layer) = step {
+            layer.iterations = iterations;
+        }
+    }
+
+    let mut program = IfsProgram::with_rng(seed.rng());
+    definition.apply(&mut program);
+    let points = program.points();
+
+    println!("{} ({} points)", name, points.len());
+    print_estimate("box", box_counting(&points, None));
+    print_estimate("information", information(&points, None));
+    print_estimate("correlation", correlation(&points, correlation_points));
+
+    // The Moran equation only applies to a single self-similar layer
+    let layers: Vec<_> = definition.layers().collect();
+    if let [layer] = layers[..] {
+        if let Some(dimension) = similarity_dimension(&layer.maps) {
+            println!("  {:<12} {:.4}", "similarity", dimension);
+        }
+    }
+}
+
+fn main() {
+    let args = Args::parse();
+
+    match args.fractal {
+        Fractal::Ifs {
+            input,
+            iterations,
+            correlation_points,
+            seed,
+        } => {
+            let names = input.map(|name| vec![name]).unwrap_or_else(example_names);
+            for name in names {
+                ifs(&name, iterations, correlation_points, &seed);
+            }
+        }
+        Fractal::Cantor { depth } => {
+            let mut lines = vec![];
+            cantor(&mut lines, [0.0, 0.0], [1.0, 0.0], 0, depth);
+
+            // Only the last level is (an approximation of) the Cantor set itself
+            let bottom = lines.iter().map(|l| l.0.y()).fold(f32::MAX, f32::min);
+            lines.retain(|l| l.0.y() == bottom);
+
+            let segment_length = 3_f32.powi(-(depth as i32));
+            let points = sample_lines(&lines, segment_length / 2.0);
+
+            println!("Cantor set, depth {} ({} segments)", depth, lines.len());
+            print_estimate("box", box_counting(&points, None));
+            println!("  {:<12} {:.4}", "similarity", 2_f64.ln() / 3_f64.ln());
+        }
+        Fractal::EscapeTime {
+            julia,
+            size,
+            max_iterations,
+        } => {
+            let julia = julia.map(|c| (c[0], c[1]));
+            let bounds = match julia {
+                Some(_) => Bounds {
+                    x_min: -2.0,
+                    x_max: 2.0,
+                    y_min: -2.0,
+                    y_max: 2.0,
+                },
+                None => Bounds {
+                    x_min: -2.0,
+                    x_max: 1.0,
+                    y_min: -1.5,
+                    y_max: 1.5,
+                },
+            };
+
+            let mask = boundary_mask(size, size, bounds, julia, max_iterations);
+            match julia {
+                Some((re, im)) => println!("Julia set boundary, c = {} + {}i", re, im),
+                None => println!("Mandelbrot set boundary"),
+            }
+            print_estimate("box", box_counting_mask(&mask, size, size));
+        }
+    }
+}
@@ -0,0 +1,174 @@
+// Renders an IFS definition as a fractal flame and saves it as a PNG:
+//
+//   cargo run --bin=flame -- sierpinski -v swirl:0.6 -v linear:0.4 --final-variation spherical
+//
+// Variations are given as name:weight and apply to every transform of the first layer. Prefixed
+// with the index of a map, starting at 0, they apply to that map's transform only and replace
+// the shared ones there. --color-index moves a transform within the palette:
+//
+//   cargo run --bin=flame -- sierpinski -v swirl:0.6 -v 1=horseshoe -v 2=polar:0.5 \
+//       --color-index 0=0.2
+
+use std::path::PathBuf;
+
+use clap::Parser;
+use rust_fractal_lab::args::{ColorScheme, SeedArgs};
+use rust_fractal_lab::ifs::flame::{Flame, FlameTransform, Variation};
+use rust_fractal_lab::ifs::format::IfsDefinition;
+
+#[derive(Parser)]
+struct Args {
+    /// Path to an .ifs file, or the name of a bundled example
+    input: String,
+
+    /// Variation and weight, e.g. swirl:0.5, optionally for a single transform, e.g.
+    /// 2=swirl:0.5. May be repeated. Defaults to linear:1.
+    #[arg(short, long = "variation", value_parser = parse_transform_variation)]
+    variations: Vec<(Option<usize>, (Variation, f32))>,
+
+    /// Variation applied by a final transform before plotting
+    #[arg(long, value_parser = parse_variation)]
+    final_variation: Option<(Variation, f32)>,
+
+    /// Position of a transform in the palette, between 0 and 1, e.g. 2=0.75. May be repeated.
+    /// Spread evenly over the palette by default.
+    #[arg(long = "color-index", value_parser = parse_color_index)]
+    color_indices: Vec<(usize, f32)>,
+
+    #[arg(value_enum, default_value_t = ColorScheme::Inferno, short, long)]
+    color_scheme: ColorScheme,
+
+    #[arg(long, default_value_t = 4.0)]
+    gamma: f32,
+
+    #[arg(long, default_value_t = 1.0)]
+    vibrancy: f32,
+
+    #[arg(long, default_value_t = 4.0)]
+    brightness: f32,
+
+    #[arg(short, long, default_value_t = 5_000_000)]
+    iterations: usize,
+
+    #[arg(long, default_value_t = 768)]
+    width: u32,
+
+    #[arg(long, default_value_t = 768)]
+    height: u32,
+
+    #[arg(short, long, default_value = "flame.png")]
+    output: PathBuf,
+
+    #[command(flatten)]
+    seed: SeedArgs,
+}
+
+fn parse_variation(s: &str) -> Result<(Variation, f32), String> {
+    let (name, weight) = s.split_once(':').unwrap_or((s, "1"));
+    let variation = name
+        .parse()
+        .map_err(|_| format!("unknown variation '{}'", name))?;
+    let weight = weight
+        .parse()
+        .map_err(|_| format!("invalid weight '{}'", weight))?;
+    Ok((variation, weight))
+}
+
+fn parse_transform_variation(s: &str) -> Result<(Option<usize>, (Variation, f32)), String> {
+    match s.split_once('=') {
+        Some((transform, variation)) => {
+            let transform = transform
+                .parse()
+                .map_err(|_| format!("invalid transform index '{}'", transform))?;
+            Ok((Some(transform), parse_variation(variation)?))
+        }
+        None => Ok((None, parse_variation(s)?)),
+    }
+}
+
+fn parse_color_index(s: &str) -> Result<(usize, f32), String> {
+    let (transform, color) = s
+        .split_once('=')
+        .ok_or_else(|| format!("expected <transform>=<color>, found '{}'", s))?;
+    let transform = transform
+        .parse()
+        .map_err(|_| format!("invalid transform index '{}'", transform))?;
+    let color: f32 = color
+        .parse()
+        .map_err(|_| format!("invalid color index '{}'", color))?;
+    if !(0.0..=1.0).contains(&color) {
+        return Err(format!("color index {} is not between 0 and 1", color));
+    }
+    Ok((transform, color))
+}
+
+fn main() {
+    let args = Args::parse();
+
+    let definition = IfsDefinition::load(&args.input).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    });
+    let layer = definition.layers().next().unwrap();
+
+    let mut flame = Flame::from_matrix(&layer.maps).unwrap_or_else(|e| {
+        eprintln!("invalid IFS: {}", e);
+        std::process::exit(1);
+    });
+
+    let count = flame.transforms.len();
+    let indices = args
+        .variations
+        .iter()
+        .filter_map(|(transform, _)| *transform);
+    for transform in indices.chain(args.color_indices.iter().map(|(transform, _)| *transform)) {
+        if transform >= count {
+            eprintln!(
+                "transform {} does not exist, the IFS has {} map(s)",
+                transform, count
+            );
+            std::process::exit(1);
+        }
+    }
+
+    let shared: Vec<(Variation, f32)> = args
+        .variations
+        .iter()
+        .filter(|(transform, _)| transform.is_none())
+        .map(|&(_, variation)| variation)
+        .collect();
+    for (i, transform) in flame.transforms.iter_mut().enumerate() {
+        let own: Vec<(Variation, f32)> = args
+            .variations
+            .iter()
+            .filter(|(t, _)| *t == Some(i))
+            .map(|&(_, variation)| variation)
+            .collect();
+        if !own.is_empty() {
+            transform.variations = own;
+        } else if !shared.is_empty() {
+            transform.variations = shared.clone();
+        }
+    }
+    for &(transform, color) in &args.color_indices {
+        flame.transforms[transform].color = color;
+    }
+    flame.final_transform = args.final_variation.map(|variation| FlameTransform {
+        coefs: [1.0, 0.0, 0.0, 1.0, 0.0, 0.0],
+        color: 0.5,
+        variations: vec![variation],
+    });
+    flame.palette = args.color_scheme;
+    flame.gamma = args.gamma;
+    flame.vibrancy = args.vibrancy;
+    flame.brightness = args.brightness;
+
+    let mut rng = args.seed.rng();
+    let bounds = flame.estimate_bounds(&mut rng, 100_000);
+    let image = flame.render(&mut rng, args.width, args.height, args.iterations, bounds);
+
+    image.save_png(&args.output).unwrap_or_else(|e| {
+        eprintln!("failed to write '{}': {}", args.output.display(), e);
+        std::process::exit(1);
+    });
+}
@@ -1,11 +1,36 @@
-use rust_fractal_lab::forest::draw_forest;
+use clap::Parser;
+use rust_fractal_lab::args::SeedArgs;
+use rust_fractal_lab::forest::{ForestConfig, Haze};
 use rust_fractal_lab::ifs::IfsProgram;
 
+#[derive(Parser)]
+struct Args {
+    #[arg(long, default_value_t = 150)]
+    trees: u32,
+
+    /// How far the smallest trees fade into the background, from 0 to 1
+    #[arg(long, default_value_t = 0.0)]
+    haze: f32,
+
+    #[command(flatten)]
+    seed: SeedArgs,
+}
+
 fn main() {
+    let args = Args::parse();
+
     let mut program = IfsProgram::default();
-    let mut rng = rand::thread_rng();
+    let mut rng = args.seed.rng();
 
-    draw_forest(&mut program, &mut rng, 150);
+    let forest = ForestConfig {
+        trees: args.trees,
+        haze: (args.haze > 0.0).then_some(Haze {
+            color: [1.0, 1.0, 1.0, 1.0],
+            strength: args.haze,
+        }),
+        ..ForestConfig::default()
+    };
+    forest.draw(&mut program, &mut rng);
 
     program.run(Some(1.5));
 }
@@ -1,8 +1,18 @@
+use clap::Parser;
 use ndarray::{array, Array, Ix2};
 use rand::Rng;
+use rust_fractal_lab::args::SeedArgs;
 use rust_fractal_lab::ifs::IfsProgram;
 
+#[derive(Parser)]
+struct Args {
+    #[command(flatten)]
+    seed: SeedArgs,
+}
+
 fn main() {
+    let args = Args::parse();
+
     let d: Array<f32, Ix2> = array![
         [0.33, 0.0, 0.0, 0.33, 1.0, 1.0, 0.2],
         [0.33, 0.0, 0.0, 0.33, 10.0, 1.0, 0.2],
@@ -14,14 +24,22 @@ fn main() {
     let mut program = IfsProgram::default();
     // Black background
     program.set_clear_color((0.0, 0.0, 0.0, 1.0));
-    let mut rng = rand::thread_rng();
+    let mut rng = args.seed.rng();
 
     for _ in 0..150 {
         let shift_x = rng.gen_range(-2000.0..2000.0);
         let shift_y = rng.gen_range(-2000.0..2000.0);
         let scale = rng.gen_range(0.1..1.0);
 
-        program.sample_affine(&d, [0.5, 0.5, 0.5, 1.0], 2000, scale, shift_x, shift_y);
+        program.sample_affine_with_rng(
+            &mut rng,
+            &d,
+            [0.5, 0.5, 0.5, 1.0],
+            2000,
+            scale,
+            shift_x,
+            shift_y,
+        );
     }
 
     program.run(Some(1.3));
@@ -0,0 +1,214 @@
+// Fractal image compression (see `src/ifs/compression.rs`). Encoding stores a grayscale image as
+// a partitioned IFS and reports the compression ratio and quality:
+//
+//   cargo run --release --bin=ifs-compress -- encode photo.png photo.pifs
+//   cargo run --release --bin=ifs-compress -- encode photo.png photo.pifs --range-size 4
+//
+// Decoding iterates the maps from a gray image, or from any other one with --start. With
+// --reference it prints the quality after every iteration, and --frames saves them all to show
+// the convergence:
+//
+//   cargo run --release --bin=ifs-compress -- decode photo.pifs decoded.png
+//   cargo run --release --bin=ifs-compress -- decode photo.pifs decoded.png --start cat.png \
+//       --reference photo.png --frames frames
+
+use std::path::{Path, PathBuf};
+
+use clap::{Parser, Subcommand};
+use rust_fractal_lab::ifs::compression::{
+    gray_from_image, gray_to_image, psnr, Encoder, FractalCode, Gray,
+};
+use rust_fractal_lab::image::Image;
+use rust_fractal_lab::utils::parallel::available_threads;
+
+#[derive(Parser)]
+struct Args {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Compress a PNG image, which is converted to grayscale
+    Encode {
+        input: PathBuf,
+        output: PathBuf,
+
+        /// Side of the range blocks in pixels
+        #[arg(long, default_value_t = 8)]
+        range_size: usize,
+
+        /// Distance between domain blocks in pixels, twice the range size by default. Smaller
+        /// steps find better matches but take much longer
+        #[arg(long)]
+        domain_step: Option<usize>,
+
+        /// Largest contrast, between 0 and 1 so that decoding converges
+        #[arg(long, default_value_t = 0.9)]
+        max_contrast: f32,
+
+        #[arg(long, default_value_t = 5)]
+        contrast_bits: u8,
+
+        #[arg(long, default_value_t = 7)]
+        brightness_bits: u8,
+
+        /// Decoder iterations for the reported quality
+        #[arg(short, long, default_value_t = 10)]
+        iterations: usize,
+
+        #[arg(long, default_value_t = available_threads())]
+        threads: usize,
+    },
+    /// Decompress to a PNG image
+    Decode {
+        input: PathBuf,
+        output: PathBuf,
+
+        #[arg(short, long, default_value_t = 10)]
+        iterations: usize,
+
+        /// Image to start iterating from instead of a uniform gray one
+        #[arg(long)]
+        start: Option<PathBuf>,
+
+        /// Original image to compare every iteration with
+        #[arg(long)]
+        reference: Option<PathBuf>,
+
+        /// Directory to save the image after every iteration in
+        #[arg(long)]
+        frames: Option<PathBuf>,
+    },
+}
+
+fn load_gray(path: &Path) -> Gray {
+    let image = Image::load_png(path).unwrap_or_else(|e| {
+        eprintln!("failed to read '{}': {}", path.display(), e);
+        std::process::exit(1);
+    });
+    gray_from_image(&image)
+}
+
+fn save_gray(gray: &Gray, path: &Path) {
+    gray_to_image(gray).save_png(path).unwrap_or_else(|e| {
+        eprintln!("failed to write '{}': {}", path.display(), e);
+        std::process::exit(1);
+    });
+}
+
+fn main() {
+    let args = Args::parse();
+
+    match args.command {
+        Command::Encode {
+            input,
+            output,
+            range_size,
+            domain_step,
+            max_contrast,
+            contrast_bits,
+            brightness_bits,
+            iterations,
+            threads,
+        } => {
+            if !(1..=255).contains(&range_size) || domain_step.is_some_and(|s| s == 0 || s > 255) {
+                eprintln!("range size and domain step must be between 1 and 255");
+                std::process::exit(1);
+            }
+            if !(2..=16).contains(&contrast_bits) || !(2..=16).contains(&brightness_bits) {
+                eprintln!("contrast and brightness need 2 to 16 bits");
+                std::process::exit(1);
+            }
+            if !(max_contrast > 0.0 && max_contrast < 1.0) {
+                eprintln!("max contrast must be between 0 and 1");
+                std::process::exit(1);
+            }
+
+            let image = load_gray(&input);
+            let encoder = Encoder {
+                range_size,
+                domain_step: domain_step.unwrap_or(2 * range_size),
+                max_contrast,
+                contrast_bits,
+                brightness_bits,
+                threads,
+            };
+            let code = encoder.encode(&image);
+            code.save(&output).unwrap_or_else(|e| {
+                eprintln!("failed to write '{}': {}", output.display(), e);
+                std::process::exit(1);
+            });
+
+            let size = code.encoded_size();
+            let raw = code.width * code.height;
+            println!(
+                "{} range blocks, {} domain blocks",
+                code.range_count(),
+                code.domain_count()
+            );
+            println!(
+                "size: {} bytes, {:.2} bits per pixel, compression ratio {:.1}:1",
+                size,
+                8.0 * size as f64 / raw as f64,
+                raw as f64 / size as f64
+            );
+            println!(
+                "collage PSNR: {:.2} dB",
+                psnr(&code.collage(&image), &image)
+            );
+            println!(
+                "decoded PSNR after {} iterations: {:.2} dB",
+                iterations,
+                psnr(&code.decode(iterations), &image)
+            );
+        }
+        Command::Decode {
+            input,
+            output,
+            iterations,
+            start,
+            reference,
+            frames,
+        } => {
+            let code = FractalCode::load(&input).unwrap_or_else(|e| {
+                eprintln!("failed to read '{}': {}", input.display(), e);
+                std::process::exit(1);
+            });
+            let start = match start {
+                Some(path) => load_gray(&path),
+                None => Gray::from_elem((code.height, code.width), 0.5),
+            };
+            let reference = reference.map(|path| {
+                let reference = load_gray(&path);
+                if reference.dim() != (code.height, code.width) {
+                    eprintln!("the reference image differs in size");
+                    std::process::exit(1);
+                }
+                reference
+            });
+            if let Some(dir) = &frames {
+                std::fs::create_dir_all(dir).unwrap_or_else(|e| {
+                    eprintln!("failed to create '{}': {}", dir.display(), e);
+                    std::process::exit(1);
+                });
+            }
+
+            let mut decoded = None;
+            for (i, image) in code.iterations(&start).take(iterations.max(1)).enumerate() {
+                if let Some(reference) = &reference {
+                    println!(
+                        "iteration {:>3}: PSNR {:.2} dB",
+                        i + 1,
+                        psnr(&image, reference)
+                    );
+                }
+                if let Some(dir) = &frames {
+                    save_gray(&image, &dir.join(format!("frame-{:03}.png", i + 1)));
+                }
+                decoded = Some(image);
+            }
+            save_gray(&decoded.unwrap(), &output);
+        }
+    }
+}
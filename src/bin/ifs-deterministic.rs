@@ -0,0 +1,195 @@
+// Draws an IFS definition with the deterministic algorithm: starting from a seed shape, every
+// generation is the union of the images of the previous one under all maps.
+//
+//   cargo run --release --bin=ifs-deterministic -- sierpinski --generations 6
+//   cargo run --release --bin=ifs-deterministic -- fern --seed-shape point --generation 3
+//
+// With --png, every generation is saved to its own file (fern-0.png, fern-1.png, ...), which is
+// how the step by step convergence figures are made:
+//
+//   cargo run --release --bin=ifs-deterministic -- fern --generations 10 --png fern.png
+
+use std::path::{Path, PathBuf};
+
+use clap::{Parser, ValueEnum};
+use rust_fractal_lab::args::SeedArgs;
+use rust_fractal_lab::ifs::deterministic::{raster_generations, Raster};
+use rust_fractal_lab::ifs::format::{IfsDefinition, IfsLayer, IfsStep};
+use rust_fractal_lab::ifs::{Bounds, IfsProgram};
+
+#[derive(Clone, Copy, ValueEnum)]
+enum SeedShape {
+    /// The whole bounding box of the attractor
+    Square,
+    /// A single point in the middle of the bounding box
+    Point,
+}
+
+#[derive(Parser)]
+struct Args {
+    /// Path to an .ifs file, or the name of a bundled example
+    input: String,
+
+    #[arg(short, long, default_value_t = 8)]
+    generations: usize,
+
+    /// Generation to show in the viewer. Defaults to the last one.
+    #[arg(long)]
+    generation: Option<usize>,
+
+    #[arg(long, value_enum, default_value_t = SeedShape::Square)]
+    seed_shape: SeedShape,
+
+    /// Cells per side of the grid the shape is kept on
+    #[arg(long, default_value_t = 512)]
+    resolution: usize,
+
+    /// Save every generation to a numbered PNG file instead of opening a window
+    #[arg(long)]
+    png: Option<PathBuf>,
+
+    #[arg(long, default_value_t = 768)]
+    width: u32,
+
+    #[arg(long, default_value_t = 768)]
+    height: u32,
+
+    #[command(flatten)]
+    seed: SeedArgs,
+}
+
+/// Generations of one layer. The grid covers the attractor as found by a short chaos game.
+fn layer_generations(layer: &IfsLayer, args: &Args) -> Vec<Raster> {
+    let mut program = IfsProgram::with_rng(args.seed.rng());
+    program.sample(&layer.maps, layer.color, 20000);
+    let bounds = Bounds::from_points_trimmed(&program.points(), 1.0)
+        .unwrap()
+        .padded(0.05);
+
+    let seed = match args.seed_shape {
+        SeedShape::Square => Raster::filled(bounds, args.resolution, args.resolution),
+        SeedShape::Point => Raster::from_points(
+            &[[
+                (bounds.x_min + bounds.x_max) / 2.0,
+                (bounds.y_min + bounds.y_max) / 2.0,
+            ]],
+            bounds,
+            args.resolution,
+            args.resolution,
+        ),
+    };
+
+    raster_generations(&layer.maps, seed, args.generations)
+}
+
+/// Same as [`IfsDefinition::apply`], but with each layer replaced by one of its generations.
+/// Points are placed within the bounds of the grids rather than their own, so that every
+/// generation is drawn at the same scale and position.
+fn program_for_generation(
+    definition: &IfsDefinition,
+    generations: &[Vec<Raster>],
+    generation: usize,
+) -> IfsProgram {
+    let mut program = IfsProgram::default();
+    if let Some(color) = definition.clear_color {
+        program.set_clear_color((color[0], color[1], color[2], color[3]));
+    }
+
+    let mut layers = definition.layers().zip(generations);
+    // Grid bounds of the layers since the last normalization
+    let mut group: Option<Bounds> = None;
+    for step in &definition.steps {
+        match step {
+            IfsStep::Sample(_) => {
+                let (layer, generations) = layers.next().unwrap();
+                let transform = |x: f32, y: f32| {
+                    (
+                        (x + layer.shift_x) * layer.scale,
+                        (y + layer.shift_y) * layer.scale,
+                    )
+                };
+
+                // Layers that converged early stay at their last generation
+                let raster = &generations[generation.min(generations.len() - 1)];
+                let grid = raster.bounds();
+                let (x0, y0) = transform(grid.x_min, grid.y_min);
+                let (x1, y1) = transform(grid.x_max, grid.y_max);
+                let grid = Bounds {
+                    x_min: x0.min(x1),
+                    x_max: x0.max(x1),
+                    y_min: y0.min(y1),
+                    y_max: y0.max(y1),
+                };
+                let bounds = group.map_or(grid, |group| group.union(&grid));
+                program.set_bounds(bounds);
+                group = Some(bounds);
+
+                let points: Vec<[f32; 2]> = raster
+                    .points()
+                    .into_iter()
+                    .map(|[x, y]| {
+                        let (x, y) = transform(x, y);
+                        [x, y]
+                    })
+                    .collect();
+                program.add_points(&points, layer.color);
+            }
+            IfsStep::Normalize {
+                x_min,
+                x_max,
+                y_min,
+                y_max,
+            } => {
+                program.normalize_points_to_ranges(*x_min, *x_max, *y_min, *y_max);
+                group = None;
+            }
+        }
+    }
+
+    program
+}
+
+fn numbered_path(path: &Path, generation: usize) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let extension = path.extension().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!("{}-{}.{}", stem, generation, extension))
+}
+
+fn main() {
+    let args = Args::parse();
+
+    let definition = IfsDefinition::load(&args.input).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    });
+
+    let generations: Vec<Vec<Raster>> = definition
+        .layers()
+        .map(|layer| layer_generations(layer, &args))
+        .collect();
+    let count = generations.iter().map(|g| g.len()).max().unwrap_or(1);
+
+    for generation in 0..count {
+        let cells: usize = generations
+            .iter()
+            .map(|g| g[generation.min(g.len() - 1)].count())
+            .sum();
+        println!("generation {}: {} cells", generation, cells);
+    }
+
+    if let Some(path) = &args.png {
+        for generation in 0..count {
+            let mut program = program_for_generation(&definition, &generations, generation);
+            let image = program.render(args.width, args.height, definition.point_size);
+            let path = numbered_path(path, generation);
+            image.save_png(&path).unwrap_or_else(|e| {
+                eprintln!("failed to write '{}': {}", path.display(), e);
+                std::process::exit(1);
+            });
+        }
+        return;
+    }
+
+    let generation = args.generation.unwrap_or(count - 1);
+    program_for_generation(&definition, &generations, generation).run(definition.point_size);
+}
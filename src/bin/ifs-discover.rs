@@ -0,0 +1,119 @@
+// Random IFS search: tries many random sets of contractions and keeps the ones whose attractors
+// look interesting, rejecting blobs, lines and dust (see `src/ifs/discover.rs`). The best
+// candidates are written to a directory as .ifs files with PNG thumbnails next to them:
+//
+//   cargo run --release --bin=ifs-discover -- --candidates 5000 --keep 12 --output found
+//   cargo run --release --bin=ifs found/candidate-01.ifs
+
+use std::path::PathBuf;
+
+use clap::Parser;
+use rust_fractal_lab::args::SeedArgs;
+use rust_fractal_lab::ifs::discover::AttractorSearch;
+use rust_fractal_lab::ifs::format::{IfsDefinition, IfsLayer, IfsStep};
+use rust_fractal_lab::ifs::IfsProgram;
+
+#[derive(Parser)]
+struct Args {
+    /// Number of random IFS to try
+    #[arg(short, long, default_value_t = 1000)]
+    candidates: usize,
+
+    /// Number of best candidates to write
+    #[arg(short, long, default_value_t = 8)]
+    keep: usize,
+
+    #[arg(long, default_value_t = 2)]
+    min_maps: usize,
+
+    #[arg(long, default_value_t = 4)]
+    max_maps: usize,
+
+    /// Attractors with a lower box counting dimension are rejected as dust
+    #[arg(long, default_value_t = 1.1)]
+    min_dimension: f32,
+
+    /// Attractors with a higher box counting dimension are rejected as blobs
+    #[arg(long, default_value_t = 1.9)]
+    max_dimension: f32,
+
+    /// Directory for the .ifs files and thumbnails
+    #[arg(short, long, default_value = "discovered")]
+    output: PathBuf,
+
+    /// Side of the thumbnails in pixels, 0 to skip them
+    #[arg(long, default_value_t = 256)]
+    thumbnail: u32,
+
+    /// Number of threads evaluating candidates
+    #[arg(long, default_value_t = 1)]
+    threads: usize,
+
+    #[command(flatten)]
+    seed: SeedArgs,
+}
+
+fn main() {
+    let args = Args::parse();
+
+    let search = AttractorSearch {
+        candidates: args.candidates,
+        keep: args.keep,
+        min_maps: args.min_maps,
+        max_maps: args.max_maps,
+        min_dimension: args.min_dimension,
+        max_dimension: args.max_dimension,
+        threads: args.threads,
+        ..AttractorSearch::default()
+    };
+    let mut rng = args.seed.rng();
+    let found = search.search(&mut rng);
+    if found.is_empty() {
+        eprintln!("none of the candidates passed, try more of them");
+        std::process::exit(1);
+    }
+
+    std::fs::create_dir_all(&args.output).unwrap_or_else(|e| {
+        eprintln!("failed to create '{}': {}", args.output.display(), e);
+        std::process::exit(1);
+    });
+
+    println!("rank  maps  dimension  coverage  thickness  score");
+    for (i, candidate) in found.iter().enumerate() {
+        let stats = candidate.stats;
+        println!(
+            "{:>4}  {:>4}  {:>9.3}  {:>8.3}  {:>9.3}  {:>5.3}",
+            i + 1,
+            candidate.maps.nrows(),
+            stats.dimension,
+            stats.coverage,
+            stats.thickness,
+            stats.score()
+        );
+
+        let definition = IfsDefinition {
+            point_size: None,
+            clear_color: None,
+            steps: vec![IfsStep::Sample(IfsLayer {
+                maps: candidate.maps.clone(),
+                ..IfsLayer::default()
+            })],
+        };
+        let path = args.output.join(format!("candidate-{:02}.ifs", i + 1));
+        std::fs::write(&path, definition.to_string()).unwrap_or_else(|e| {
+            eprintln!("failed to write '{}': {}", path.display(), e);
+            std::process::exit(1);
+        });
+
+        if args.thumbnail > 0 {
+            let mut program = IfsProgram::with_rng(args.seed.rng());
+            definition.apply(&mut program);
+            let image = program.render(args.thumbnail, args.thumbnail, None);
+            let path = path.with_extension("png");
+            image.save_png(&path).unwrap_or_else(|e| {
+                eprintln!("failed to write '{}': {}", path.display(), e);
+                std::process::exit(1);
+            });
+        }
+    }
+}
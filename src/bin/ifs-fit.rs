@@ -0,0 +1,111 @@
+// Inverse IFS: searches for maps whose attractor looks like the shape in a PNG image, using the
+// collage theorem (see `src/ifs/inverse.rs`). Dark pixels are the shape, use --invert for light
+// shapes on a dark background. The result is printed in the .ifs format:
+//
+//   cargo run --release --bin=ifs-fit -- leaf.png --maps 4 --output leaf.ifs --png leaf-fit.png
+//   cargo run --release --bin=ifs leaf.ifs
+
+use std::path::PathBuf;
+
+use clap::builder::RangedU64ValueParser;
+use clap::Parser;
+use rust_fractal_lab::args::SeedArgs;
+use rust_fractal_lab::ifs::format::{IfsDefinition, IfsLayer, IfsStep};
+use rust_fractal_lab::ifs::inverse::{mask_from_image, CollageMetric, InverseSolver};
+use rust_fractal_lab::ifs::IfsProgram;
+use rust_fractal_lab::image::Image;
+
+#[derive(Parser)]
+struct Args {
+    /// Target image
+    input: PathBuf,
+
+    /// Number of maps to fit
+    #[arg(short, long, default_value_t = 4)]
+    #[arg(value_parser = RangedU64ValueParser::<usize>::new().range(1..))]
+    maps: usize,
+
+    #[arg(long, value_enum, default_value_t = CollageMetric::Pixel)]
+    metric: CollageMetric,
+
+    /// Annealing steps per restart
+    #[arg(long, default_value_t = 20000)]
+    steps: usize,
+
+    #[arg(long, default_value_t = 4)]
+    restarts: usize,
+
+    /// The target is downsampled to at most this many pixels per side while searching
+    #[arg(long, default_value_t = 64)]
+    resolution: usize,
+
+    /// Treat light pixels as the shape instead of dark ones
+    #[arg(long)]
+    invert: bool,
+
+    /// Write the definition to a file instead of printing it
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+
+    /// Render the attractor of the result to a PNG file
+    #[arg(long)]
+    png: Option<PathBuf>,
+
+    #[command(flatten)]
+    seed: SeedArgs,
+}
+
+fn main() {
+    let args = Args::parse();
+
+    let image = Image::load_png(&args.input).unwrap_or_else(|e| {
+        eprintln!("failed to read '{}': {}", args.input.display(), e);
+        std::process::exit(1);
+    });
+    let target = mask_from_image(&image, args.invert);
+    if !target.iter().any(|&set| set) {
+        eprintln!("the image contains no shape, try --invert");
+        std::process::exit(1);
+    }
+
+    let solver = InverseSolver {
+        maps: args.maps,
+        steps: args.steps,
+        restarts: args.restarts,
+        metric: args.metric,
+        resolution: args.resolution,
+        ..InverseSolver::default()
+    };
+    let fit = solver.fit(&target, &mut args.seed.rng());
+    eprintln!(
+        "collage error ({}): {:.4}, contraction: {:.3}",
+        args.metric, fit.error, fit.contraction
+    );
+
+    let definition = IfsDefinition {
+        point_size: None,
+        clear_color: None,
+        steps: vec![IfsStep::Sample(IfsLayer {
+            maps: fit.maps,
+            ..IfsLayer::default()
+        })],
+    };
+
+    match &args.output {
+        Some(path) => std::fs::write(path, definition.to_string()).unwrap_or_else(|e| {
+            eprintln!("failed to write '{}': {}", path.display(), e);
+            std::process::exit(1);
+        }),
+        None => print!("{}", definition),
+    }
+
+    if let Some(path) = args.png {
+        let mut program = IfsProgram::with_rng(args.seed.rng());
+        definition.apply(&mut program);
+        let image = program.render(image.width(), image.height(), definition.point_size);
+        image.save_png(&path).unwrap_or_else(|e| {
+            eprintln!("failed to write '{}': {}", path.display(), e);
+            std::process::exit(1);
+        });
+    }
+}
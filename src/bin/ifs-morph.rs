@@ -0,0 +1,155 @@
+// Morphs between IFS definitions by interpolating their maps, and renders the frames on the CPU.
+// Takes two or more definitions, by name or path like the `ifs` bin, and uses the first layer of
+// each. The frames are written as an animated GIF, or as numbered PNG files into a directory:
+//
+//   cargo run --release --bin=ifs-morph -- sierpinski fern --gif morph.gif
+//   cargo run --release --bin=ifs-morph -- sierpinski fern tree --frames 120 --png-dir frames
+//
+// --interpolation polar interpolates the rotation of each map separately from its scale and
+// shear, so maps turn instead of shrinking and growing again on the way:
+//
+//   cargo run --release --bin=ifs-morph -- tree fern --interpolation polar --gif morph.gif
+
+use std::path::PathBuf;
+
+use clap::Parser;
+use rust_fractal_lab::args::SeedArgs;
+use rust_fractal_lab::ifs::format::IfsDefinition;
+use rust_fractal_lab::ifs::morph::{Interpolation, Morph};
+use rust_fractal_lab::ifs::IfsProgram;
+use rust_fractal_lab::image::GifWriter;
+
+#[derive(Parser)]
+struct Args {
+    /// Paths to .ifs files, or names of bundled examples, in the order they are shown
+    #[arg(required = true, num_args = 2..)]
+    inputs: Vec<String>,
+
+    #[arg(long, default_value_t = 60)]
+    frames: usize,
+
+    #[arg(value_enum, long, default_value_t = Interpolation::Linear)]
+    interpolation: Interpolation,
+
+    /// Write an animated GIF
+    #[arg(long, required_unless_present = "png_dir")]
+    gif: Option<PathBuf>,
+
+    /// Write every frame as frame-0000.png, frame-0001.png... into this directory
+    #[arg(long)]
+    png_dir: Option<PathBuf>,
+
+    /// Time every frame is shown in the GIF, in milliseconds
+    #[arg(long, default_value_t = 50)]
+    delay: u32,
+
+    /// Play the morph backwards after it ends, so the GIF loops smoothly
+    #[arg(long)]
+    bounce: bool,
+
+    /// Iterations per frame, instead of the ones in the definitions
+    #[arg(long)]
+    iterations: Option<usize>,
+
+    #[arg(long, default_value_t = 512)]
+    width: u32,
+
+    #[arg(long, default_value_t = 512)]
+    height: u32,
+
+    /// Number of threads sampling each frame
+    #[arg(long, default_value_t = 1)]
+    threads: usize,
+
+    #[command(flatten)]
+    seed: SeedArgs,
+}
+
+fn main() {
+    let args = Args::parse();
+
+    let definitions: Vec<IfsDefinition> = args
+        .inputs
+        .iter()
+        .map(|input| {
+            IfsDefinition::load(input).unwrap_or_else(|e| {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            })
+        })
+        .collect();
+
+    let keyframes = definitions
+        .iter()
+        .zip(&args.inputs)
+        .map(|(definition, input)| {
+            let mut layers = definition.layers();
+            let layer = layers.next().unwrap_or_else(|| {
+                eprintln!("{}: definition has no layers", input);
+                std::process::exit(1);
+            });
+            if layers.next().is_some() {
+                eprintln!("warning: {}: only the first layer is morphed", input);
+            }
+            layer.clone()
+        })
+        .collect();
+    let morph = Morph::new(keyframes, args.interpolation);
+
+    // Every frame starts from the same generator, so parts that don't move don't flicker either
+    let mut rng = args.seed.rng();
+    let view = morph.bounds(&mut rng, args.frames, 20_000);
+    let point_size = definitions[0].point_size;
+    let clear_color = definitions[0].clear_color.unwrap_or([1.0, 1.0, 1.0, 1.0]);
+
+    let fail = |path: &PathBuf, e: std::io::Error| -> ! {
+        eprintln!("failed to write '{}': {}", path.display(), e);
+        std::process::exit(1);
+    };
+
+    if let Some(dir) = &args.png_dir {
+        std::fs::create_dir_all(dir).unwrap_or_else(|e| fail(dir, e));
+    }
+    let mut gif = args.gif.as_ref().map(|path| {
+        GifWriter::create(path, args.width, args.height, args.delay)
+            .unwrap_or_else(|e| fail(path, e))
+    });
+
+    let mut order: Vec<usize> = (0..args.frames).collect();
+    if args.bounce {
+        // Leave out both ends, they would be shown twice in a row when looping
+        order.extend((1..args.frames.saturating_sub(1)).rev());
+    }
+
+    for (i, &frame) in order.iter().enumerate() {
+        let layer = morph.at(Morph::frame_time(frame, args.frames));
+        let mut program = IfsProgram::with_rng(rng.clone());
+        program.set_threads(args.threads);
+        program.set_view(view);
+        let [r, g, b, a] = clear_color;
+        program.set_clear_color((r, g, b, a));
+        program.sample_ifs(
+            &layer.system(),
+            layer.color,
+            args.iterations.unwrap_or(layer.iterations),
+            layer.scale,
+            layer.shift_x,
+            layer.shift_y,
+        );
+        let image = program.render(args.width, args.height, point_size);
+
+        if let Some(dir) = &args.png_dir {
+            let path = dir.join(format!("frame-{:04}.png", i));
+            image.save_png(&path).unwrap_or_else(|e| fail(&path, e));
+        }
+        if let (Some(gif), Some(path)) = (&mut gif, &args.gif) {
+            gif.write_frame(&image).unwrap_or_else(|e| fail(path, e));
+        }
+        eprint!("\rframe {}/{}", i + 1, order.len());
+    }
+    eprintln!();
+
+    if let (Some(gif), Some(path)) = (gif, &args.gif) {
+        gif.finish().unwrap_or_else(|e| fail(path, e));
+    }
+}
@@ -0,0 +1,320 @@
+// Generic IFS viewer. Loads a definition in the format described in `src/ifs/format.rs`, either
+// from a file or by the name of one of the bundled examples in `src/ifs/examples`:
+//
+//   cargo run --bin=ifs fern
+//   cargo run --bin=ifs path/to/my-fractal.ifs
+//
+// With --png the fractal is rendered on the CPU and saved to a file instead, no display needed:
+//
+//   cargo run --bin=ifs -- fern --png fern.png --width 1024 --height 1024
+//
+// --svg writes a vector image, --merge keeps it small by writing one point per pixel and color:
+//
+//   cargo run --bin=ifs -- fern --svg fern.svg --merge
+//
+// --color-mode replaces the colors from the definition, e.g. to show which maps produced each
+// part of the attractor or how densely it is visited:
+//
+//   cargo run --bin=ifs -- sierpinski --color-mode map --history 2
+//   cargo run --bin=ifs -- fern --color-mode density --palette inferno
+//
+// --accumulate counts the points in a histogram the size of the window instead of storing them,
+// so billions of iterations fit in memory:
+//
+//   cargo run --release --bin=ifs -- fern --accumulate --iterations 1000000000 --png fern.png
+//
+// --progressive opens the viewer right away and streams points into it until the picture stops
+// changing:
+//
+//   cargo run --release --bin=ifs -- fern --progressive
+//
+// --convert prints the definition with every map written as coefficients or by its scales,
+// rotation, shear and translation:
+//
+//   cargo run --bin=ifs -- tree --convert geometric > tree.ifs
+
+use std::path::PathBuf;
+
+use clap::{Parser, ValueEnum};
+use rust_fractal_lab::args::{ColorScheme, SeedArgs};
+use rust_fractal_lab::ifs::density::{estimate_bounds, DensityGrid, Scaling, ToneMap};
+use rust_fractal_lab::ifs::format::{example_names, IfsDefinition, MapNotation};
+use rust_fractal_lab::ifs::progressive::{Progressive, ProgressiveSampler};
+use rust_fractal_lab::ifs::system::MapSystem;
+use rust_fractal_lab::ifs::{Bounds, ColorMode, IfsProgram};
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Coloring {
+    /// Colors from the definition
+    Uniform,
+    /// By the maps that produced each point
+    Map,
+    /// By how often each pixel is visited
+    Density,
+    /// By the iteration in which each point was produced
+    Age,
+}
+
+#[derive(Parser)]
+struct Args {
+    /// Path to an .ifs file, or the name of a bundled example
+    input: Option<String>,
+
+    /// List the bundled examples and exit
+    #[arg(short, long)]
+    list: bool,
+
+    /// Render to a PNG file instead of opening a window
+    #[arg(long)]
+    png: Option<PathBuf>,
+
+    /// Write an SVG file instead of opening a window
+    #[arg(long, conflicts_with = "png")]
+    svg: Option<PathBuf>,
+
+    /// Only write one point per pixel and color to the SVG file
+    #[arg(long)]
+    merge: bool,
+
+    #[arg(long, default_value_t = 768)]
+    width: u32,
+
+    #[arg(long, default_value_t = 768)]
+    height: u32,
+
+    #[arg(value_enum, long, default_value_t = Coloring::Uniform)]
+    color_mode: Coloring,
+
+    /// Number of most recent maps that determine a point's color with --color-mode map
+    #[arg(long, default_value_t = 1)]
+    history: usize,
+
+    #[arg(value_enum, long, default_value_t = ColorScheme::Viridis)]
+    palette: ColorScheme,
+
+    /// Count the points in a density grid instead of storing every one of them
+    #[arg(long)]
+    accumulate: bool,
+
+    /// Iterations per layer with --accumulate, instead of the ones in the definition
+    #[arg(long)]
+    iterations: Option<u64>,
+
+    /// Only count points within x_min,x_max,y_min,y_max with --accumulate. Computed from the maps
+    /// by default, or estimated from a short run if some map is not a contraction.
+    #[arg(long, value_parser = parse_bounds, allow_hyphen_values = true)]
+    bounds: Option<Bounds>,
+
+    /// Open the viewer right away and keep adding points until the picture stops changing.
+    /// Needs a definition with a single layer.
+    #[arg(long, conflicts_with_all = ["png", "svg", "accumulate"])]
+    progressive: bool,
+
+    /// Most points to sample with --progressive
+    #[arg(long, default_value_t = Progressive::default().budget)]
+    budget: usize,
+
+    /// Print the definition with its maps written in this notation and exit
+    #[arg(value_enum, long)]
+    convert: Option<MapNotation>,
+
+    /// Number of threads running the chaos game
+    #[arg(long, default_value_t = 1)]
+    threads: usize,
+
+    #[arg(value_enum, long, default_value_t = Scaling::Log)]
+    scaling: Scaling,
+
+    #[arg(long, default_value_t = 2.2)]
+    gamma: f32,
+
+    #[command(flatten)]
+    seed: SeedArgs,
+}
+
+fn parse_bounds(s: &str) -> Result<Bounds, String> {
+    let values = s
+        .split(',')
+        .map(|v| v.trim().parse::<f32>().map_err(|e| e.to_string()))
+        .collect::<Result<Vec<_>, _>>()?;
+    match values[..] {
+        [x_min, x_max, y_min, y_max] if x_min < x_max && y_min < y_max => Ok(Bounds {
+            x_min,
+            x_max,
+            y_min,
+            y_max,
+        }),
+        _ => Err(String::from("expected x_min,x_max,y_min,y_max")),
+    }
+}
+
+/// Accumulates every layer of the definition into one grid. Normalization steps are ignored.
+fn accumulate(definition: &IfsDefinition, args: &Args) -> DensityGrid {
+    if definition.layers().count() < definition.steps.len() {
+        eprintln!("warning: normalize steps are not supported with --accumulate");
+    }
+
+    let layers: Vec<_> = definition
+        .layers()
+        .map(|layer| (layer, layer.system()))
+        .collect();
+
+    let mut rng = args.seed.rng();
+    let bounds = args.bounds.unwrap_or_else(|| {
+        layers
+            .iter()
+            .map(|(layer, ifs)| {
+                let b = ifs
+                    .attractor_bounds()
+                    .unwrap_or_else(|| estimate_bounds(ifs, &mut rng, 100_000));
+                let (scale, dx, dy) = (layer.scale, layer.shift_x, layer.shift_y);
+                Bounds {
+                    x_min: (b.x_min + dx) * scale,
+                    x_max: (b.x_max + dx) * scale,
+                    y_min: (b.y_min + dy) * scale,
+                    y_max: (b.y_max + dy) * scale,
+                }
+            })
+            .reduce(|a, b| Bounds {
+                x_min: a.x_min.min(b.x_min),
+                x_max: a.x_max.max(b.x_max),
+                y_min: a.y_min.min(b.y_min),
+                y_max: a.y_max.max(b.y_max),
+            })
+            .unwrap_or_else(|| {
+                eprintln!("definition has no layers");
+                std::process::exit(1);
+            })
+    });
+
+    let mut grid = DensityGrid::new(bounds, args.width, args.height);
+    grid.set_threads(args.threads);
+    for (layer, ifs) in &layers {
+        let [r, g, b, _] = layer.color;
+        let iterations = args.iterations.unwrap_or(layer.iterations as u64);
+        grid.accumulate(
+            &mut rng,
+            ifs,
+            [r, g, b],
+            iterations,
+            layer.scale,
+            layer.shift_x,
+            layer.shift_y,
+        );
+    }
+    grid
+}
+
+fn main() {
+    let args = Args::parse();
+
+    let input = match args.input.clone() {
+        Some(input) if !args.list => input,
+        _ => {
+            for name in example_names() {
+                println!("{}", name);
+            }
+            return;
+        }
+    };
+
+    let mut definition = IfsDefinition::load(&input).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    });
+    if let Some(notation) = args.convert {
+        definition.set_notation(notation);
+        print!("{}", definition);
+        return;
+    }
+    for (layer, warning) in definition.warnings() {
+        eprintln!("warning: layer {}: {}", layer, warning);
+    }
+
+    if args.accumulate {
+        let grid = accumulate(&definition, &args);
+        let tone_map = ToneMap {
+            scaling: args.scaling,
+            gamma: args.gamma,
+            // The grid only knows the layer colors and the density
+            palette: match args.color_mode {
+                Coloring::Density => Some(args.palette),
+                _ => None,
+            },
+            background: definition.clear_color.unwrap_or([1.0, 1.0, 1.0, 1.0]),
+            ..ToneMap::default()
+        };
+
+        match &args.png {
+            Some(path) => grid.render(&tone_map).save_png(path).unwrap_or_else(|e| {
+                eprintln!("failed to write '{}': {}", path.display(), e);
+                std::process::exit(1);
+            }),
+            None => grid.run(&tone_map),
+        }
+        return;
+    }
+
+    let palette = args.palette;
+    let mut program = IfsProgram::with_rng(args.seed.rng());
+    program.set_threads(args.threads);
+    program.set_color_mode(match args.color_mode {
+        Coloring::Uniform => ColorMode::Uniform,
+        Coloring::Map => ColorMode::Map {
+            history: args.history.max(1),
+            palette,
+        },
+        Coloring::Density => ColorMode::Density { palette },
+        Coloring::Age => ColorMode::Age { palette },
+    });
+
+    if args.progressive {
+        let mut layers = definition.layers();
+        let layer = match (layers.next(), layers.next()) {
+            (Some(layer), None) if definition.steps.len() == 1 => layer,
+            _ => {
+                eprintln!("--progressive needs a definition with a single layer");
+                std::process::exit(1);
+            }
+        };
+
+        let settings = Progressive {
+            budget: args.budget,
+            ..Progressive::default()
+        };
+        if let Some([r, g, b, a]) = definition.clear_color {
+            program.set_clear_color((r, g, b, a));
+        }
+        let sampler = ProgressiveSampler::new(
+            layer.system(),
+            layer.color,
+            settings,
+            args.width,
+            args.height,
+        );
+        sampler.run(program, definition.point_size);
+        return;
+    }
+
+    definition.apply(&mut program);
+
+    if let Some(path) = args.png {
+        let image = program.render(args.width, args.height, definition.point_size);
+        image.save_png(&path).unwrap_or_else(|e| {
+            eprintln!("failed to write '{}': {}", path.display(), e);
+            std::process::exit(1);
+        });
+        return;
+    }
+
+    if let Some(path) = args.svg {
+        let svg = program.render_svg(args.width, args.height, definition.point_size, args.merge);
+        svg.save_svg(&path).unwrap_or_else(|e| {
+            eprintln!("failed to write '{}': {}", path.display(), e);
+            std::process::exit(1);
+        });
+        return;
+    }
+
+    program.run(definition.point_size);
+}
@@ -0,0 +1,144 @@
+// Three-dimensional IFS viewer. Drag with the left mouse button to rotate, scroll to zoom, press
+// P to switch between orthographic and perspective projection and space to reset the view.
+//
+//   cargo run --release --bin=ifs3d -- fern
+//   cargo run --release --bin=ifs3d -- tetrahedron --perspective
+//
+// With --png the point cloud is rendered on the CPU instead:
+//
+//   cargo run --release --bin=ifs3d -- fern --png fern3d.png --yaw 90 --pitch 20
+
+use std::path::PathBuf;
+
+use clap::{Parser, ValueEnum};
+use ndarray::{array, Array, Ix2};
+use rust_fractal_lab::args::SeedArgs;
+use rust_fractal_lab::ifs::three_d::{Camera, Ifs3d, IfsProgram3d, Projection};
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Example {
+    /// Barnsley's fern with its fronds bent out of the plane
+    Fern,
+    /// Sierpinski tetrahedron
+    Tetrahedron,
+    /// Menger sponge
+    Menger,
+}
+
+#[derive(Parser)]
+struct Args {
+    #[arg(value_enum, default_value_t = Example::Fern)]
+    example: Example,
+
+    #[arg(short, long, default_value_t = 500_000)]
+    iterations: usize,
+
+    /// Start with perspective instead of orthographic projection
+    #[arg(long)]
+    perspective: bool,
+
+    /// Initial rotation about the vertical axis, in degrees
+    #[arg(long, default_value_t = 30.0, allow_negative_numbers = true)]
+    yaw: f32,
+
+    /// Initial rotation about the horizontal axis, in degrees
+    #[arg(long, default_value_t = 20.0, allow_negative_numbers = true)]
+    pitch: f32,
+
+    /// Render to a PNG file instead of opening a window
+    #[arg(long)]
+    png: Option<PathBuf>,
+
+    #[arg(long, default_value_t = 768)]
+    width: u32,
+
+    #[arg(long, default_value_t = 768)]
+    height: u32,
+
+    #[command(flatten)]
+    seed: SeedArgs,
+}
+
+fn maps(example: Example) -> (Array<f32, Ix2>, [f32; 4]) {
+    match example {
+        Example::Fern => (
+            array![
+                [0.0, 0.0, 0.0, 0.0, 0.0, 0.18, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.01],
+                [0.85, 0.0, 0.0, 0.0, 0.0, 0.85, 0.1, 1.6, 0.0, -0.1, 0.85, 0.0, 0.85],
+                [0.2, -0.2, 0.0, 0.0, 0.2, 0.2, 0.0, 0.8, 0.0, 0.0, 0.3, 0.0, 0.07],
+                [-0.2, 0.2, 0.0, 0.0, 0.2, 0.2, 0.0, 0.8, 0.0, 0.0, 0.3, 0.0, 0.07],
+            ],
+            [0.0, 100.0 / 255.0, 0.0, 1.0],
+        ),
+        Example::Tetrahedron => (
+            array![
+                [0.5, 0.0, 0.0, 0.5, 0.0, 0.5, 0.0, 0.5, 0.0, 0.0, 0.5, 0.5, 0.25],
+                [0.5, 0.0, 0.0, 0.5, 0.0, 0.5, 0.0, -0.5, 0.0, 0.0, 0.5, -0.5, 0.25],
+                [0.5, 0.0, 0.0, -0.5, 0.0, 0.5, 0.0, 0.5, 0.0, 0.0, 0.5, -0.5, 0.25],
+                [0.5, 0.0, 0.0, -0.5, 0.0, 0.5, 0.0, -0.5, 0.0, 0.0, 0.5, 0.5, 0.25],
+            ],
+            [0.0, 0.0, 100.0 / 255.0, 1.0],
+        ),
+        Example::Menger => {
+            // One third size copies at the 20 positions of the 3x3x3 grid that are not in the
+            // middle of a face or the center
+            let mut rows = vec![];
+            for x in -1..=1_i32 {
+                for y in -1..=1_i32 {
+                    for z in -1..=1_i32 {
+                        if x.abs() + y.abs() + z.abs() > 1 {
+                            let mut row = [0.0; 13];
+                            // Scale by 1/3 about the center of the grid cell
+                            row[0] = 1.0 / 3.0;
+                            row[5] = 1.0 / 3.0;
+                            row[10] = 1.0 / 3.0;
+                            row[3] = x as f32 * 2.0 / 3.0;
+                            row[7] = y as f32 * 2.0 / 3.0;
+                            row[11] = z as f32 * 2.0 / 3.0;
+                            row[12] = 1.0;
+                            rows.extend(row);
+                        }
+                    }
+                }
+            }
+            (
+                Array::from_shape_vec((rows.len() / 13, 13), rows).unwrap(),
+                [72.0 / 255.0, 24.0 / 255.0, 0.0, 1.0],
+            )
+        }
+    }
+}
+
+fn main() {
+    let args = Args::parse();
+
+    let (maps, color) = maps(args.example);
+    let ifs = Ifs3d::new(maps).unwrap();
+
+    let mut program = IfsProgram3d::with_rng(args.seed.rng());
+    program.sample(&ifs, color, args.iterations);
+
+    let camera = Camera {
+        yaw: args.yaw.to_radians(),
+        pitch: args.pitch.to_radians(),
+        projection: if args.perspective {
+            Projection::Perspective {
+                fov: 45_f32.to_radians(),
+            }
+        } else {
+            Projection::Orthographic
+        },
+        ..Camera::default()
+    };
+
+    if let Some(path) = args.png {
+        let image = program.render(args.width, args.height, &camera, None);
+        image.save_png(&path).unwrap_or_else(|e| {
+            eprintln!("failed to write '{}': {}", path.display(), e);
+            std::process::exit(1);
+        });
+        return;
+    }
+
+    program.run(camera, None);
+}
@@ -0,0 +1,478 @@
+// Interactive IFS editor. Every map is drawn as the image of the unit square, a parallelogram whose
+// corners can be dragged with the mouse (see `src/ifs/editor.rs`):
+//
+// * the inside, or the corner at the image of (0, 0), moves the map
+// * the corners at the images of (1, 0) and (0, 1) move the images of the axes, which rotates,
+//   stretches and shears the map
+// * the opposite corner rotates and scales the map about its center
+//
+// The attractor is sampled again whenever a map changes, each part colored like the map that
+// produced it. The controls window edits the selected map numerically, prints the matrix in the
+// `d` array layout used by the bins, and saves it as an .ifs file:
+//
+//   cargo run --bin=ifs_editor
+//   cargo run --bin=ifs_editor -- fern --output my-fern.ifs
+
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use clap::Parser;
+use glium::glutin::event::{ElementState, Event, MouseButton, WindowEvent};
+use glium::glutin::event_loop::{ControlFlow, EventLoop};
+use glium::glutin::window::WindowBuilder;
+use glium::index::{NoIndices, PrimitiveType};
+use glium::{glutin, implement_vertex, uniform, Blend, DrawParameters, Program, VertexBuffer};
+use glium::{Display, Surface};
+use imgui::{Condition, Context, Ui};
+use imgui_glium_renderer::Renderer;
+use imgui_winit_support::{HiDpiMode, WinitPlatform};
+use rand_chacha::ChaCha8Rng;
+use rust_fractal_lab::args::{ColorScheme, SeedArgs};
+use rust_fractal_lab::ifs::editor::{Editor, Handle};
+use rust_fractal_lab::ifs::format::IfsDefinition;
+use rust_fractal_lab::ifs::system::Probabilities;
+use rust_fractal_lab::ifs::Bounds;
+
+const PALETTE: ColorScheme = ColorScheme::Turbo;
+
+/// Radius of the handles, in pixels.
+const HANDLE_RADIUS: f32 = 6.0;
+
+const PROBABILITIES: [&str; 3] = ["Given", "Determinant", "Uniform density"];
+
+#[derive(Parser)]
+struct Args {
+    /// Path to an .ifs file, or the name of a bundled example. The maps of its first layer are
+    /// edited.
+    #[arg(default_value = "sierpinski")]
+    input: String,
+
+    /// Where the "Save" button writes the definition
+    #[arg(short, long, default_value = "edited.ifs")]
+    output: PathBuf,
+
+    #[command(flatten)]
+    seed: SeedArgs,
+}
+
+#[derive(Copy, Clone)]
+struct ColoredVertex {
+    position: [f32; 2],
+    color: [f32; 4],
+}
+
+implement_vertex!(ColoredVertex, position, color);
+
+/// Everything the controls window can change.
+struct State {
+    editor: Editor,
+    color: [f32; 4],
+    iterations: u32,
+    probabilities: usize,
+    /// Error or warnings from checking the maps.
+    status: String,
+}
+
+/// The entry of [`PROBABILITIES`] at `index`.
+fn probabilities(index: usize) -> Probabilities {
+    match index {
+        1 => Probabilities::Determinant {
+            floor: Probabilities::DEFAULT_FLOOR,
+        },
+        2 => Probabilities::UniformDensity {
+            floor: Probabilities::DEFAULT_FLOOR,
+        },
+        _ => Probabilities::Given,
+    }
+}
+
+fn map_color(i: usize, maps: usize, alpha: f32) -> [f32; 4] {
+    let [r, g, b] = PALETTE.color((i as f32 + 0.5) / maps as f32);
+    [r, g, b, alpha]
+}
+
+/// Samples the attractor, colored by the map that produced each point. Every sample starts from
+/// the same generator, so points only move when the maps do.
+fn sample(state: &mut State, rng: &ChaCha8Rng) -> Vec<ColoredVertex> {
+    let ifs = match state.editor.ifs() {
+        Ok(ifs) => ifs,
+        Err(e) => {
+            state.status = e.to_string();
+            return vec![];
+        }
+    };
+    state.status = ifs
+        .warnings()
+        .iter()
+        .map(|w| w.to_string())
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let mut rng = rng.clone();
+    let mut vertices = Vec::with_capacity(state.iterations as usize);
+    let (mut x, mut y) = (0.0, 0.0);
+    for i in 0..state.iterations {
+        let map = ifs.choose(&mut rng);
+        (x, y) = ifs.apply(map, x, y);
+
+        // Skip first few iterations, and points that escaped to infinity
+        if i >= 10 && x.is_finite() && y.is_finite() {
+            vertices.push(ColoredVertex {
+                position: [x, y],
+                color: map_color(map, ifs.map_count(), state.color[3]),
+            });
+        }
+    }
+    vertices
+}
+
+/// Bounds showing every map and point, with square pixels in a `width` x `height` window.
+fn fit_view(editor: &Editor, points: &[ColoredVertex], width: u32, height: u32) -> Bounds {
+    let mut positions: Vec<[f32; 2]> = points.iter().map(|v| v.position).collect();
+    positions.extend(editor.maps.iter().flat_map(|map| map.corners()));
+
+    let b = Bounds::from_points_trimmed(&positions, 0.999)
+        .unwrap_or(Bounds {
+            x_min: -1.0,
+            x_max: 1.0,
+            y_min: -1.0,
+            y_max: 1.0,
+        })
+        .padded(0.1);
+
+    // Grow the narrower side to match the window
+    let aspect = width as f32 / height.max(1) as f32;
+    let size = b.width().max(b.height() * aspect).max(1e-3);
+    let (cx, cy) = ((b.x_min + b.x_max) / 2.0, (b.y_min + b.y_max) / 2.0);
+    Bounds {
+        x_min: cx - size / 2.0,
+        x_max: cx + size / 2.0,
+        y_min: cy - size / aspect / 2.0,
+        y_max: cy + size / aspect / 2.0,
+    }
+}
+
+/// Outlines of the maps, as pairs of vertices for [`PrimitiveType::LinesList`], and their
+/// handles.
+fn guides(editor: &Editor) -> (Vec<ColoredVertex>, Vec<ColoredVertex>) {
+    let mut lines = vec![];
+    let mut handles = vec![];
+    let n = editor.maps.len();
+
+    for (i, map) in editor.maps.iter().enumerate() {
+        let alpha = if editor.selected == Some(i) { 1.0 } else { 0.5 };
+        let color = map_color(i, n, alpha);
+
+        let corners = map.corners();
+        for j in 0..4 {
+            lines.push(ColoredVertex {
+                position: corners[j],
+                color,
+            });
+            lines.push(ColoredVertex {
+                position: corners[(j + 1) % 4],
+                color,
+            });
+        }
+
+        for handle in Handle::POINTS {
+            handles.push(ColoredVertex {
+                position: map.handle_position(handle),
+                color,
+            });
+        }
+    }
+
+    (lines, handles)
+}
+
+/// Draws the controls and applies them. Returns true if the maps changed.
+fn controls(ui: &Ui, state: &mut State, output: &Path) -> bool {
+    let mut changed = false;
+    let editor = &mut state.editor;
+
+    if ui.button("Add map") {
+        editor.add_map();
+        changed = true;
+    }
+    ui.same_line();
+    ui.disabled(editor.selected.is_none() || editor.maps.len() < 2, || {
+        if ui.button("Remove map") {
+            editor.remove_selected();
+            changed = true;
+        }
+    });
+
+    if ui.combo_simple_string("Probabilities", &mut state.probabilities, &PROBABILITIES) {
+        editor.probabilities = probabilities(state.probabilities);
+        changed = true;
+    }
+    changed |= ui.slider("Iterations", 1000, 1_000_000, &mut state.iterations);
+
+    ui.separator();
+    match editor.selected {
+        Some(i) => {
+            ui.text(format!("Map {}", i));
+            let map = &mut editor.maps[i];
+
+            for (j, name) in ["a", "b", "c", "d"].into_iter().enumerate() {
+                changed |= ui.slider(name, -1.5, 1.5, &mut map.coefs[j]);
+            }
+            changed |= ui.input_scalar("e", &mut map.coefs[4]).step(0.01).build();
+            changed |= ui.input_scalar("f", &mut map.coefs[5]).step(0.01).build();
+            ui.disabled(state.probabilities != 0, || {
+                changed |= ui.slider("p", 0.0, 1.0, &mut map.probability);
+            });
+
+            for (label, angle) in [("Rotate left", 15.0_f32), ("Rotate right", -15.0)] {
+                if ui.button(label) {
+                    map.rotate(angle.to_radians());
+                    changed = true;
+                }
+                ui.same_line();
+            }
+            ui.new_line();
+            for (label, factor) in [("Shrink", 0.9), ("Grow", 1.0 / 0.9)] {
+                if ui.button(label) {
+                    map.scale(factor, factor);
+                    changed = true;
+                }
+                ui.same_line();
+            }
+            ui.new_line();
+            for (label, amount) in [("Shear left", -0.1), ("Shear right", 0.1)] {
+                if ui.button(label) {
+                    map.shear(amount);
+                    changed = true;
+                }
+                ui.same_line();
+            }
+            ui.new_line();
+        }
+        None => ui.text("Click a map to select it"),
+    }
+
+    ui.separator();
+    if ui.button("Print matrix") {
+        println!("let d: Array<f32, Ix2> = {};", editor.to_rust());
+    }
+    ui.same_line();
+    if ui.button("Save") {
+        let definition = editor.to_definition(state.color, state.iterations as usize);
+        match std::fs::write(output, definition.to_string()) {
+            Ok(()) => println!("saved '{}'", output.display()),
+            Err(e) => eprintln!("failed to write '{}': {}", output.display(), e),
+        }
+    }
+
+    if !state.status.is_empty() {
+        ui.separator();
+        ui.text_wrapped(&state.status);
+    }
+
+    changed
+}
+
+fn main() {
+    let args = Args::parse();
+
+    let definition = IfsDefinition::load(&args.input).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    });
+    let layer = definition.layers().next().unwrap_or_else(|| {
+        eprintln!("definition has no layers");
+        std::process::exit(1);
+    });
+
+    let mut state = State {
+        editor: Editor::new(&layer.maps),
+        color: layer.color,
+        iterations: layer.iterations.clamp(1000, 1_000_000) as u32,
+        probabilities: 0,
+        status: String::new(),
+    };
+    let rng = args.seed.rng();
+
+    let event_loop = EventLoop::new();
+    let context = glutin::ContextBuilder::new().with_vsync(true);
+    let builder = WindowBuilder::new()
+        .with_title("IFS editor")
+        .with_inner_size(glutin::dpi::LogicalSize::new(1024f64, 768f64));
+    let display =
+        Display::new(builder, context, &event_loop).expect("Failed to initialize display");
+
+    let mut imgui = Context::create();
+    imgui.set_ini_filename(None);
+
+    let mut platform = WinitPlatform::init(&mut imgui);
+    let gl_window = display.gl_window();
+    let window = gl_window.window();
+    platform.attach_window(imgui.io_mut(), window, HiDpiMode::Default);
+    drop(gl_window);
+
+    let program = Program::from_source(
+        &display,
+        r##"#version 140
+uniform vec4 bounds;
+in vec2 position;
+in vec4 color;
+out vec4 v_color;
+void main() {
+    vec2 p = (position - bounds.xz) / (bounds.yw - bounds.xz) * 2.0 - 1.0;
+    gl_Position = vec4(p, 0.0, 1.0);
+    v_color = color;
+}
+"##,
+        r##"#version 130
+in vec4 v_color;
+out vec4 color;
+void main() {
+    color = v_color;
+}
+"##,
+        None,
+    )
+    .unwrap();
+
+    let mut renderer = Renderer::init(&mut imgui, &display).expect("Failed to initialize renderer");
+
+    let mut points = sample(&mut state, &rng);
+    let (width, height) = display.get_framebuffer_dimensions();
+    let mut view = fit_view(&state.editor, &points, width, height);
+    let mut point_buffer = VertexBuffer::new(&display, &points).unwrap();
+    let mut changed = false;
+
+    let mut cursor = [0.0, 0.0];
+    let mut last_frame = Instant::now();
+
+    event_loop.run(move |event, _, control_flow| match event {
+        Event::NewEvents(_) => {
+            let now = Instant::now();
+            imgui.io_mut().update_delta_time(now - last_frame);
+            last_frame = now;
+        }
+        Event::MainEventsCleared => {
+            let gl_window = display.gl_window();
+            platform
+                .prepare_frame(imgui.io_mut(), gl_window.window())
+                .expect("Failed to prepare frame");
+            gl_window.window().request_redraw();
+        }
+        Event::RedrawRequested(_) => {
+            if changed {
+                points = sample(&mut state, &rng);
+                point_buffer = VertexBuffer::new(&display, &points).unwrap();
+                changed = false;
+            }
+
+            let ui = imgui.new_frame();
+
+            ui.window("Controls")
+                .size([320.0, 480.0], Condition::FirstUseEver)
+                .position([10.0, 10.0], Condition::FirstUseEver)
+                .build(|| {
+                    changed |= controls(ui, &mut state, &args.output);
+                    if ui.button("Fit view") {
+                        let (width, height) = display.get_framebuffer_dimensions();
+                        view = fit_view(&state.editor, &points, width, height);
+                    }
+                });
+
+            let (lines, handles) = guides(&state.editor);
+            let line_buffer = VertexBuffer::new(&display, &lines).unwrap();
+            let handle_buffer = VertexBuffer::new(&display, &handles).unwrap();
+            let uniforms = uniform! {
+                bounds: [view.x_min, view.x_max, view.y_min, view.y_max],
+            };
+            let params = DrawParameters {
+                blend: Blend::alpha_blending(),
+                ..Default::default()
+            };
+
+            let gl_window = display.gl_window();
+            let mut target = display.draw();
+            target.clear_color_srgb(1.0, 1.0, 1.0, 1.0);
+            target
+                .draw(
+                    &point_buffer,
+                    NoIndices(PrimitiveType::Points),
+                    &program,
+                    &uniforms,
+                    &params,
+                )
+                .unwrap();
+            target
+                .draw(
+                    &line_buffer,
+                    NoIndices(PrimitiveType::LinesList),
+                    &program,
+                    &uniforms,
+                    &params,
+                )
+                .unwrap();
+            target
+                .draw(
+                    &handle_buffer,
+                    NoIndices(PrimitiveType::Points),
+                    &program,
+                    &uniforms,
+                    &DrawParameters {
+                        point_size: Some(HANDLE_RADIUS * 2.0),
+                        ..params.clone()
+                    },
+                )
+                .unwrap();
+            platform.prepare_render(ui, gl_window.window());
+            let draw_data = imgui.render();
+            renderer
+                .render(&mut target, draw_data)
+                .expect("Rendering failed");
+            target.finish().expect("Failed to swap buffers");
+        }
+        Event::WindowEvent {
+            event: WindowEvent::CloseRequested,
+            ..
+        } => *control_flow = ControlFlow::Exit,
+        event => {
+            let gl_window = display.gl_window();
+            platform.handle_event(imgui.io_mut(), gl_window.window(), &event);
+            drop(gl_window);
+
+            let (width, height) = display.get_framebuffer_dimensions();
+            let to_world = move |[px, py]: [f32; 2]| {
+                let (x, y) = view.from_pixel(px, py, width, height);
+                [x, y]
+            };
+
+            // Clicks on the controls window are not meant for the maps
+            let captured = imgui.io().want_capture_mouse;
+            if let Event::WindowEvent { event, .. } = event {
+                match event {
+                    WindowEvent::MouseInput {
+                        state: ElementState::Pressed,
+                        button: MouseButton::Left,
+                        ..
+                    } if !captured => {
+                        let radius = HANDLE_RADIUS * view.width() / width as f32;
+                        state.editor.begin_drag(to_world(cursor), radius);
+                    }
+                    WindowEvent::MouseInput {
+                        state: ElementState::Released,
+                        button: MouseButton::Left,
+                        ..
+                    } => state.editor.end_drag(),
+                    WindowEvent::CursorMoved { position, .. } => {
+                        cursor = [position.x as f32, position.y as f32];
+                        changed |= state.editor.drag_to(to_world(cursor));
+                    }
+                    WindowEvent::Resized(size) => {
+                        // Keep the pixels square
+                        view = fit_view(&state.editor, &points, size.width, size.height);
+                    }
+                    _ => (),
+                }
+            }
+        }
+    });
+}
@@ -0,0 +1,151 @@
+// L-system viewer. Loads a definition in the format described in `src/lsystem/mod.rs`, either
+// from a file or by the name of one of the bundled examples in `src/lsystem/examples`:
+//
+//   cargo run --bin=lsystem koch
+//   cargo run --bin=lsystem -- dragon --iterations 14
+//   cargo run --bin=lsystem path/to/my-plant.lsys
+//
+// Stochastic systems like `plant` grow differently on every run unless --seed is given. With
+// --svg the lines are written to a vector image instead of shown:
+//
+//   cargo run --bin=lsystem -- plant --seed 3 --svg plant.svg
+
+use std::path::PathBuf;
+
+use clap::Parser;
+use glium::glutin::dpi::LogicalSize;
+use glium::glutin::event::{Event, WindowEvent};
+use glium::glutin::event_loop::{ControlFlow, EventLoop};
+use glium::glutin::window::WindowBuilder;
+use glium::glutin::ContextBuilder;
+use glium::index::{NoIndices, PrimitiveType};
+use glium::{Display, DrawParameters, Program, Surface, VertexBuffer};
+use rust_fractal_lab::args::SeedArgs;
+use rust_fractal_lab::lsystem::turtle::{fit_lines, Turtle};
+use rust_fractal_lab::lsystem::{example_names, LSystem};
+use rust_fractal_lab::svg::Svg;
+use rust_fractal_lab::vertex::Line;
+
+use crate::ControlFlow::Wait;
+
+#[derive(Parser)]
+struct Args {
+    /// Path to an .lsys file, or the name of a bundled example
+    input: Option<String>,
+
+    /// List the bundled examples and exit
+    #[arg(short, long)]
+    list: bool,
+
+    /// Number of rewrites, instead of the one in the definition
+    #[arg(short, long)]
+    iterations: Option<usize>,
+
+    /// Write an SVG file instead of opening a window
+    #[arg(long)]
+    svg: Option<PathBuf>,
+
+    #[arg(long, default_value_t = 1.0)]
+    line_width: f32,
+
+    #[command(flatten)]
+    seed: SeedArgs,
+}
+
+fn main() {
+    let args = Args::parse();
+
+    let input = match args.input.clone() {
+        Some(input) if !args.list => input,
+        _ => {
+            for name in example_names() {
+                println!("{}", name);
+            }
+            return;
+        }
+    };
+
+    let system = LSystem::load(&input).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    });
+
+    let iterations = args.iterations.unwrap_or(system.iterations);
+    let modules = system.generate(iterations, &mut args.seed.rng());
+    let mut lines = Turtle::for_system(&system).draw(&modules);
+    fit_lines(&mut lines, 0.95);
+
+    if let Some(path) = args.svg {
+        let mut svg = Svg::new(768, 768);
+        svg.set_background([1.0, 1.0, 1.0, 1.0]);
+        svg.add_lines(&lines, [0.0, 0.0, 0.0, 1.0], args.line_width);
+        svg.save_svg(&path).unwrap_or_else(|e| {
+            eprintln!("failed to write '{}': {}", path.display(), e);
+            std::process::exit(1);
+        });
+        return;
+    }
+
+    let event_loop = EventLoop::new();
+
+    let wb = WindowBuilder::new()
+        .with_inner_size(LogicalSize::new(768.0_f32, 768.0_f32))
+        .with_title(format!("L-system - {}", input));
+
+    let cb = ContextBuilder::new();
+
+    let display = Display::new(wb, cb, &event_loop).unwrap();
+
+    let vertices: Vec<_> = lines.into_iter().flat_map(Line::into_vertices).collect();
+
+    let vertex_buffer = VertexBuffer::new(&display, &vertices).unwrap();
+    let indices = NoIndices(PrimitiveType::LinesList);
+
+    let program = Program::from_source(
+        &display,
+        r##"#version 140
+in vec2 position;
+void main() {
+	gl_Position = vec4(position, 0.0, 1.0);
+}
+"##,
+        r##"#version 130
+out vec4 color;
+void main() {
+	color = vec4(0, 0, 0, 1);
+}
+"##,
+        None,
+    )
+    .unwrap();
+
+    let uniforms = glium::uniforms::EmptyUniforms;
+    let line_width = args.line_width;
+
+    event_loop.run(move |ev, _, control_flow| {
+        *control_flow = Wait;
+
+        match ev {
+            Event::WindowEvent {
+                event: WindowEvent::CloseRequested,
+                ..
+            } => {
+                *control_flow = ControlFlow::Exit;
+                return;
+            }
+            Event::WindowEvent { .. } => return,
+            _ => (),
+        }
+
+        let mut target = display.draw();
+        target.clear_color(255.0, 255.0, 255.0, 1.0);
+        let params = DrawParameters {
+            line_width: Some(line_width),
+            ..Default::default()
+        };
+        target
+            .draw(&vertex_buffer, indices, &program, &uniforms, &params)
+            .unwrap();
+        target.finish().unwrap();
+    });
+}
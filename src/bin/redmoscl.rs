@@ -1,57 +1,58 @@
-use ndarray::{array, Array, Ix2};
-use rust_fractal_lab::forest::draw_forest;
+// Redwoods in a forest under a layer of mist, composed from the scene in
+// `src/ifs/examples/redmoscl.scene`. Other scenes can be shown by name or path, see
+// `src/ifs/scene.rs` for the format:
+//
+//   cargo run --bin=redmoscl
+//   cargo run --bin=redmoscl path/to/my-landscape.scene
+//   cargo run --bin=redmoscl -- --png redmoscl.png
+
+use std::path::PathBuf;
+
+use clap::Parser;
+use rust_fractal_lab::args::SeedArgs;
+use rust_fractal_lab::ifs::scene::Scene;
 use rust_fractal_lab::ifs::IfsProgram;
 
+#[derive(Parser)]
+struct Args {
+    /// Path to a .scene file, or the name of a bundled scene
+    #[arg(default_value = "redmoscl")]
+    input: String,
+
+    /// Render to a PNG file instead of opening a window
+    #[arg(long)]
+    png: Option<PathBuf>,
+
+    #[arg(long, default_value_t = 768)]
+    width: u32,
+
+    #[arg(long, default_value_t = 768)]
+    height: u32,
+
+    #[command(flatten)]
+    seed: SeedArgs,
+}
+
 fn main() {
+    let args = Args::parse();
+
+    let scene = Scene::load(&args.input).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    });
+
     let mut program = IfsProgram::default();
-    let mut rng = rand::thread_rng();
-
-    // Draw 75 trees behind the redwoods
-    draw_forest(&mut program, &mut rng, 75);
-    // Keep the trees to the bottom half of the screen
-    program.normalize_points_to_ranges(-1.0, 1.0, -1.0, 0.5);
-
-    let inc = 8.0;
-    let cloud_d: Array<f32, Ix2> = array![
-        [0.33, 1.0, 0.0, 0.33, 0.0, 0.0, 0.125],
-        [0.33, 1.0, 0.0, 0.33, inc, 0.0, 0.125],
-        [0.33, 1.0, 0.0, 0.33, 1.0, inc, 0.125],
-        [0.33, 1.0, 0.0, 0.33, inc, inc, 0.125],
-        [0.33, 0.0, 0.0, 0.33, inc / 2.0, 1.0, 0.125],
-        [0.33, 0.0, 0.0, 0.33, inc, inc / 2.0, 0.125],
-        [0.33, 0.0, 0.0, 0.33, 1.0, inc / 2.0, 0.125],
-        [0.33, 0.0, 0.0, 0.33, inc / 2.0, inc, 0.125],
-    ];
-
-    // Draw the redwoods themselves
-    let inc = 6.0;
-    let redwood_d: Array<f32, Ix2> = array![
-        [0.33, 0.0, 0.0, 0.5, 1.0, 0.0, 0.125],
-        [0.33, 0.0, 0.0, 0.5, inc, 0.0, 0.125],
-        [0.33, 0.0, 0.0, 0.5, 1.0, -inc, 0.125],
-        [0.33, 0.0, 0.0, 0.5, inc, -inc, 0.125],
-        [0.33, 0.0, 2.0, 0.5, inc / 2.0, 1.0, 0.125],
-        [0.33, 0.0, 2.0, 0.5, inc, inc / 2.0, 0.125],
-        [0.33, 0.0, 3.0, 0.5, 1.0, inc / 2.0, 0.125],
-        [0.33, 0.0, 2.0, 0.5, inc / 2.0, inc, 0.125],
-    ];
-
-    program.sample(&redwood_d, [101.0 / 255.0, 2.0 / 255.0, 0.0, 1.0], 300000);
-    program.normalize_points();
-
-    // Draw 75 trees in front of the redwoods
-    draw_forest(&mut program, &mut rng, 75);
-    // Keep the trees to the bottom half of the screen
-    program.normalize_points_to_ranges(-1.0, 1.0, -1.0, 0.5);
-
-    // Draw mist
-    program.sample(
-        &cloud_d,
-        [129.0 / 255.0, 129.0 / 255.0, 129.0 / 255.0, 1.0],
-        30000,
-    );
-    // Place the mist in upper half of screen
-    program.normalize_points_to_ranges(-1.0, 1.0, 0.5, 1.0);
-
-    program.run(Some(1.5));
+    let mut rng = args.seed.rng();
+    scene.apply(&mut program, &mut rng);
+
+    if let Some(path) = args.png {
+        let image = program.render(args.width, args.height, scene.point_size);
+        image.save_png(&path).unwrap_or_else(|e| {
+            eprintln!("failed to write '{}': {}", path.display(), e);
+            std::process::exit(1);
+        });
+        return;
+    }
+
+    program.run(scene.point_size);
 }
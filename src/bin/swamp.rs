@@ -1,8 +1,18 @@
+use clap::Parser;
 use ndarray::{array, Array, Ix2};
 use rand::Rng;
+use rust_fractal_lab::args::SeedArgs;
 use rust_fractal_lab::ifs::IfsProgram;
 
+#[derive(Parser)]
+struct Args {
+    #[command(flatten)]
+    seed: SeedArgs,
+}
+
 fn main() {
+    let args = Args::parse();
+
     let d: Array<f32, Ix2> = array![
         [0.5, 0.0, 0.0, 0.25, 1.0, 1.0, 0.25],
         [0.25, 0.0, 0.0, 0.7, 50.0, 1.0, 0.25],
@@ -11,7 +21,7 @@ fn main() {
     ];
 
     let mut program = IfsProgram::default();
-    let mut rng = rand::thread_rng();
+    let mut rng = args.seed.rng();
 
     for _ in 0..100 {
         let r = rng.gen_range(24.0..35.0);
@@ -21,7 +31,8 @@ fn main() {
         let shift_y = rng.gen_range(-200.0..200.0);
         let scale = rng.gen_range(0.6..1.0);
 
-        program.sample_affine(
+        program.sample_affine_with_rng(
+            &mut rng,
             &d,
             [r / 255.0, g / 255.0, 0.0, 1.0],
             2000,
@@ -0,0 +1,39 @@
+use crate::vertex::{Line, Vertex};
+
+/// Draws the Cantor set: the line from `left` to `right`, then recursively its left and right
+/// thirds a bit further down, until `max_depth` levels have been drawn below it.
+pub fn cantor<L, R>(lines: &mut Vec<Line>, left: L, right: R, depth: u8, max_depth: u8)
+where
+    L: Into<Vertex>,
+    R: Into<Vertex>,
+{
+    let left = left.into();
+    let right = right.into();
+    lines.push(Line(left, right));
+
+    // Keep track of recursion depth
+    if depth >= max_depth {
+        return;
+    }
+
+    // Shift subsequent lines down a bit
+    let y = left.y() - 0.05;
+    // Calculate third of line segment
+    let delta = (right.x() - left.x()) / 3.0;
+    // Draw left third
+    cantor(
+        lines,
+        [left.x(), y],
+        [left.x() + delta, y],
+        depth + 1,
+        max_depth,
+    );
+    // Draw right third
+    cantor(
+        lines,
+        [right.x() - delta, y],
+        [right.x(), y],
+        depth + 1,
+        max_depth,
+    );
+}
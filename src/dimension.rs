@@ -0,0 +1,380 @@
+//! Estimates of fractal dimension.
+//!
+//! Box counting, information and correlation dimensions are all measured the same way: some
+//! quantity is computed at a range of scales, and the dimension is the slope of a least squares
+//! line through the results on a log-log plot. The fit is returned along with the estimate, since
+//! a poor fit means the scales did not capture any self-similarity.
+
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+
+use ndarray::{Array, Ix2};
+
+use crate::ifs::effective_map;
+use crate::vertex::Line;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct LinearFit {
+    pub slope: f64,
+    pub intercept: f64,
+    /// Coefficient of determination, 1.0 for a perfect fit.
+    pub r_squared: f64,
+}
+
+impl LinearFit {
+    /// Least squares fit of `y = slope * x + intercept`. Needs at least two distinct `x` values.
+    pub fn new(samples: &[(f64, f64)]) -> Option<Self> {
+        let n = samples.len() as f64;
+        let mean_x = samples.iter().map(|s| s.0).sum::<f64>() / n;
+        let mean_y = samples.iter().map(|s| s.1).sum::<f64>() / n;
+
+        let sxx: f64 = samples.iter().map(|s| (s.0 - mean_x).powi(2)).sum();
+        let sxy: f64 = samples
+            .iter()
+            .map(|s| (s.0 - mean_x) * (s.1 - mean_y))
+            .sum();
+        let syy: f64 = samples.iter().map(|s| (s.1 - mean_y).powi(2)).sum();
+
+        if samples.len() < 2 || sxx == 0.0 {
+            return None;
+        }
+
+        let slope = sxy / sxx;
+        Some(Self {
+            slope,
+            intercept: mean_y - slope * mean_x,
+            r_squared: if syy == 0.0 {
+                1.0
+            } else {
+                sxy * sxy / (sxx * syy)
+            },
+        })
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct DimensionEstimate {
+    pub dimension: f64,
+    pub fit: LinearFit,
+    /// The log-log samples the fit was computed from.
+    pub samples: Vec<(f64, f64)>,
+}
+
+impl DimensionEstimate {
+    fn from_samples(samples: Vec<(f64, f64)>) -> Option<Self> {
+        let fit = LinearFit::new(&samples)?;
+        Some(Self {
+            dimension: fit.slope,
+            fit,
+            samples,
+        })
+    }
+}
+
+impl Display for DimensionEstimate {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:.4} (R² = {:.4}, {} scales)",
+            self.dimension,
+            self.fit.r_squared,
+            self.samples.len()
+        )
+    }
+}
+
+/// Box sizes used for counting. Boxes are `2^-k` times the size of the square enclosing the
+/// points, for `k` between `min_exponent` and `max_exponent` inclusive.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Scales {
+    pub min_exponent: u32,
+    pub max_exponent: u32,
+}
+
+impl Scales {
+    /// Picks the finest scale at which the boxes still hold 8 points on average, since finer
+    /// boxes just end up counting individual points.
+    pub fn auto(points: &[[f32; 2]]) -> Self {
+        let unit = to_unit_square(points);
+        let mut max_exponent = 2;
+        while max_exponent < 24 && box_counts(&unit, max_exponent + 1).len() * 8 <= unit.len() {
+            max_exponent += 1;
+        }
+
+        Self {
+            min_exponent: 1,
+            max_exponent: max_exponent.max(3),
+        }
+    }
+}
+
+/// Maps the points into [0, 1) while keeping their aspect ratio.
+fn to_unit_square(points: &[[f32; 2]]) -> Vec<[f64; 2]> {
+    let finite = || {
+        points
+            .iter()
+            .filter(|p| p[0].is_finite() && p[1].is_finite())
+    };
+
+    let (mut x_min, mut x_max, mut y_min, mut y_max) = (f64::MAX, f64::MIN, f64::MAX, f64::MIN);
+    for p in finite() {
+        x_min = x_min.min(p[0] as f64);
+        x_max = x_max.max(p[0] as f64);
+        y_min = y_min.min(p[1] as f64);
+        y_max = y_max.max(p[1] as f64);
+    }
+
+    // Shrink slightly so the maximum lands inside the last box rather than past it
+    let extent = (x_max - x_min).max(y_max - y_min).max(f64::MIN_POSITIVE) * (1.0 + 1e-9);
+    finite()
+        .map(|p| {
+            [
+                (p[0] as f64 - x_min) / extent,
+                (p[1] as f64 - y_min) / extent,
+            ]
+        })
+        .collect()
+}
+
+/// Number of points in each occupied box of size `2^-k`.
+fn box_counts(unit: &[[f64; 2]], k: u32) -> HashMap<(u64, u64), usize> {
+    let boxes = (1_u64 << k) as f64;
+    let mut counts = HashMap::new();
+    for p in unit {
+        *counts
+            .entry(((p[0] * boxes) as u64, (p[1] * boxes) as u64))
+            .or_insert(0) += 1;
+    }
+    counts
+}
+
+/// Box counting dimension: the slope of `ln N(ε)` against `ln 1/ε`, where `N(ε)` is the number
+/// of boxes of size `ε` containing at least one point.
+pub fn box_counting(points: &[[f32; 2]], scales: Option<Scales>) -> Option<DimensionEstimate> {
+    let scales = scales.unwrap_or_else(|| Scales::auto(points));
+    let unit = to_unit_square(points);
+
+    let samples = (scales.min_exponent..=scales.max_exponent)
+        .map(|k| {
+            let occupied = box_counts(&unit, k).len() as f64;
+            (k as f64 * 2_f64.ln(), occupied.ln())
+        })
+        .collect();
+
+    DimensionEstimate::from_samples(samples)
+}
+
+/// Information dimension: the slope of the Shannon entropy of the box occupation probabilities
+/// against `ln 1/ε`. Unlike box counting this weighs boxes by how often they are visited.
+pub fn information(points: &[[f32; 2]], scales: Option<Scales>) -> Option<DimensionEstimate> {
+    let scales = scales.unwrap_or_else(|| Scales::auto(points));
+    let unit = to_unit_square(points);
+    let total = unit.len() as f64;
+
+    let samples = (scales.min_exponent..=scales.max_exponent)
+        .map(|k| {
+            let entropy: f64 = box_counts(&unit, k)
+                .values()
+                .map(|&count| {
+                    let p = count as f64 / total;
+                    -p * p.ln()
+                })
+                .sum();
+            (k as f64 * 2_f64.ln(), entropy)
+        })
+        .collect();
+
+    DimensionEstimate::from_samples(samples)
+}
+
+/// Correlation dimension (Grassberger-Procaccia): the slope of `ln C(r)` against `ln r`, where
+/// `C(r)` is the fraction of pairs of points closer than `r`. Pairs are counted among at most
+/// `max_points` evenly spaced points, since the work grows quadratically.
+pub fn correlation(points: &[[f32; 2]], max_points: usize) -> Option<DimensionEstimate> {
+    let unit = to_unit_square(points);
+    let stride = (unit.len() / max_points.max(2)).max(1);
+    let subset: Vec<[f64; 2]> = unit.into_iter().step_by(stride).collect();
+
+    let mut distances = Vec::with_capacity(subset.len() * subset.len() / 2);
+    for (i, p) in subset.iter().enumerate() {
+        for q in &subset[i + 1..] {
+            distances.push(((p[0] - q[0]).powi(2) + (p[1] - q[1]).powi(2)).sqrt());
+        }
+    }
+    distances.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+    let total = distances.len() as f64;
+    let samples = (1..24)
+        .filter_map(|k| {
+            let r = 2_f64.powi(-k);
+            let pairs = distances.partition_point(|&d| d < r);
+            // Skip radii so large that most pairs are counted, or so small that only a handful
+            // are, neither of which says anything about the scaling
+            let c = pairs as f64 / total;
+            (pairs >= 50 && c <= 0.25).then(|| (r.ln(), c.ln()))
+        })
+        .collect();
+
+    DimensionEstimate::from_samples(samples)
+}
+
+/// Box counting dimension of the set pixels of a `width` x `height` mask, such as the boundary
+/// of an escape-time fractal.
+pub fn box_counting_mask(mask: &[bool], width: u32, height: u32) -> Option<DimensionEstimate> {
+    let points: Vec<[f32; 2]> = mask
+        .iter()
+        .enumerate()
+        .filter(|(_, &set)| set)
+        .map(|(i, _)| {
+            [
+                (i % width as usize) as f32 + 0.5,
+                (i / width as usize) as f32 + 0.5,
+            ]
+        })
+        .collect();
+
+    // Stop a couple of levels above single pixels, where the count stops growing
+    let pixel_exponent = width.max(height).max(1).ilog2();
+    let scales = Scales {
+        min_exponent: 1,
+        max_exponent: pixel_exponent.saturating_sub(2).max(2),
+    };
+
+    box_counting(&points, Some(scales))
+}
+
+/// Points spaced at most `spacing` apart along each line, for measuring line sets such as the
+/// Cantor set.
+pub fn sample_lines(lines: &[Line], spacing: f32) -> Vec<[f32; 2]> {
+    lines
+        .iter()
+        .flat_map(|Line(a, b)| {
+            let length = ((b.x() - a.x()).powi(2) + (b.y() - a.y()).powi(2)).sqrt();
+            let steps = (length / spacing).ceil().max(1.0) as usize;
+            (0..=steps).map(move |i| {
+                let t = i as f32 / steps as f32;
+                [a.x() + t * (b.x() - a.x()), a.y() + t * (b.y() - a.y())]
+            })
+        })
+        .collect()
+}
+
+/// Similarity dimension of an IFS whose maps are all similarities (rotation, reflection and
+/// uniform scaling), i.e. the solution `s` of the Moran equation `Σ rᵢˢ = 1` for the scaling
+/// ratios `rᵢ`. This equals the Hausdorff dimension when the images of the attractor don't
+/// overlap much (the open set condition). Returns `None` if a map is not a contracting
+/// similarity.
+pub fn similarity_dimension(d: &Array<f32, Ix2>) -> Option<f64> {
+    let ratios = d
+        .rows()
+        .into_iter()
+        .map(|row| {
+            let [a, b, c, d, _, _] = effective_map(row).map(|v| v as f64);
+            let col1 = a * a + c * c;
+            let col2 = b * b + d * d;
+            let tolerance = 1e-4 * col1.max(col2).max(1e-12);
+
+            // A similarity has orthogonal columns of equal length
+            let is_similarity =
+                (col1 - col2).abs() <= tolerance && (a * b + c * d).abs() <= tolerance;
+            let ratio = col1.sqrt();
+            (is_similarity && ratio > 0.0 && ratio < 1.0).then_some(ratio)
+        })
+        .collect::<Option<Vec<f64>>>()?;
+
+    moran_exponent(&ratios)
+}
+
+/// Solution `s` of the Moran equation `Σ rᵢˢ = 1`, or `None` if a ratio is not between 0 and 1
+/// (exclusive), in which case there is no solution.
+pub fn moran_exponent(ratios: &[f64]) -> Option<f64> {
+    if ratios.iter().any(|&r| !(r > 0.0 && r < 1.0)) {
+        return None;
+    }
+
+    let moran = |s: f64| ratios.iter().map(|r| r.powf(s)).sum::<f64>() - 1.0;
+
+    // The sum decreases with s, find an upper bound and bisect
+    let mut hi = 1.0;
+    while moran(hi) > 0.0 {
+        hi *= 2.0;
+    }
+    let mut lo = 0.0;
+    for _ in 0..100 {
+        let mid = (lo + hi) / 2.0;
+        if moran(mid) > 0.0 {
+            lo = mid;
+        } else {
+            hi = mid;
+        }
+    }
+
+    Some((lo + hi) / 2.0)
+}
+
+#[cfg(test)]
+mod test {
+    use ndarray::array;
+
+    use crate::dimension::{
+        box_counting, correlation, information, sample_lines, similarity_dimension, LinearFit,
+    };
+    use crate::ifs::IfsProgram;
+    use crate::utils::rng::seeded_rng;
+    use crate::vertex::{Line, Vertex};
+
+    #[test]
+    fn linear_fit() {
+        let fit = LinearFit::new(&[(0.0, 1.0), (1.0, 3.0), (2.0, 5.0)]).unwrap();
+        assert!((fit.slope - 2.0).abs() < 1e-12);
+        assert!((fit.intercept - 1.0).abs() < 1e-12);
+        assert!((fit.r_squared - 1.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn sierpinski() {
+        let d = array![
+            [0.5, 0.0, 0.0, 0.5, -0.5, -0.5, 0.33],
+            [0.5, 0.0, 0.0, 0.5, 0.0, 0.5, 0.33],
+            [0.5, 0.0, 0.0, 0.5, 0.5, -0.5, 0.33]
+        ];
+        let expected = 3_f64.ln() / 2_f64.ln();
+        assert!((similarity_dimension(&d).unwrap() - expected).abs() < 1e-9);
+
+        let mut program = IfsProgram::with_rng(seeded_rng(Some(1)));
+        program.sample(&d, [0.0, 0.0, 0.0, 1.0], 200000);
+        let points = program.points();
+
+        for estimate in [
+            box_counting(&points, None).unwrap(),
+            information(&points, None).unwrap(),
+            correlation(&points, 2000).unwrap(),
+        ] {
+            assert!(
+                (estimate.dimension - expected).abs() < 0.1,
+                "estimate {} too far from {}",
+                estimate,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn line_is_one_dimensional() {
+        let line = Line(Vertex::from([0.0, 0.0]), Vertex::from([1.0, 0.5]));
+        let points = sample_lines(&[line], 1e-4);
+        let estimate = box_counting(&points, None).unwrap();
+        assert!((estimate.dimension - 1.0).abs() < 0.05, "{}", estimate);
+    }
+
+    #[test]
+    fn fern_is_not_a_similarity() {
+        let d = array![
+            [0.0, 0.0, 0.0, 0.16, 0.0, 0.0, 0.01],
+            [0.85, 0.04, -0.04, 0.85, 0.0, 1.6, 0.85],
+            [0.2, -0.26, 0.23, 0.22, 0.0, 1.6, 0.07],
+            [-0.15, 0.28, 0.26, 0.24, 0.0, 0.44, 0.07],
+        ];
+        assert_eq!(similarity_dimension(&d), None);
+    }
+}
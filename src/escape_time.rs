@@ -0,0 +1,61 @@
+//! CPU versions of the escape-time iteration used by the Mandelbrot and Julia set shaders, for
+//! analysis that needs the pixels in memory.
+
+use crate::ifs::Bounds;
+
+/// Number of iterations of `z -> z^2 + c` before `|z| > 2`, or `max_iterations` if the orbit
+/// stays bounded.
+pub fn escape_time(mut z: (f64, f64), c: (f64, f64), max_iterations: u32) -> u32 {
+    for i in 0..max_iterations {
+        let (x, y) = z;
+        if x * x + y * y > 4.0 {
+            return i;
+        }
+        z = (x * x - y * y + c.0, 2.0 * x * y + c.1);
+    }
+
+    max_iterations
+}
+
+/// Rasterizes the boundary of the Mandelbrot set, or of the filled Julia set for `julia = Some(c)`.
+/// A pixel is on the boundary if it belongs to the set while one of its neighbors does not.
+/// Returns one flag per pixel, row by row starting at the top left corner.
+pub fn boundary_mask(
+    width: u32,
+    height: u32,
+    bounds: Bounds,
+    julia: Option<(f64, f64)>,
+    max_iterations: u32,
+) -> Vec<bool> {
+    let (width, height) = (width as usize, height as usize);
+
+    let inside: Vec<bool> = (0..width * height)
+        .map(|i| {
+            let x = bounds.x_min as f64 + (i % width) as f64 / width as f64 * bounds.width() as f64;
+            let y =
+                bounds.y_max as f64 - (i / width) as f64 / height as f64 * bounds.height() as f64;
+
+            let iterations = match julia {
+                Some(c) => escape_time((x, y), c, max_iterations),
+                None => escape_time((0.0, 0.0), (x, y), max_iterations),
+            };
+            iterations == max_iterations
+        })
+        .collect();
+
+    (0..width * height)
+        .map(|i| {
+            let (x, y) = (i % width, i / width);
+            inside[i]
+                && [(0, -1), (0, 1), (-1, 0), (1, 0)].iter().any(|(dx, dy)| {
+                    let nx = x as i64 + dx;
+                    let ny = y as i64 + dy;
+                    nx >= 0
+                        && ny >= 0
+                        && (nx as usize) < width
+                        && (ny as usize) < height
+                        && !inside[ny as usize * width + nx as usize]
+                })
+        })
+        .collect()
+}
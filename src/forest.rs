@@ -1,34 +1,212 @@
+use std::ops::RangeInclusive;
+
 use ndarray::{array, Array, Ix2};
-use rand::prelude::ThreadRng;
+use rand::distributions::{Distribution, WeightedIndex};
 use rand::Rng;
 
 use crate::ifs::IfsProgram;
+use crate::utils::rng::seeded_rng;
 
-pub fn draw_forest(program: &mut IfsProgram, rng: &mut ThreadRng, count: u32) {
-    let d: Array<f32, Ix2> = array![
+/// The tree every forest is made of unless configured otherwise.
+fn default_tree() -> Array<f32, Ix2> {
+    array![
         [0.0, 0.0, 0.0, 0.5, 0.0, 0.0, 0.05],
         [0.42, -0.42, 0.42, 0.42, 0.0, 0.2, 0.40],
         [0.42, 0.42, -0.42, 0.42, 0.0, 0.2, 0.40],
         [0.1, 0.0, 0.0, 0.1, 0.0, 0.2, 0.15],
-    ];
+    ]
+}
+
+/// Color that distant trees fade into, like the air between them and the viewer.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Haze {
+    pub color: [f32; 4],
+    /// How far the most distant trees are tinted toward `color`, from 0 (not at all) to 1
+    /// (entirely). The nearest trees are never tinted.
+    pub strength: f32,
+}
 
-    for _ in 0..count {
-        let shift_x = rng.gen_range(-0.5..0.5);
-        let shift_y = rng.gen_range(-0.5..0.5);
-        let scale = rng.gen_range(1.0..10.0);
+/// How [`ForestConfig::draw`] picks and places its trees.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ForestConfig {
+    /// IFS every tree is sampled from, in the 7-column layout of [`IfsProgram::sample_affine`].
+    pub tree: Array<f32, Ix2>,
+    /// Colors of the trees with their relative weights.
+    pub palette: Vec<([f32; 4], f32)>,
+    /// Ranges the shifts passed to [`IfsProgram::sample_affine`] are drawn from.
+    pub shift_x: RangeInclusive<f32>,
+    pub shift_y: RangeInclusive<f32>,
+    /// Range the scale of every tree is drawn from. Smaller trees count as farther away.
+    pub scale: RangeInclusive<f32>,
+    pub trees: u32,
+    /// Chaos game iterations per tree.
+    pub iterations: usize,
+    /// Places the trees with a generator of their own seeded with this, so the forest stays the
+    /// same whatever else is drawn with the generator passed to [`ForestConfig::draw`].
+    pub seed: Option<u64>,
+    pub haze: Option<Haze>,
+}
 
-        let color = {
-            match rng.gen_range(0..=9) {
+impl Default for ForestConfig {
+    fn default() -> Self {
+        Self {
+            tree: default_tree(),
+            palette: vec![
                 // Most trees are green
-                0..=7 => [0.0, 0.39, 0.0, 1.0],
+                ([0.0, 0.39, 0.0, 1.0], 8.0),
                 // Some trees are yellow
-                8 => [0.8, 0.95, 0.0, 1.0],
+                ([0.8, 0.95, 0.0, 1.0], 1.0),
                 // Some trees are dead (brown)
-                9 => [0.64, 0.16, 0.16, 1.0],
-                _ => unreachable!(),
+                ([0.64, 0.16, 0.16, 1.0], 1.0),
+            ],
+            shift_x: -0.5..=0.5,
+            shift_y: -0.5..=0.5,
+            scale: 1.0..=10.0,
+            trees: 75,
+            iterations: 2000,
+            seed: None,
+            haze: None,
+        }
+    }
+}
+
+/// Where one tree goes and what it looks like.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct TreePlacement {
+    pub shift_x: f32,
+    pub shift_y: f32,
+    pub scale: f32,
+    pub color: [f32; 4],
+}
+
+impl ForestConfig {
+    /// Picks the position, size and color of every tree, farthest (smallest) first so that
+    /// nearer trees are drawn on top of them. Colors are already tinted by the haze.
+    ///
+    /// Panics if the palette has no color with a positive weight.
+    pub fn placements<R: Rng + ?Sized>(&self, rng: &mut R) -> Vec<TreePlacement> {
+        let colors = WeightedIndex::new(self.palette.iter().map(|(_, weight)| *weight))
+            .unwrap_or_else(|e| panic!("invalid forest palette: {}", e));
+
+        let mut placements: Vec<TreePlacement> = (0..self.trees)
+            .map(|_| TreePlacement {
+                shift_x: rng.gen_range(self.shift_x.clone()),
+                shift_y: rng.gen_range(self.shift_y.clone()),
+                scale: rng.gen_range(self.scale.clone()),
+                color: self.palette[colors.sample(rng)].0,
+            })
+            .collect();
+        placements.sort_by(|a, b| a.scale.total_cmp(&b.scale));
+
+        if let Some(haze) = self.haze {
+            let (near, far) = (*self.scale.end(), *self.scale.start());
+            for tree in &mut placements {
+                let distance = if near > far {
+                    (near - tree.scale) / (near - far)
+                } else {
+                    0.0
+                };
+                let t = (distance * haze.strength).clamp(0.0, 1.0);
+                for (c, h) in tree.color.iter_mut().zip(haze.color) {
+                    *c += (h - *c) * t;
+                }
             }
+        }
+
+        placements
+    }
+
+    /// Samples every tree into `program`, drawing random numbers from `rng` unless the config
+    /// has a seed of its own.
+    ///
+    /// Panics if the tree is not a valid IFS, see [`IfsProgram::sample_affine`], or if the palette
+    /// is invalid, see [`ForestConfig::placements`].
+    pub fn draw<R: Rng + ?Sized>(&self, program: &mut IfsProgram, rng: &mut R) {
+        match self.seed {
+            Some(seed) => self.draw_with_rng(program, &mut seeded_rng(Some(seed))),
+            None => self.draw_with_rng(program, rng),
+        }
+    }
+
+    fn draw_with_rng<R: Rng + ?Sized>(&self, program: &mut IfsProgram, rng: &mut R) {
+        for tree in self.placements(rng) {
+            program.sample_affine_with_rng(
+                rng,
+                &self.tree,
+                tree.color,
+                self.iterations,
+                tree.scale,
+                tree.shift_x,
+                tree.shift_y,
+            );
+        }
+    }
+}
+
+pub fn draw_forest<R: Rng + ?Sized>(program: &mut IfsProgram, rng: &mut R, count: u32) {
+    draw_trees(program, rng, count, 2000, None);
+}
+
+/// Same as [`draw_forest`] with `iterations` chaos game iterations per tree, and every tree in
+/// `color` if given instead of the usual mix of green, yellow and dead trees.
+pub fn draw_trees<R: Rng + ?Sized>(
+    program: &mut IfsProgram,
+    rng: &mut R,
+    count: u32,
+    iterations: usize,
+    color: Option<[f32; 4]>,
+) {
+    let mut config = ForestConfig {
+        trees: count,
+        iterations,
+        ..ForestConfig::default()
+    };
+    if let Some(color) = color {
+        config.palette = vec![(color, 1.0)];
+    }
+    config.draw(program, rng);
+}
+
+#[cfg(test)]
+mod test {
+    use crate::forest::{ForestConfig, Haze};
+    use crate::utils::rng::seeded_rng;
+
+    #[test]
+    fn far_trees_first_and_hazy() {
+        let config = ForestConfig {
+            trees: 50,
+            palette: vec![([0.0, 0.0, 0.0, 1.0], 1.0)],
+            haze: Some(Haze {
+                color: [1.0, 1.0, 1.0, 1.0],
+                strength: 0.8,
+            }),
+            ..ForestConfig::default()
         };
+        let placements = config.placements(&mut seeded_rng(Some(1)));
+        assert_eq!(placements.len(), 50);
 
-        program.sample_affine(&d, color, 2000, scale, shift_x, shift_y);
+        for pair in placements.windows(2) {
+            assert!(pair[0].scale <= pair[1].scale);
+            // Nearer trees are less hazy, i.e. darker
+            assert!(pair[0].color[0] >= pair[1].color[0]);
+        }
+        for tree in &placements {
+            let expected = 0.8 * (10.0 - tree.scale) / 9.0;
+            assert!((tree.color[0] - expected).abs() < 1e-5, "{:?}", tree);
+            assert_eq!(tree.color[3], 1.0);
+        }
+    }
+
+    #[test]
+    fn palette_weights() {
+        let config = ForestConfig {
+            trees: 1000,
+            palette: vec![([1.0, 0.0, 0.0, 1.0], 3.0), ([0.0, 0.0, 1.0, 1.0], 1.0)],
+            ..ForestConfig::default()
+        };
+        let placements = config.placements(&mut seeded_rng(Some(2)));
+        let red = placements.iter().filter(|t| t.color[0] == 1.0).count();
+        assert!((700..800).contains(&red), "{}", red);
     }
 }
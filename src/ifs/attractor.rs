@@ -0,0 +1,277 @@
+//! Bounds of an attractor computed from its maps, without sampling it.
+//!
+//! Bounds taken from sampled points need every point, and a few stray ones from before the chaos
+//! game converged can stretch them. The bounds here are known up front and guaranteed to contain
+//! the attractor, so points can be normalized and a view framed before the first one is sampled.
+//!
+//! A [`Ball`] that every map sends into itself contains the attractor. That ball is usually
+//! generous, so [`attractor_hull`] shrinks a polygon around it by repeatedly replacing it with the
+//! convex hull of its images under the maps, which still contains the attractor.
+
+use std::f32::consts::PI;
+
+use crate::ifs::{fixed_point, operator_norm, Bounds};
+
+/// Iterations of [`attractor_hull`] at most. Each one brings the polygons closer to the
+/// attractor by at least the largest contraction factor.
+const MAX_REFINEMENTS: usize = 200;
+
+/// A disk in the plane.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Ball {
+    pub center: [f32; 2],
+    pub radius: f32,
+}
+
+impl Ball {
+    pub fn bounds(&self) -> Bounds {
+        let [x, y] = self.center;
+        Bounds {
+            x_min: x - self.radius,
+            x_max: x + self.radius,
+            y_min: y - self.radius,
+            y_max: y + self.radius,
+        }
+    }
+}
+
+/// A ball containing the attractor of the conventional affine maps `maps`, or `None` unless
+/// they are all contractions.
+///
+/// The ball is centered on the mean of the fixed points. A map `f` with contraction factor `r`
+/// sends the ball with center `c` and radius `R` into itself if `|f(c) - c| + rR <= R`, so the
+/// radius is the smallest one that works for every map.
+pub fn bounding_ball(maps: &[[f32; 6]]) -> Option<Ball> {
+    if maps.is_empty() {
+        return None;
+    }
+
+    let mut center = [0.0, 0.0];
+    for &coefs in maps {
+        if operator_norm(coefs) >= 1.0 {
+            return None;
+        }
+        let [x, y] = fixed_point(coefs)?;
+        center[0] += x / maps.len() as f32;
+        center[1] += y / maps.len() as f32;
+    }
+
+    let radius = maps
+        .iter()
+        .map(|&coefs| {
+            let [x, y] = apply(coefs, center);
+            let moved = (x - center[0]).hypot(y - center[1]);
+            moved / (1.0 - operator_norm(coefs))
+        })
+        .fold(0.0, f32::max);
+
+    Some(Ball { center, radius })
+}
+
+fn apply([a, b, c, d, e, f]: [f32; 6], [x, y]: [f32; 2]) -> [f32; 2] {
+    [a * x + b * y + e, c * x + d * y + f]
+}
+
+/// Vertices of the convex hull of `points`, counterclockwise. Vertices on an edge are left out.
+pub fn convex_hull(points: &[[f32; 2]]) -> Vec<[f32; 2]> {
+    let mut points = points.to_vec();
+    points.sort_by(|p, q| p[0].total_cmp(&q[0]).then(p[1].total_cmp(&q[1])));
+    points.dedup();
+    if points.len() < 3 {
+        return points;
+    }
+
+    let cross = |o: [f32; 2], a: [f32; 2], b: [f32; 2]| {
+        (a[0] - o[0]) * (b[1] - o[1]) - (a[1] - o[1]) * (b[0] - o[0])
+    };
+
+    // Andrew's monotone chain: the lower hull left to right, then the upper hull right to left
+    // without touching the lower one
+    let mut hull: Vec<[f32; 2]> = Vec::with_capacity(points.len() + 1);
+    let extend = |hull: &mut Vec<[f32; 2]>, p: [f32; 2], floor: usize| {
+        while hull.len() >= floor + 2 && cross(hull[hull.len() - 2], hull[hull.len() - 1], p) <= 0.0
+        {
+            hull.pop();
+        }
+        hull.push(p);
+    };
+    for &p in &points {
+        extend(&mut hull, p, 0);
+    }
+    let floor = hull.len() - 1;
+    for &p in points.iter().rev().skip(1) {
+        extend(&mut hull, p, floor);
+    }
+    // The last point is the first one again
+    hull.pop();
+    hull
+}
+
+/// Vertices of a convex polygon containing the attractor of the conventional affine maps `maps`,
+/// counterclockwise, or `None` unless they are all contractions.
+///
+/// `allowed(i, j)` tells whether map `j` may be applied right after map `i`, so this also covers
+/// recurrent IFS, see [`recurrent`](crate::ifs::recurrent). Maps that can never be applied are
+/// left out, apart from the very first point of the chaos game.
+///
+/// Starting from a polygon around the [`bounding_ball`], the polygon of the points produced by
+/// each map is repeatedly replaced by the convex hull of its image under the map. Every step
+/// keeps the attractor inside and brings the polygons closer to its convex hull.
+pub fn attractor_hull<F>(maps: &[[f32; 6]], allowed: F) -> Option<Vec<[f32; 2]>>
+where
+    F: Fn(usize, usize) -> bool,
+{
+    let ball = bounding_ball(maps)?;
+    // A regular polygon around the ball
+    let corners = 16;
+    let radius = ball.radius / (PI / corners as f32).cos() * 1.001;
+    let start: Vec<[f32; 2]> = (0..corners)
+        .map(|i| {
+            let (sin, cos) = (2.0 * PI * i as f32 / corners as f32).sin_cos();
+            [ball.center[0] + radius * cos, ball.center[1] + radius * sin]
+        })
+        .collect();
+    let tolerance = ball.radius * 1e-6;
+
+    // Hulls of the points right after applying each map, or `None` if it is never applied
+    let mut pieces: Vec<Option<Vec<[f32; 2]>>> = vec![Some(start); maps.len()];
+    for _ in 0..MAX_REFINEMENTS {
+        let refined: Vec<Option<Vec<[f32; 2]>>> = (0..maps.len())
+            .map(|j| {
+                let before: Vec<[f32; 2]> = (0..maps.len())
+                    .filter(|&i| allowed(i, j))
+                    .filter_map(|i| pieces[i].as_ref())
+                    .flatten()
+                    .map(|&p| apply(maps[j], p))
+                    .collect();
+                (!before.is_empty()).then(|| convex_hull(&before))
+            })
+            .collect();
+
+        let converged = pieces
+            .iter()
+            .zip(&refined)
+            .all(|(old, new)| match (old, new) {
+                (Some(old), Some(new)) => {
+                    let (old, new) = (hull_bounds(old), hull_bounds(new));
+                    (old.width() - new.width()).abs() <= tolerance
+                        && (old.height() - new.height()).abs() <= tolerance
+                }
+                (None, None) => true,
+                _ => false,
+            });
+        pieces = refined;
+        if converged {
+            break;
+        }
+    }
+
+    let points: Vec<[f32; 2]> = pieces.into_iter().flatten().flatten().collect();
+    (!points.is_empty()).then(|| convex_hull(&points))
+}
+
+fn hull_bounds(points: &[[f32; 2]]) -> Bounds {
+    Bounds::from_points_trimmed(points, 1.0).unwrap()
+}
+
+/// Bounds containing the attractor of the conventional affine maps `maps`, or `None` unless they
+/// are all contractions. The bounds of the polygon from [`attractor_hull`].
+pub fn attractor_bounds<F>(maps: &[[f32; 6]], allowed: F) -> Option<Bounds>
+where
+    F: Fn(usize, usize) -> bool,
+{
+    attractor_hull(maps, allowed).map(|hull| hull_bounds(&hull))
+}
+
+#[cfg(test)]
+mod test {
+    use ndarray::array;
+
+    use crate::ifs::attractor::{attractor_bounds, attractor_hull, bounding_ball, convex_hull};
+    use crate::ifs::density::estimate_bounds;
+    use crate::ifs::system::Ifs;
+    use crate::ifs::{effective_map, Bounds};
+    use crate::utils::rng::seeded_rng;
+
+    fn contains(outer: &Bounds, inner: &Bounds) -> bool {
+        outer.x_min <= inner.x_min
+            && outer.x_max >= inner.x_max
+            && outer.y_min <= inner.y_min
+            && outer.y_max >= inner.y_max
+    }
+
+    #[test]
+    fn hull() {
+        let points = [
+            [0.0, 0.0],
+            [1.0, 0.0],
+            [0.5, 0.5],
+            [1.0, 1.0],
+            [0.0, 1.0],
+            [0.5, 1.0],
+            [1.0, 0.0],
+        ];
+        assert_eq!(
+            convex_hull(&points),
+            vec![[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.0]]
+        );
+        assert_eq!(convex_hull(&[[1.0, 2.0], [1.0, 2.0]]), vec![[1.0, 2.0]]);
+    }
+
+    #[test]
+    fn sierpinski_is_exact() {
+        let maps = [
+            [0.5, 0.0, 0.0, 0.5, 0.0, 0.0],
+            [0.5, 0.0, 0.0, 0.5, 0.5, 0.0],
+            [0.5, 0.0, 0.0, 0.5, 0.0, 0.5],
+        ];
+        // Just touching the corners of the triangle
+        let ball = bounding_ball(&maps).unwrap();
+        assert!(
+            (ball.radius - 5.0_f32.sqrt() / 3.0).abs() < 1e-5,
+            "{:?}",
+            ball
+        );
+
+        // The corners of the starting polygon shrink onto the corners of the triangle
+        let hull = attractor_hull(&maps, |_, _| true).unwrap();
+        for [x, y] in hull {
+            let corner = [[0.0, 0.0], [1.0, 0.0], [0.0, 1.0]]
+                .iter()
+                .any(|c| (x - c[0]).abs() < 1e-5 && (y - c[1]).abs() < 1e-5);
+            assert!(corner, "{} {}", x, y);
+        }
+
+        let bounds = attractor_bounds(&maps, |_, _| true).unwrap();
+        for v in [bounds.x_min, bounds.y_min] {
+            assert!(v.abs() < 1e-4, "{:?}", bounds);
+        }
+        for v in [bounds.x_max, bounds.y_max] {
+            assert!((v - 1.0).abs() < 1e-4, "{:?}", bounds);
+        }
+
+        // Not contractive
+        assert_eq!(
+            attractor_bounds(&[[1.0, 0.0, 0.0, 0.5, 0.0, 0.0]], |_, _| true),
+            None
+        );
+    }
+
+    #[test]
+    fn contains_sampled_fern() {
+        let rows = array![
+            [0.0, 0.0, 0.0, 0.16, 0.0, 0.0, 0.01],
+            [0.85, 0.04, -0.04, 0.85, 0.0, 1.6, 0.85],
+            [0.2, -0.26, 0.23, 0.22, 0.0, 1.6, 0.07],
+            [-0.15, 0.28, 0.26, 0.24, 0.0, 0.44, 0.07],
+        ];
+        let maps: Vec<[f32; 6]> = rows.rows().into_iter().map(effective_map).collect();
+        let bounds = attractor_bounds(&maps, |_, _| true).unwrap();
+
+        let ifs = Ifs::new(rows).unwrap();
+        let sampled = estimate_bounds(&ifs, &mut seeded_rng(Some(1)), 100_000);
+        // The sampled bounds are padded by 2% on every side
+        assert!(contains(&sampled.padded(0.01), &bounds.padded(-0.01)));
+        assert!(contains(&bounds, &sampled.padded(-0.03)));
+    }
+}
@@ -0,0 +1,619 @@
+//! Fractal image compression with a partitioned IFS.
+//!
+//! The image is cut into square range blocks. Each one is approximated by a domain block twice
+//! its size somewhere else in the image, shrunk by averaging 2x2 pixels, turned by one of the 8
+//! symmetries of the square and adjusted in contrast and brightness: `r ≈ s * d + o`. Only the
+//! position of the domain, the symmetry, `s` and `o` are stored for every range.
+//!
+//! Together these maps form a contraction on the space of images as long as `|s| < 1`, so
+//! iterating them from any starting image converges to their attractor. By the collage theorem
+//! the attractor is close to the original image if each range block is close to its transformed
+//! domain block: the distance is at most the collage error divided by `1 - max |s|`.
+
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use std::thread;
+
+use bitvec::order::Msb0;
+use bitvec::vec::BitVec;
+use ndarray::{s, Array, Ix2};
+
+use crate::image::{to_rgba8, Image};
+
+/// A grayscale image with values from 0 (black) to 1 (white), indexed by `[row, column]` with
+/// row 0 at the top.
+pub type Gray = Array<f32, Ix2>;
+
+/// Luminance of every pixel, with transparent pixels blended over white.
+pub fn gray_from_image(image: &Image) -> Gray {
+    Gray::from_shape_fn(
+        (image.height() as usize, image.width() as usize),
+        |(r, c)| {
+            let [red, green, blue, alpha] = image.pixel(c as u32, r as u32);
+            let luminance =
+                (0.299 * red as f32 + 0.587 * green as f32 + 0.114 * blue as f32) / 255.0;
+            let alpha = alpha as f32 / 255.0;
+            luminance * alpha + 1.0 - alpha
+        },
+    )
+}
+
+pub fn gray_to_image(gray: &Gray) -> Image {
+    let (rows, cols) = gray.dim();
+    let mut image = Image::new(cols as u32, rows as u32, [0.0, 0.0, 0.0, 1.0]);
+    for ((r, c), &v) in gray.indexed_iter() {
+        image.set_pixel(c as i64, r as i64, to_rgba8([v, v, v, 1.0]));
+    }
+    image
+}
+
+/// Peak signal-to-noise ratio between two images of the same size in decibels, infinite if they
+/// are the same. Higher is better, around 30 dB differences are hard to see.
+pub fn psnr(a: &Gray, b: &Gray) -> f64 {
+    assert_eq!(a.dim(), b.dim(), "images differ in size");
+    let mse = a
+        .iter()
+        .zip(b)
+        .map(|(x, y)| (f64::from(*x) - f64::from(*y)).powi(2))
+        .sum::<f64>()
+        / a.len() as f64;
+    -10.0 * mse.log10()
+}
+
+/// Number of symmetries of the square, 4 rotations with and without a mirror image.
+const ISOMETRIES: u8 = 8;
+
+/// Identifies compressed files.
+const MAGIC: &[u8; 4] = b"PIFS";
+
+/// Index of the pixel of an `n` x `n` block, stored row by row, that ends up at `(x, y)` when the
+/// block is transformed by isometry `i`.
+fn isometry_source(i: u8, x: usize, y: usize, n: usize) -> usize {
+    let (mut x, mut y) = if i & 4 != 0 { (n - 1 - x, y) } else { (x, y) };
+    for _ in 0..i & 3 {
+        (x, y) = (n - 1 - y, x);
+    }
+    y * n + x
+}
+
+/// Value of quantization level `q` out of the `2^bits - 1` between `min` and `max`. Using an odd
+/// number of levels makes the middle of the range exact, so that e.g. a contrast of 0 is.
+fn dequantize(q: u32, min: f32, max: f32, bits: u8) -> f32 {
+    let steps = (1_u32 << bits) - 2;
+    min + (max - min) * q as f32 / steps as f32
+}
+
+fn quantize(v: f32, min: f32, max: f32, bits: u8) -> u32 {
+    let steps = (1_u32 << bits) - 2;
+    ((v - min) / (max - min) * steps as f32)
+        .round()
+        .clamp(0.0, steps as f32) as u32
+}
+
+/// `image` with edge pixels repeated, or cut off, to make it `rows` x `cols`.
+fn padded(image: &Gray, rows: usize, cols: usize) -> Gray {
+    let (r_max, c_max) = (image.nrows() - 1, image.ncols() - 1);
+    Gray::from_shape_fn((rows, cols), |(r, c)| image[[r.min(r_max), c.min(c_max)]])
+}
+
+/// How one range block is approximated, with contrast and brightness quantized.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct BlockMap {
+    /// Index of the domain block, row by row.
+    pub domain: u32,
+    /// Which of the 8 symmetries of the square turns the shrunk domain block.
+    pub isometry: u8,
+    pub contrast: u32,
+    pub brightness: u32,
+}
+
+/// Settings of the encoder. Smaller ranges and domain steps give better quality, at the cost of
+/// bigger files and a slower search.
+#[derive(Clone, Debug)]
+pub struct Encoder {
+    /// Side of the range blocks in pixels. Domain blocks are twice as large.
+    pub range_size: usize,
+    /// Distance between neighboring domain blocks in pixels. The search time grows with the
+    /// square of the number of blocks per side, so this is the main trade-off between speed and
+    /// quality.
+    pub domain_step: usize,
+    /// Largest allowed `|s|`, between 0 and 1 exclusive so that the decoder is guaranteed to
+    /// converge.
+    pub max_contrast: f32,
+    /// Bits used to store the contrast and brightness of each block, 2 to 16.
+    pub contrast_bits: u8,
+    pub brightness_bits: u8,
+    /// Range blocks are split between this many threads.
+    pub threads: usize,
+}
+
+impl Default for Encoder {
+    fn default() -> Self {
+        Self {
+            range_size: 8,
+            domain_step: 16,
+            max_contrast: 0.9,
+            contrast_bits: 5,
+            brightness_bits: 7,
+            threads: 1,
+        }
+    }
+}
+
+/// A shrunk domain block with the sums the least squares fit needs.
+struct Domain {
+    pixels: Vec<f32>,
+    sum: f32,
+    sum_squares: f32,
+}
+
+impl Encoder {
+    /// Finds the best domain block, isometry, contrast and brightness for every range block of
+    /// `image`.
+    ///
+    /// Panics if the settings are out of range.
+    pub fn encode(&self, image: &Gray) -> FractalCode {
+        assert!((1..=255).contains(&self.range_size), "invalid range size");
+        assert!((1..=255).contains(&self.domain_step), "invalid domain step");
+        assert!(
+            (2..=16).contains(&self.contrast_bits) && (2..=16).contains(&self.brightness_bits),
+            "contrast and brightness need 2 to 16 bits"
+        );
+        assert!(
+            self.max_contrast > 0.0 && self.max_contrast < 1.0,
+            "max contrast must be between 0 and 1"
+        );
+
+        let mut code = FractalCode {
+            width: image.ncols(),
+            height: image.nrows(),
+            range_size: self.range_size,
+            domain_step: self.domain_step,
+            max_contrast: self.max_contrast,
+            contrast_bits: self.contrast_bits,
+            brightness_bits: self.brightness_bits,
+            maps: vec![],
+        };
+        let (rows, cols) = code.padded_size();
+        let image = padded(image, rows, cols);
+
+        let n = self.range_size;
+        let domains: Vec<Domain> = (0..code.domain_count())
+            .map(|i| {
+                let (x, y) = code.domain_origin(i);
+                let pixels: Vec<f32> = (0..n * n)
+                    .map(|k| shrunk_pixel(&image, x, y, k % n, k / n))
+                    .collect();
+                Domain {
+                    sum: pixels.iter().sum(),
+                    sum_squares: pixels.iter().map(|v| v * v).sum(),
+                    pixels,
+                }
+            })
+            .collect();
+        let sources: Vec<Vec<usize>> = (0..ISOMETRIES)
+            .map(|i| {
+                (0..n * n)
+                    .map(|k| isometry_source(i, k % n, k / n, n))
+                    .collect()
+            })
+            .collect();
+
+        let ranges = code.range_count();
+        let chunk = ranges.div_ceil(self.threads.max(1)).max(1);
+        let code_ref = &code;
+        let (image, domains, sources) = (&image, &domains, &sources);
+        let maps = thread::scope(|scope| {
+            let handles: Vec<_> = (0..ranges)
+                .step_by(chunk)
+                .map(|start| {
+                    scope.spawn(move || {
+                        (start..(start + chunk).min(ranges))
+                            .map(|i| self.best_map(code_ref, image, i, domains, sources))
+                            .collect::<Vec<_>>()
+                    })
+                })
+                .collect();
+            handles
+                .into_iter()
+                .flat_map(|h| h.join().unwrap())
+                .collect()
+        });
+        code.maps = maps;
+        code
+    }
+
+    /// The map giving the smallest squared error for range block `i`.
+    fn best_map(
+        &self,
+        code: &FractalCode,
+        image: &Gray,
+        i: usize,
+        domains: &[Domain],
+        sources: &[Vec<usize>],
+    ) -> BlockMap {
+        let n = self.range_size;
+        let (x, y) = code.range_origin(i);
+        let range: Vec<f32> = (0..n * n).map(|k| image[[y + k / n, x + k % n]]).collect();
+        let count = (n * n) as f32;
+        let sum_r: f32 = range.iter().sum();
+        let sum_r2: f32 = range.iter().map(|v| v * v).sum();
+        let range = &range;
+
+        let (min_s, max_s) = (-self.max_contrast, self.max_contrast);
+        let (min_o, max_o) = (-self.max_contrast, 1.0 + self.max_contrast);
+
+        let fits = domains.iter().enumerate().flat_map(|(d, domain)| {
+            sources.iter().enumerate().map(move |(isometry, source)| {
+                let sum_dr: f32 = range
+                    .iter()
+                    .zip(source)
+                    .map(|(r, &k)| r * domain.pixels[k])
+                    .sum();
+
+                let denominator = count * domain.sum_squares - domain.sum * domain.sum;
+                let s = if denominator.abs() < 1e-6 {
+                    0.0
+                } else {
+                    (count * sum_dr - domain.sum * sum_r) / denominator
+                };
+                let contrast = quantize(s, min_s, max_s, self.contrast_bits);
+                let s = dequantize(contrast, min_s, max_s, self.contrast_bits);
+                let brightness = quantize(
+                    (sum_r - s * domain.sum) / count,
+                    min_o,
+                    max_o,
+                    self.brightness_bits,
+                );
+                let o = dequantize(brightness, min_o, max_o, self.brightness_bits);
+
+                // Sum of (s * d + o - r)² expanded in terms of the sums
+                let error = s * s * domain.sum_squares + 2.0 * s * o * domain.sum
+                    - 2.0 * s * sum_dr
+                    + count * o * o
+                    - 2.0 * o * sum_r
+                    + sum_r2;
+                let map = BlockMap {
+                    domain: d as u32,
+                    isometry: isometry as u8,
+                    contrast,
+                    brightness,
+                };
+                (error, map)
+            })
+        });
+        // There is always at least one domain block
+        fits.min_by(|a, b| a.0.total_cmp(&b.0)).unwrap().1
+    }
+}
+
+/// Average of the 2x2 pixels of the domain block at `(x, y)` that shrink to `(sx, sy)`.
+fn shrunk_pixel(image: &Gray, x: usize, y: usize, sx: usize, sy: usize) -> f32 {
+    let (r, c) = (y + 2 * sy, x + 2 * sx);
+    (image[[r, c]] + image[[r, c + 1]] + image[[r + 1, c]] + image[[r + 1, c + 1]]) / 4.0
+}
+
+/// A compressed image: one [`BlockMap`] per range block, row by row.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FractalCode {
+    /// Size of the original image in pixels.
+    pub width: usize,
+    pub height: usize,
+    pub range_size: usize,
+    pub domain_step: usize,
+    pub max_contrast: f32,
+    pub contrast_bits: u8,
+    pub brightness_bits: u8,
+    pub maps: Vec<BlockMap>,
+}
+
+impl FractalCode {
+    /// Size the image is padded to, a whole number of range blocks and at least one domain block.
+    fn padded_size(&self) -> (usize, usize) {
+        let size = |pixels: usize| {
+            let n = self.range_size;
+            (pixels.div_ceil(n) * n).max(2 * n)
+        };
+        (size(self.height), size(self.width))
+    }
+
+    fn ranges_per_row(&self) -> usize {
+        self.padded_size().1 / self.range_size
+    }
+
+    pub fn range_count(&self) -> usize {
+        let (rows, cols) = self.padded_size();
+        rows / self.range_size * cols / self.range_size
+    }
+
+    fn domains_per_row(&self) -> usize {
+        (self.padded_size().1 - 2 * self.range_size) / self.domain_step + 1
+    }
+
+    pub fn domain_count(&self) -> usize {
+        let rows = (self.padded_size().0 - 2 * self.range_size) / self.domain_step + 1;
+        rows * self.domains_per_row()
+    }
+
+    /// Top left corner of range block `i`.
+    fn range_origin(&self, i: usize) -> (usize, usize) {
+        let per_row = self.ranges_per_row();
+        (
+            (i % per_row) * self.range_size,
+            (i / per_row) * self.range_size,
+        )
+    }
+
+    /// Top left corner of domain block `i`.
+    fn domain_origin(&self, i: usize) -> (usize, usize) {
+        let per_row = self.domains_per_row();
+        (
+            (i % per_row) * self.domain_step,
+            (i / per_row) * self.domain_step,
+        )
+    }
+
+    pub fn contrast(&self, map: &BlockMap) -> f32 {
+        let m = self.max_contrast;
+        dequantize(map.contrast, -m, m, self.contrast_bits)
+    }
+
+    pub fn brightness(&self, map: &BlockMap) -> f32 {
+        let m = self.max_contrast;
+        dequantize(map.brightness, -m, 1.0 + m, self.brightness_bits)
+    }
+
+    /// Applies every map once to a padded image.
+    fn apply(&self, image: &Gray) -> Gray {
+        let n = self.range_size;
+        let mut ret = Gray::zeros(image.dim());
+        for (i, map) in self.maps.iter().enumerate() {
+            let (rx, ry) = self.range_origin(i);
+            let (dx, dy) = self.domain_origin(map.domain as usize);
+            let (s, o) = (self.contrast(map), self.brightness(map));
+            for y in 0..n {
+                for x in 0..n {
+                    let k = isometry_source(map.isometry, x, y, n);
+                    let d = shrunk_pixel(image, dx, dy, k % n, k / n);
+                    ret[[ry + y, rx + x]] = (s * d + o).clamp(0.0, 1.0);
+                }
+            }
+        }
+        ret
+    }
+
+    /// The images after each iteration of the decoder, starting from `start`. A start of a
+    /// different size than the original is stretched to fit.
+    pub fn iterations<'a>(&'a self, start: &Gray) -> impl Iterator<Item = Gray> + 'a {
+        let (rows, cols) = start.dim();
+        let start = Gray::from_shape_fn((self.height, self.width), |(r, c)| {
+            start[[r * rows / self.height, c * cols / self.width]]
+        });
+        let (rows, cols) = self.padded_size();
+        let mut image = padded(&start, rows, cols);
+        std::iter::repeat_with(move || {
+            image = self.apply(&image);
+            image.slice(s![..self.height, ..self.width]).to_owned()
+        })
+    }
+
+    /// Decodes the image with `iterations` iterations, at least one, from `start`.
+    pub fn decode_from(&self, start: &Gray, iterations: usize) -> Gray {
+        self.iterations(start)
+            .take(iterations.max(1))
+            .last()
+            .unwrap()
+    }
+
+    /// Decodes the image with `iterations` iterations from a uniform gray one.
+    pub fn decode(&self, iterations: usize) -> Gray {
+        self.decode_from(&Gray::from_elem((1, 1), 0.5), iterations)
+    }
+
+    /// The maps applied once to `image` itself. By the collage theorem, the closer this is to
+    /// `image`, the closer the decoded image is too.
+    pub fn collage(&self, image: &Gray) -> Gray {
+        let (rows, cols) = self.padded_size();
+        self.apply(&padded(image, rows, cols))
+            .slice(s![..self.height, ..self.width])
+            .to_owned()
+    }
+
+    /// Bits needed to store a domain index.
+    fn domain_bits(&self) -> u32 {
+        usize::BITS - (self.domain_count() - 1).leading_zeros()
+    }
+
+    /// Writes a header with the size and settings, followed by the maps packed bit by bit.
+    pub fn write<W: Write>(&self, mut w: W) -> io::Result<()> {
+        w.write_all(MAGIC)?;
+        w.write_all(&(self.width as u32).to_le_bytes())?;
+        w.write_all(&(self.height as u32).to_le_bytes())?;
+        w.write_all(&[
+            self.range_size as u8,
+            self.domain_step as u8,
+            self.contrast_bits,
+            self.brightness_bits,
+        ])?;
+        w.write_all(&self.max_contrast.to_le_bytes())?;
+
+        let mut bits: BitVec<u8, Msb0> = BitVec::new();
+        let mut push = |value: u32, count: u32| {
+            for i in (0..count).rev() {
+                bits.push((value >> i) & 1 == 1);
+            }
+        };
+        for map in &self.maps {
+            push(map.domain, self.domain_bits());
+            push(map.isometry.into(), 3);
+            push(map.contrast, self.contrast_bits.into());
+            push(map.brightness, self.brightness_bits.into());
+        }
+        w.write_all(bits.as_raw_slice())
+    }
+
+    pub fn read<R: Read>(mut r: R) -> io::Result<Self> {
+        let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message);
+
+        let mut header = [0; 20];
+        r.read_exact(&mut header)?;
+        if &header[..4] != MAGIC {
+            return Err(invalid("not a compressed image"));
+        }
+        let le_u32 = |i: usize| u32::from_le_bytes(header[i..i + 4].try_into().unwrap());
+        let mut code = FractalCode {
+            width: le_u32(4) as usize,
+            height: le_u32(8) as usize,
+            range_size: header[12].into(),
+            domain_step: header[13].into(),
+            contrast_bits: header[14],
+            brightness_bits: header[15],
+            max_contrast: f32::from_le_bytes(header[16..20].try_into().unwrap()),
+            maps: vec![],
+        };
+        if code.width == 0
+            || code.height == 0
+            || code.range_size == 0
+            || code.domain_step == 0
+            || !(2..=16).contains(&code.contrast_bits)
+            || !(2..=16).contains(&code.brightness_bits)
+            || !(code.max_contrast > 0.0 && code.max_contrast < 1.0)
+        {
+            return Err(invalid("invalid header"));
+        }
+
+        let mut data = vec![];
+        r.read_to_end(&mut data)?;
+        let bits: BitVec<u8, Msb0> = BitVec::from_vec(data);
+        let mut bits = bits.into_iter();
+        let mut pull = |count: u32| -> io::Result<u32> {
+            (0..count).try_fold(0, |value, _| {
+                let bit = bits.next().ok_or_else(|| invalid("file ends too early"))?;
+                Ok(value << 1 | u32::from(bit))
+            })
+        };
+
+        let (domain_bits, domains) = (code.domain_bits(), code.domain_count() as u32);
+        for _ in 0..code.range_count() {
+            let map = BlockMap {
+                domain: pull(domain_bits)?,
+                isometry: pull(3)? as u8,
+                contrast: pull(code.contrast_bits.into())?,
+                brightness: pull(code.brightness_bits.into())?,
+            };
+            let steps = |bits: u8| (1 << bits) - 2;
+            if map.domain >= domains
+                || map.contrast > steps(code.contrast_bits)
+                || map.brightness > steps(code.brightness_bits)
+            {
+                return Err(invalid("invalid block map"));
+            }
+            code.maps.push(map);
+        }
+        Ok(code)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut w = BufWriter::new(File::create(path)?);
+        self.write(&mut w)?;
+        w.flush()
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Self::read(BufReader::new(File::open(path)?))
+    }
+
+    /// Size of the written file in bytes.
+    pub fn encoded_size(&self) -> usize {
+        let mut buf = vec![];
+        self.write(&mut buf).unwrap();
+        buf.len()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashSet;
+
+    use crate::ifs::compression::{isometry_source, psnr, Encoder, FractalCode, Gray};
+
+    /// A smooth gradient with a dark disk, which self-similar blocks approximate well.
+    fn test_image(rows: usize, cols: usize) -> Gray {
+        Gray::from_shape_fn((rows, cols), |(r, c)| {
+            let (x, y) = (c as f32 / cols as f32, r as f32 / rows as f32);
+            let disk = (x - 0.6).powi(2) + (y - 0.4).powi(2) < 0.06;
+            if disk {
+                0.1 + 0.2 * x
+            } else {
+                0.3 + 0.6 * y
+            }
+        })
+    }
+
+    #[test]
+    fn isometries() {
+        let n = 3;
+        let permutations: HashSet<Vec<usize>> = (0..8)
+            .map(|i| {
+                let p: Vec<usize> = (0..n * n)
+                    .map(|k| isometry_source(i, k % n, k / n, n))
+                    .collect();
+                let mut sorted = p.clone();
+                sorted.sort();
+                assert_eq!(sorted, (0..n * n).collect::<Vec<_>>());
+                p
+            })
+            .collect();
+        assert_eq!(permutations.len(), 8);
+        // A quarter turn brings the bottom right corner to the top right
+        assert_eq!(isometry_source(1, n - 1, 0, n), n * n - 1);
+    }
+
+    #[test]
+    fn round_trip() {
+        let image = test_image(32, 32);
+        let encoder = Encoder {
+            range_size: 4,
+            domain_step: 4,
+            threads: 3,
+            ..Encoder::default()
+        };
+        let code = encoder.encode(&image);
+        assert_eq!(code.maps.len(), 64);
+
+        let collage = psnr(&code.collage(&image), &image);
+        let decoded = psnr(&code.decode(10), &image);
+        assert!(collage > 25.0, "{}", collage);
+        assert!(decoded > 25.0, "{}", decoded);
+
+        // The attractor doesn't depend on where the decoder starts
+        let black = code.decode_from(&Gray::zeros((5, 7)), 20);
+        let white = code.decode_from(&Gray::ones((32, 32)), 20);
+        assert!(psnr(&black, &white) > 50.0);
+    }
+
+    #[test]
+    fn file_format() {
+        // Not a whole number of range blocks
+        let image = test_image(21, 30);
+        let code = Encoder::default().encode(&image);
+        assert_eq!(code.decode(4).dim(), (21, 30));
+
+        let mut buf = vec![];
+        code.write(&mut buf).unwrap();
+        assert_eq!(buf.len(), code.encoded_size());
+        assert!(buf.len() < 21 * 30 / 4);
+        assert_eq!(FractalCode::read(&buf[..]).unwrap(), code);
+
+        assert!(FractalCode::read(&buf[..buf.len() - 1]).is_err());
+        // A contrast that would keep the decoder from converging
+        for max_contrast in [1.5, 0.0, -0.5, f32::NAN] {
+            let mut corrupt = buf.clone();
+            corrupt[16..20].copy_from_slice(&max_contrast.to_le_bytes());
+            assert!(FractalCode::read(&corrupt[..]).is_err());
+        }
+        buf[0] = b'X';
+        assert!(FractalCode::read(&buf[..]).is_err());
+    }
+}
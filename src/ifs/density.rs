@@ -0,0 +1,419 @@
+//! Accumulating IFS points into a fixed-resolution histogram instead of storing them.
+//!
+//! [`IfsProgram`](crate::ifs::IfsProgram) keeps every sampled point around so it can upload them
+//! to the GPU, which limits it to a few million points. A [`DensityGrid`] only counts how often
+//! every cell is visited, so its memory use depends on the resolution alone and the chaos game
+//! can run for billions of iterations. The counts are turned into an image by a [`ToneMap`].
+
+use glium::glutin::dpi::LogicalSize;
+use glium::glutin::event::{Event, WindowEvent};
+use glium::glutin::event_loop::{ControlFlow, EventLoop};
+use glium::glutin::window::WindowBuilder;
+use glium::glutin::ContextBuilder;
+use glium::texture::RawImage2d;
+use glium::uniforms::MagnifySamplerFilter;
+use glium::{Display, Surface, Texture2d};
+use rand::Rng;
+
+use crate::args::ColorScheme;
+use crate::ifs::system::MapSystem;
+use crate::ifs::Bounds;
+use crate::image::{to_rgba8, Image};
+use crate::utils::parallel::run_workers;
+
+/// Number of initial iterations that are not counted, while the point converges to the
+/// attractor.
+const SKIP: u64 = 10;
+
+/// Runs the chaos game from the origin and returns `samples` points, skipping the first few
+/// while the point converges to the attractor.
+pub fn sample_points<R, S>(ifs: &S, rng: &mut R, samples: usize) -> Vec<[f32; 2]>
+where
+    R: Rng + ?Sized,
+    S: MapSystem,
+{
+    let mut x: f32 = 0.0;
+    let mut y: f32 = 0.0;
+    let mut previous = None;
+    let mut points = Vec::with_capacity(samples);
+    for i in 0..samples as u64 + SKIP {
+        let map = ifs.next_map(rng, previous);
+        previous = Some(map);
+        (x, y) = ifs.apply(map, x, y);
+        if i >= SKIP {
+            points.push([x, y]);
+        }
+    }
+    points
+}
+
+/// Runs the chaos game without storing anything and returns bounds containing nearly all of the
+/// attractor, for use as the first pass before accumulating into a [`DensityGrid`].
+pub fn estimate_bounds<R, S>(ifs: &S, rng: &mut R, samples: usize) -> Bounds
+where
+    R: Rng + ?Sized,
+    S: MapSystem,
+{
+    let points = sample_points(ifs, rng, samples);
+    Bounds::from_points_trimmed(&points, 0.999)
+        .map(|b| b.padded(0.02))
+        .unwrap_or(Bounds {
+            x_min: -1.0,
+            x_max: 1.0,
+            y_min: -1.0,
+            y_max: 1.0,
+        })
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+struct Cell {
+    count: u64,
+    /// Sum of the colors of all points in the cell. Kept in f64 so that adding one more point to
+    /// a cell visited millions of times still changes the sum.
+    color: [f64; 3],
+}
+
+/// Visit counts of a grid of cells covering `bounds`. Row 0 is at the top, like in [`Image`].
+#[derive(Clone, Debug)]
+pub struct DensityGrid {
+    bounds: Bounds,
+    width: u32,
+    height: u32,
+    cells: Vec<Cell>,
+    total: u64,
+    threads: usize,
+}
+
+impl DensityGrid {
+    pub fn new(bounds: Bounds, width: u32, height: u32) -> Self {
+        Self {
+            bounds,
+            width,
+            height,
+            cells: vec![Cell::default(); width as usize * height as usize],
+            total: 0,
+            threads: 1,
+        }
+    }
+
+    /// Splits [`DensityGrid::accumulate`] between `threads` threads, each counting into a grid
+    /// of its own that is merged when it is done. Like
+    /// [`IfsProgram::set_threads`](crate::ifs::IfsProgram::set_threads), seeded runs are
+    /// reproducible for a fixed number of threads. Defaults to 1.
+    pub fn set_threads(&mut self, threads: usize) {
+        self.threads = threads.max(1);
+    }
+
+    pub fn bounds(&self) -> Bounds {
+        self.bounds
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Number of points that landed inside the bounds.
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    pub fn count(&self, x: u32, y: u32) -> u64 {
+        self.cells[y as usize * self.width as usize + x as usize].count
+    }
+
+    pub fn max_count(&self) -> u64 {
+        self.cells.iter().map(|c| c.count).max().unwrap_or(0)
+    }
+
+    /// Counts a point with the given RGB color. Returns false if it is outside the bounds.
+    pub fn add(&mut self, x: f32, y: f32, color: [f32; 3]) -> bool {
+        let b = self.bounds;
+        if !(x >= b.x_min && x <= b.x_max && y >= b.y_min && y <= b.y_max) {
+            return false;
+        }
+
+        let (px, py) = b.to_pixel(x, y, self.width, self.height);
+        // Points on the far edges of the bounds belong to the last row or column
+        let px = px.min(self.width as f32 - 1.0);
+        let py = py.min(self.height as f32 - 1.0);
+
+        let cell = &mut self.cells[py as usize * self.width as usize + px as usize];
+        cell.count += 1;
+        for (sum, c) in cell.color.iter_mut().zip(color) {
+            *sum += c as f64;
+        }
+        self.total += 1;
+        true
+    }
+
+    /// Runs the chaos game for `iters` iterations and counts every point, transformed the same
+    /// way as [`IfsProgram::sample_ifs`](crate::ifs::IfsProgram::sample_ifs) does.
+    #[allow(clippy::too_many_arguments)]
+    pub fn accumulate<R: Rng + ?Sized, S: MapSystem>(
+        &mut self,
+        rng: &mut R,
+        ifs: &S,
+        color: [f32; 3],
+        iters: u64,
+        scale: f32,
+        shift_x: f32,
+        shift_y: f32,
+    ) {
+        if self.threads <= 1 {
+            self.accumulate_chain(rng, ifs, color, iters, scale, shift_x, shift_y);
+            return;
+        }
+
+        let (bounds, width, height) = (self.bounds, self.width, self.height);
+        let grids = run_workers(rng, self.threads, iters, |rng, iters| {
+            let mut grid = DensityGrid::new(bounds, width, height);
+            grid.accumulate_chain(rng, ifs, color, iters, scale, shift_x, shift_y);
+            grid
+        });
+        for grid in &grids {
+            self.merge(grid);
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn accumulate_chain<R: Rng + ?Sized, S: MapSystem>(
+        &mut self,
+        rng: &mut R,
+        ifs: &S,
+        color: [f32; 3],
+        iters: u64,
+        scale: f32,
+        shift_x: f32,
+        shift_y: f32,
+    ) {
+        let mut x: f32 = 0.0;
+        let mut y: f32 = 0.0;
+        let mut previous = None;
+        for i in 0..iters {
+            let map = ifs.next_map(rng, previous);
+            previous = Some(map);
+            (x, y) = ifs.apply(map, x, y);
+            if i >= SKIP {
+                self.add((x + shift_x) * scale, (y + shift_y) * scale, color);
+            }
+        }
+    }
+
+    /// Adds the counts of another grid with the same bounds and resolution.
+    pub fn merge(&mut self, other: &DensityGrid) {
+        assert_eq!(
+            (self.bounds, self.width, self.height),
+            (other.bounds, other.width, other.height),
+            "can only merge grids covering the same cells"
+        );
+
+        for (cell, other) in self.cells.iter_mut().zip(&other.cells) {
+            cell.count += other.count;
+            for (sum, c) in cell.color.iter_mut().zip(other.color) {
+                *sum += c;
+            }
+        }
+        self.total += other.total;
+    }
+
+    pub fn render(&self, tone_map: &ToneMap) -> Image {
+        let mut image = Image::new(self.width, self.height, tone_map.background);
+        let max = self.max_count();
+        if max == 0 {
+            return image;
+        }
+
+        for (i, cell) in self.cells.iter().enumerate() {
+            if cell.count == 0 {
+                continue;
+            }
+
+            let t = tone_map.intensity(cell.count, max);
+            let [r, g, b] = match tone_map.palette {
+                Some(palette) => palette.color(t),
+                None => {
+                    // Average color of the points in the cell, faded into the background
+                    let n = cell.count as f64;
+                    let mut color = [0.0; 3];
+                    for (channel, sum) in cell.color.iter().enumerate() {
+                        let average = (sum / n) as f32;
+                        color[channel] = t * average + (1.0 - t) * tone_map.background[channel];
+                    }
+                    color
+                }
+            };
+
+            let x = (i % self.width as usize) as i64;
+            let y = (i / self.width as usize) as i64;
+            image.set_pixel(x, y, to_rgba8([r, g, b, 1.0]));
+        }
+
+        image
+    }
+
+    /// Shows the tone mapped grid in a window, scaled to fit.
+    pub fn run(&self, tone_map: &ToneMap) {
+        let image = self.render(tone_map);
+
+        let event_loop = EventLoop::new();
+        let wb = WindowBuilder::new()
+            .with_inner_size(LogicalSize::new(self.width as f32, self.height as f32))
+            .with_title("IFS density");
+        let display = Display::new(wb, ContextBuilder::new(), &event_loop).unwrap();
+
+        // OpenGL puts the first row of a texture at the bottom
+        let raw = RawImage2d::from_raw_rgba_reversed(image.data(), (image.width(), image.height()));
+        let texture = Texture2d::new(&display, raw).unwrap();
+
+        event_loop.run(move |ev, _, control_flow| {
+            *control_flow = ControlFlow::Wait;
+
+            if let Event::WindowEvent { event, .. } = ev {
+                match event {
+                    WindowEvent::CloseRequested => {
+                        *control_flow = ControlFlow::Exit;
+                        return;
+                    }
+                    WindowEvent::Resized(_) => (),
+                    _ => return,
+                }
+            }
+
+            let target = display.draw();
+            texture
+                .as_surface()
+                .fill(&target, MagnifySamplerFilter::Linear);
+            target.finish().unwrap();
+        });
+    }
+}
+
+/// How visit counts are scaled before gamma correction.
+#[derive(Copy, Clone, Debug, PartialEq, clap::ValueEnum, strum_macros::Display)]
+#[strum(serialize_all = "snake_case")]
+pub enum Scaling {
+    Linear,
+    /// `ln(1 + count)`, which keeps sparse areas visible next to very dense ones
+    Log,
+}
+
+/// Turns visit counts into colors.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ToneMap {
+    pub scaling: Scaling,
+    pub gamma: f32,
+    /// Multiplies the scaled density before clamping to 1.0.
+    pub brightness: f32,
+    /// Colors cells by density. Without a palette the average color of the points in a cell is
+    /// used, faded into the background by density.
+    pub palette: Option<ColorScheme>,
+    pub background: [f32; 4],
+}
+
+impl Default for ToneMap {
+    fn default() -> Self {
+        Self {
+            scaling: Scaling::Log,
+            gamma: 2.2,
+            brightness: 1.0,
+            palette: Some(ColorScheme::Inferno),
+            background: [0.0, 0.0, 0.0, 1.0],
+        }
+    }
+}
+
+impl ToneMap {
+    /// Brightness of a cell between 0.0 and 1.0.
+    pub fn intensity(&self, count: u64, max: u64) -> f32 {
+        let t = match self.scaling {
+            Scaling::Linear => count as f64 / max as f64,
+            Scaling::Log => (count as f64).ln_1p() / (max as f64).ln_1p(),
+        } as f32;
+        (t * self.brightness).min(1.0).powf(1.0 / self.gamma)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ndarray::array;
+
+    use crate::ifs::density::{estimate_bounds, DensityGrid, Scaling, ToneMap};
+    use crate::ifs::system::Ifs;
+    use crate::ifs::Bounds;
+    use crate::utils::rng::seeded_rng;
+
+    fn unit() -> Bounds {
+        Bounds {
+            x_min: 0.0,
+            x_max: 1.0,
+            y_min: 0.0,
+            y_max: 1.0,
+        }
+    }
+
+    #[test]
+    fn add_points() {
+        let mut grid = DensityGrid::new(unit(), 2, 2);
+        assert!(grid.add(0.25, 0.25, [1.0; 3]));
+        assert!(grid.add(0.1, 0.1, [1.0; 3]));
+        assert!(grid.add(1.0, 1.0, [1.0; 3]));
+        assert!(!grid.add(1.5, 0.5, [1.0; 3]));
+        assert!(!grid.add(0.5, -0.5, [1.0; 3]));
+
+        assert_eq!(grid.total(), 3);
+        assert_eq!(grid.count(0, 1), 2);
+        assert_eq!(grid.count(1, 0), 1);
+        assert_eq!(grid.max_count(), 2);
+    }
+
+    #[test]
+    fn accumulate_sierpinski() {
+        let ifs = Ifs::new(array![
+            [0.5, 0.0, 0.0, 0.5, 0.0, 0.0, 1.0],
+            [0.5, 0.0, 0.0, 0.5, 0.5, 0.0, 1.0],
+            [0.5, 0.0, 0.0, 0.5, 0.0, 0.5, 1.0],
+        ])
+        .unwrap();
+
+        let mut rng = seeded_rng(Some(1));
+        let bounds = estimate_bounds(&ifs, &mut rng, 10000);
+        assert!(bounds.x_min < 0.05 && bounds.x_max > 0.95);
+
+        let mut grid = DensityGrid::new(unit(), 4, 4);
+        grid.accumulate(&mut rng, &ifs, [1.0; 3], 100_000, 1.0, 0.0, 0.0);
+        // The top right quarter is the hole in the middle of the triangle
+        for (x, y) in [(2, 0), (3, 0), (2, 1), (3, 1)] {
+            assert_eq!(grid.count(x, y), 0);
+        }
+        assert_eq!(grid.total(), 100_000 - 10);
+
+        let mut other = grid.clone();
+        other.merge(&grid);
+        assert_eq!(other.total(), 2 * grid.total());
+
+        // Every thread skips the first few points of its own chain
+        let mut threaded = DensityGrid::new(unit(), 4, 4);
+        threaded.set_threads(4);
+        threaded.accumulate(&mut rng, &ifs, [1.0; 3], 100_000, 1.0, 0.0, 0.0);
+        assert_eq!(threaded.total(), 100_000 - 4 * 10);
+        assert_eq!(threaded.count(3, 0), 0);
+    }
+
+    #[test]
+    fn tone_mapping() {
+        let tone_map = ToneMap {
+            scaling: Scaling::Linear,
+            gamma: 1.0,
+            ..ToneMap::default()
+        };
+        assert_eq!(tone_map.intensity(5, 10), 0.5);
+        assert_eq!(tone_map.intensity(10, 10), 1.0);
+
+        let log = ToneMap::default();
+        assert!(log.intensity(1, 1000) > 0.1);
+    }
+}
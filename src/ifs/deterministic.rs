@@ -0,0 +1,214 @@
+//! The deterministic algorithm for drawing IFS attractors.
+//!
+//! Instead of following a single random orbit like the chaos game in
+//! [`IfsProgram::sample_affine`](crate::ifs::IfsProgram::sample_affine), start from any shape
+//! and replace it by the union of its images under all maps, over and over. Every generation is
+//! closer to the attractor, whatever the starting shape was.
+//!
+//! The number of points grows as `n^k` for `n` maps after `k` generations, so
+//! [`point_generations`] is only practical for a handful of generations. A [`Raster`] keeps the
+//! shape at a fixed resolution instead, so it can be iterated until nothing changes any more.
+
+use ndarray::{Array, Ix2};
+
+use crate::ifs::{apply_map, Bounds};
+
+/// Union of the images of `points` under every map of `d`.
+pub fn apply_all(d: &Array<f32, Ix2>, points: &[[f32; 2]]) -> Vec<[f32; 2]> {
+    d.rows()
+        .into_iter()
+        .flat_map(|row| {
+            points.iter().map(move |&[x, y]| {
+                let (x, y) = apply_map(row, x, y);
+                [x, y]
+            })
+        })
+        .collect()
+}
+
+/// The seed followed by `generations` applications of [`apply_all`].
+pub fn point_generations(
+    d: &Array<f32, Ix2>,
+    seed: Vec<[f32; 2]>,
+    generations: usize,
+) -> Vec<Vec<[f32; 2]>> {
+    let mut ret = vec![seed];
+    for _ in 0..generations {
+        let next = apply_all(d, ret.last().unwrap());
+        ret.push(next);
+    }
+    ret
+}
+
+/// A set of points stored as a grid of cells covering `bounds`. Row 0 is at `y_min`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Raster {
+    bounds: Bounds,
+    width: usize,
+    height: usize,
+    cells: Vec<bool>,
+}
+
+impl Raster {
+    /// An empty raster.
+    pub fn new(bounds: Bounds, width: usize, height: usize) -> Self {
+        Self {
+            bounds,
+            width,
+            height,
+            cells: vec![false; width * height],
+        }
+    }
+
+    /// A raster with every cell set, i.e. the whole rectangle `bounds`.
+    pub fn filled(bounds: Bounds, width: usize, height: usize) -> Self {
+        Self {
+            cells: vec![true; width * height],
+            ..Self::new(bounds, width, height)
+        }
+    }
+
+    pub fn from_points(points: &[[f32; 2]], bounds: Bounds, width: usize, height: usize) -> Self {
+        let mut ret = Self::new(bounds, width, height);
+        for &[x, y] in points {
+            ret.insert(x, y);
+        }
+        ret
+    }
+
+    pub fn bounds(&self) -> Bounds {
+        self.bounds
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Index of the cell containing a point, if it lies inside the bounds.
+    fn cell(&self, x: f32, y: f32) -> Option<usize> {
+        let col = ((x - self.bounds.x_min) / self.bounds.width() * self.width as f32).floor();
+        let row = ((y - self.bounds.y_min) / self.bounds.height() * self.height as f32).floor();
+        (col >= 0.0 && row >= 0.0 && (col as usize) < self.width && (row as usize) < self.height)
+            .then(|| row as usize * self.width + col as usize)
+    }
+
+    /// Sets the cell containing a point. Points outside the bounds are ignored.
+    pub fn insert(&mut self, x: f32, y: f32) {
+        if let Some(i) = self.cell(x, y) {
+            self.cells[i] = true;
+        }
+    }
+
+    pub fn contains(&self, x: f32, y: f32) -> bool {
+        self.cell(x, y).is_some_and(|i| self.cells[i])
+    }
+
+    /// Number of cells set.
+    pub fn count(&self) -> usize {
+        self.cells.iter().filter(|&&set| set).count()
+    }
+
+    /// Centers of the cells that are set.
+    pub fn points(&self) -> Vec<[f32; 2]> {
+        let cell_width = self.bounds.width() / self.width as f32;
+        let cell_height = self.bounds.height() / self.height as f32;
+
+        self.cells
+            .iter()
+            .enumerate()
+            .filter(|(_, &set)| set)
+            .map(|(i, _)| {
+                [
+                    self.bounds.x_min + ((i % self.width) as f32 + 0.5) * cell_width,
+                    self.bounds.y_min + ((i / self.width) as f32 + 0.5) * cell_height,
+                ]
+            })
+            .collect()
+    }
+
+    /// Next generation: the union of the images of every cell center under the maps of `d`.
+    /// Images falling outside the bounds are lost, so the bounds should contain the attractor.
+    pub fn step(&self, d: &Array<f32, Ix2>) -> Self {
+        let mut ret = Self::new(self.bounds, self.width, self.height);
+        for row in d.rows() {
+            for [x, y] in self.points() {
+                let (x, y) = apply_map(row, x, y);
+                ret.insert(x, y);
+            }
+        }
+        ret
+    }
+}
+
+/// The seed followed by `generations` applications of [`Raster::step`]. Stops early once a
+/// generation is identical to the previous one, as the attractor has then been reached at this
+/// resolution.
+pub fn raster_generations(d: &Array<f32, Ix2>, seed: Raster, generations: usize) -> Vec<Raster> {
+    let mut ret = vec![seed];
+    for _ in 0..generations {
+        let previous = ret.last().unwrap();
+        let next = previous.step(d);
+        if &next == previous {
+            break;
+        }
+        ret.push(next);
+    }
+    ret
+}
+
+#[cfg(test)]
+mod test {
+    use ndarray::array;
+
+    use crate::ifs::deterministic::{point_generations, raster_generations, Raster};
+    use crate::ifs::{Bounds, IfsProgram};
+    use crate::utils::rng::seeded_rng;
+
+    fn sierpinski() -> ndarray::Array<f32, ndarray::Ix2> {
+        array![
+            [0.5, 0.0, 0.0, 0.5, 0.0, 0.0, 0.33],
+            [0.5, 0.0, 0.0, 0.5, 0.5, 0.0, 0.33],
+            [0.5, 0.0, 0.0, 0.5, 0.25, 0.5, 0.34],
+        ]
+    }
+
+    const UNIT: Bounds = Bounds {
+        x_min: 0.0,
+        x_max: 1.0,
+        y_min: 0.0,
+        y_max: 1.0,
+    };
+
+    #[test]
+    fn point_generations_grow() {
+        let generations = point_generations(&sierpinski(), vec![[0.5, 0.5]], 4);
+        let sizes: Vec<usize> = generations.iter().map(|g| g.len()).collect();
+        assert_eq!(sizes, vec![1, 3, 9, 27, 81]);
+    }
+
+    #[test]
+    fn raster_points_round_trip() {
+        let raster = Raster::from_points(&[[0.1, 0.1], [0.9, 0.6], [2.0, 0.5]], UNIT, 10, 10);
+        assert_eq!(raster.count(), 2);
+        assert!(raster.contains(0.15, 0.15));
+        assert_eq!(Raster::from_points(&raster.points(), UNIT, 10, 10), raster);
+    }
+
+    #[test]
+    fn raster_converges_to_chaos_game() {
+        let generations = raster_generations(&sierpinski(), Raster::filled(UNIT, 64, 64), 20);
+        let attractor = generations.last().unwrap();
+        assert!(generations.len() < 21, "did not converge");
+
+        // Every point of the chaos game lands in a cell of the deterministic attractor
+        let mut program = IfsProgram::with_rng(seeded_rng(Some(1)));
+        program.sample(&sierpinski(), [0.0, 0.0, 0.0, 1.0], 10000);
+        let points = program.points();
+        let chaos = Raster::from_points(&points, UNIT, 64, 64);
+        assert_eq!(&chaos, attractor);
+    }
+}
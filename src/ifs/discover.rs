@@ -0,0 +1,341 @@
+//! Searching for interesting attractors among random IFS.
+//!
+//! Most random sets of contractions produce something dull: a blurry blob, a dusting of a few
+//! points, or everything squashed onto a line. [`AttractorSearch`] samples every candidate with
+//! a short run of the chaos game and measures the points, see [`AttractorStats`]. Candidates that
+//! are clearly degenerate are rejected, the rest are ranked by [`AttractorStats::score`].
+
+use std::fmt::{Display, Formatter};
+
+use ndarray::{Array, Ix2};
+use rand::Rng;
+
+use crate::dimension::box_counting;
+use crate::ifs::density::sample_points;
+use crate::ifs::system::{Ifs, Probabilities};
+use crate::ifs::{effective_map, operator_norm, Bounds};
+use crate::utils::parallel::run_workers;
+
+/// Measurements of a sampled attractor.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct AttractorStats {
+    /// Fraction of the cells of a square grid around the points that contain at least one.
+    pub coverage: f32,
+    /// Square root of the ratio of the smaller to the larger principal variance of the points.
+    /// 0 for points on a line, 1 for points spread evenly in every direction.
+    pub thickness: f32,
+    /// Box counting dimension, see [`box_counting`].
+    pub dimension: f32,
+}
+
+impl AttractorStats {
+    /// Measures `points`, using a `resolution` x `resolution` grid for the coverage. Returns
+    /// `None` if the points don't span any area, e.g. because there are none.
+    pub fn new(points: &[[f32; 2]], resolution: u32) -> Option<Self> {
+        let bounds = Bounds::from_points_trimmed(points, 1.0)?;
+        let extent = bounds.width().max(bounds.height());
+        if !(extent > 0.0 && extent.is_finite()) {
+            return None;
+        }
+
+        let n = resolution as usize;
+        let mut cells = vec![false; n * n];
+        for [x, y] in points {
+            let cell = |v: f32, min: f32| (((v - min) / extent * n as f32) as usize).min(n - 1);
+            cells[cell(*y, bounds.y_min) * n + cell(*x, bounds.x_min)] = true;
+        }
+        let coverage = cells.iter().filter(|&&c| c).count() as f32 / cells.len() as f32;
+
+        // Eigenvalues of the covariance matrix
+        let count = points.len() as f64;
+        let mean = |axis: usize| points.iter().map(|p| p[axis] as f64).sum::<f64>() / count;
+        let (mx, my) = (mean(0), mean(1));
+        let (mut sxx, mut syy, mut sxy) = (0.0, 0.0, 0.0);
+        for [x, y] in points {
+            let (dx, dy) = (*x as f64 - mx, *y as f64 - my);
+            sxx += dx * dx;
+            syy += dy * dy;
+            sxy += dx * dy;
+        }
+        let root = ((sxx - syy).powi(2) + 4.0 * sxy * sxy).sqrt();
+        let (major, minor) = ((sxx + syy + root) / 2.0, (sxx + syy - root) / 2.0);
+        let thickness = (minor.max(0.0) / major).sqrt() as f32;
+
+        let dimension = box_counting(points, None).map_or(0.0, |e| e.dimension as f32);
+
+        Some(Self {
+            coverage,
+            thickness,
+            dimension,
+        })
+    }
+
+    /// How interesting the attractor is: fine detail (a high dimension) spread over much of its
+    /// bounds (a high coverage) scores highest.
+    pub fn score(&self) -> f32 {
+        self.dimension * self.coverage.sqrt()
+    }
+}
+
+/// Why a candidate was thrown out.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Rejection {
+    /// The points don't span any area, e.g. because every map has the same fixed point.
+    Point,
+    /// Squashed onto a line or a thin sliver.
+    Collapsed { thickness: f32 },
+    /// Too few cells of the grid are visited.
+    Sparse { coverage: f32 },
+    /// Little more than a dust of isolated points.
+    Dust { dimension: f32 },
+    /// Fills its bounds like a solid blob.
+    Blob { dimension: f32 },
+}
+
+impl Display for Rejection {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Rejection::Point => write!(f, "the attractor is a single point"),
+            Rejection::Collapsed { thickness } => {
+                write!(
+                    f,
+                    "the attractor is nearly a line (thickness {:.3})",
+                    thickness
+                )
+            }
+            Rejection::Sparse { coverage } => {
+                write!(
+                    f,
+                    "the attractor covers only {:.1}% of its bounds",
+                    coverage * 100.0
+                )
+            }
+            Rejection::Dust { dimension } => {
+                write!(f, "the attractor is a dust (dimension {:.3})", dimension)
+            }
+            Rejection::Blob { dimension } => {
+                write!(
+                    f,
+                    "the attractor is a solid blob (dimension {:.3})",
+                    dimension
+                )
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Candidate {
+    /// The maps in the 7-column IFS layout, with probabilities proportional to their areas.
+    pub maps: Array<f32, Ix2>,
+    pub stats: AttractorStats,
+}
+
+/// Settings of the search. The thresholds decide which candidates count as degenerate.
+#[derive(Clone, Debug)]
+pub struct AttractorSearch {
+    /// Every candidate gets a number of maps in this range, inclusive.
+    pub min_maps: usize,
+    pub max_maps: usize,
+    /// Number of random IFS to try.
+    pub candidates: usize,
+    /// Number of best candidates to keep.
+    pub keep: usize,
+    /// Chaos game points per candidate.
+    pub samples: usize,
+    /// Maps with a larger operator norm are redrawn, so every attractor exists.
+    pub max_contraction: f32,
+    /// Side of the grid the coverage is measured on.
+    pub resolution: u32,
+    pub min_thickness: f32,
+    pub min_coverage: f32,
+    pub min_dimension: f32,
+    pub max_dimension: f32,
+    /// Candidates are split between this many threads. Seeded searches are reproducible for a
+    /// fixed number of threads.
+    pub threads: usize,
+}
+
+impl Default for AttractorSearch {
+    fn default() -> Self {
+        Self {
+            min_maps: 2,
+            max_maps: 4,
+            candidates: 1000,
+            keep: 8,
+            samples: 20_000,
+            max_contraction: 0.9,
+            resolution: 64,
+            min_thickness: 0.15,
+            min_coverage: 0.05,
+            min_dimension: 1.1,
+            max_dimension: 1.9,
+            threads: 1,
+        }
+    }
+}
+
+impl AttractorSearch {
+    /// A random IFS whose maps are all contractions, with probabilities proportional to their
+    /// areas.
+    pub fn random_ifs<R: Rng + ?Sized>(&self, rng: &mut R) -> Ifs {
+        let maps = rng.gen_range(self.min_maps.max(1)..=self.max_maps.max(self.min_maps));
+        let mut rows = Array::zeros((maps, 6));
+        for mut row in rows.rows_mut() {
+            loop {
+                for coef in row.iter_mut() {
+                    *coef = rng.gen_range(-1.0..1.0);
+                }
+                if operator_norm(effective_map(row.view())) <= self.max_contraction {
+                    break;
+                }
+            }
+        }
+
+        let probabilities = Probabilities::Determinant {
+            floor: Probabilities::DEFAULT_FLOOR,
+        };
+        // Contractions with a floor on their probabilities are always valid
+        Ifs::with_probabilities(rows, probabilities).unwrap()
+    }
+
+    /// Samples the attractor of `ifs` and measures it, or tells why it is degenerate.
+    pub fn evaluate<R: Rng + ?Sized>(
+        &self,
+        ifs: &Ifs,
+        rng: &mut R,
+    ) -> Result<AttractorStats, Rejection> {
+        let points = sample_points(ifs, rng, self.samples);
+        let stats = AttractorStats::new(&points, self.resolution).ok_or(Rejection::Point)?;
+
+        if stats.thickness < self.min_thickness {
+            Err(Rejection::Collapsed {
+                thickness: stats.thickness,
+            })
+        } else if stats.coverage < self.min_coverage {
+            Err(Rejection::Sparse {
+                coverage: stats.coverage,
+            })
+        } else if stats.dimension < self.min_dimension {
+            Err(Rejection::Dust {
+                dimension: stats.dimension,
+            })
+        } else if stats.dimension > self.max_dimension {
+            Err(Rejection::Blob {
+                dimension: stats.dimension,
+            })
+        } else {
+            Ok(stats)
+        }
+    }
+
+    /// Tries random IFS and returns the best candidates that are not degenerate, best first.
+    pub fn search<R: Rng + ?Sized>(&self, rng: &mut R) -> Vec<Candidate> {
+        let chunks = run_workers(rng, self.threads, self.candidates as u64, |rng, tries| {
+            (0..tries)
+                .filter_map(|_| {
+                    let ifs = self.random_ifs(rng);
+                    let stats = self.evaluate(&ifs, rng).ok()?;
+                    Some(Candidate {
+                        maps: ifs.maps().clone(),
+                        stats,
+                    })
+                })
+                .collect::<Vec<_>>()
+        });
+
+        let mut candidates: Vec<Candidate> = chunks.into_iter().flatten().collect();
+        candidates.sort_by(|a, b| b.stats.score().total_cmp(&a.stats.score()));
+        candidates.truncate(self.keep);
+        candidates
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ndarray::array;
+
+    use crate::ifs::discover::{AttractorSearch, Rejection};
+    use crate::ifs::system::Ifs;
+    use crate::ifs::{effective_map, operator_norm};
+    use crate::utils::rng::seeded_rng;
+
+    #[test]
+    fn random_maps_contract() {
+        let search = AttractorSearch::default();
+        let mut rng = seeded_rng(Some(1));
+        for _ in 0..20 {
+            let ifs = search.random_ifs(&mut rng);
+            assert!((2..=4).contains(&ifs.map_count()));
+            for row in ifs.maps().rows() {
+                assert!(operator_norm(effective_map(row)) <= 0.9);
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_degenerate() {
+        let search = AttractorSearch::default();
+        let mut rng = seeded_rng(Some(1));
+        let evaluate = |maps| search.evaluate(&Ifs::new(maps).unwrap(), &mut seeded_rng(Some(1)));
+
+        // Every map shrinks toward the origin
+        let point = array![
+            [0.5, 0.0, 0.0, 0.5, 0.0, 0.0, 0.5],
+            [0.3, 0.0, 0.0, 0.3, 0.0, 0.0, 0.5],
+        ];
+        assert_eq!(evaluate(point), Err(Rejection::Point));
+
+        // Cantor set on the x axis
+        let line = array![
+            [0.33, 0.0, 0.0, 0.0, 0.0, 0.0, 0.5],
+            [0.33, 0.0, 0.0, 0.0, 0.66, 0.0, 0.5],
+        ];
+        assert!(matches!(evaluate(line), Err(Rejection::Collapsed { .. })));
+
+        // Fills the unit square
+        let blob = array![
+            [0.5, 0.0, 0.0, 0.5, 0.0, 0.0, 0.25],
+            [0.5, 0.0, 0.0, 0.5, 0.5, 0.0, 0.25],
+            [0.5, 0.0, 0.0, 0.5, 0.0, 0.5, 0.25],
+            [0.5, 0.0, 0.0, 0.5, 0.5, 0.5, 0.25],
+        ];
+        assert!(matches!(evaluate(blob), Err(Rejection::Blob { .. })));
+
+        let sierpinski = array![
+            [0.5, 0.0, 0.0, 0.5, 0.0, 0.0, 0.33],
+            [0.5, 0.0, 0.0, 0.5, 0.5, 0.0, 0.33],
+            [0.5, 0.0, 0.0, 0.5, 0.0, 0.5, 0.34],
+        ];
+        let stats = search
+            .evaluate(&Ifs::new(sierpinski).unwrap(), &mut rng)
+            .unwrap();
+        assert!((stats.dimension - 1.585).abs() < 0.1, "{:?}", stats);
+        // Each halving of the cell size keeps 3 of every 4 cells
+        assert!(
+            (stats.coverage - 0.75_f32.powi(6)).abs() < 0.02,
+            "{:?}",
+            stats
+        );
+    }
+
+    #[test]
+    fn search_ranks_candidates() {
+        let search = AttractorSearch {
+            candidates: 100,
+            keep: 5,
+            samples: 5000,
+            threads: 2,
+            ..AttractorSearch::default()
+        };
+        let found = search.search(&mut seeded_rng(Some(1)));
+        assert!(!found.is_empty());
+        assert!(found.len() <= 5);
+        for pair in found.windows(2) {
+            assert!(pair[0].stats.score() >= pair[1].stats.score());
+        }
+
+        // Reproducible with the same seed and number of threads
+        assert_eq!(found, search.search(&mut seeded_rng(Some(1))));
+    }
+}
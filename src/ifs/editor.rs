@@ -0,0 +1,418 @@
+//! Designing an IFS by dragging its maps around.
+//!
+//! Every map is shown as the image of the unit square, a parallelogram with corners `(e, f)`,
+//! `(a + e, c + f)`, `(a + b + e, c + d + f)` and `(b + e, d + f)` in terms of the conventional
+//! coefficients from [`effective_map`]. Dragging a corner changes the map, see [`Handle`]. The
+//! `ifs_editor` bin turns mouse events into calls to [`Editor`]; everything here is in the
+//! coordinates of the attractor, not of the window.
+
+use std::fmt::Write as _;
+
+use ndarray::{Array, ArrayView1, Ix2};
+
+use crate::ifs::format::{IfsDefinition, IfsLayer, IfsStep};
+use crate::ifs::system::{Ifs, IfsError, Probabilities};
+use crate::ifs::{effective_map, nearest_sequential_map};
+
+/// Part of a map's parallelogram that can be dragged.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Handle {
+    /// Anywhere inside the parallelogram. Moves the whole map.
+    Body,
+    /// The image of `(0, 0)`. Moves the whole map, like [`Handle::Body`].
+    Origin,
+    /// The image of `(1, 0)`. Changes `a` and `c`, the image of the x axis, while the origin
+    /// stays put.
+    XAxis,
+    /// The image of `(0, 1)`. Changes `b` and `d`, the image of the y axis.
+    YAxis,
+    /// The image of `(1, 1)`. Rotates and scales the map about its center.
+    Corner,
+}
+
+impl Handle {
+    /// The handles drawn as points, in the order they are picked.
+    pub const POINTS: [Handle; 4] = [Handle::Corner, Handle::XAxis, Handle::YAxis, Handle::Origin];
+}
+
+/// One map, in the conventional form `x' = ax + by + e`, `y' = cx + dy + f`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct EditorMap {
+    /// `a, b, c, d, e, f`
+    pub coefs: [f32; 6],
+    pub probability: f32,
+}
+
+impl EditorMap {
+    /// Reads a row in the 7-column layout of [`apply_map`](crate::ifs::apply_map).
+    pub fn from_row(row: ArrayView1<f32>) -> Self {
+        Self {
+            coefs: effective_map(row),
+            probability: row[6],
+        }
+    }
+
+    /// The row in the 7-column layout of [`apply_map`](crate::ifs::apply_map). Maps with
+    /// `a` = 0 but `c` ≠ 0 cannot be written that way, see [`nearest_sequential_map`].
+    pub fn to_row(&self) -> [f32; 7] {
+        let [a, b, c, d, e, f] = nearest_sequential_map(self.coefs);
+        [a, b, c, d, e, f, self.probability]
+    }
+
+    /// Images of `(0, 0)`, `(1, 0)`, `(1, 1)` and `(0, 1)`, in this order.
+    pub fn corners(&self) -> [[f32; 2]; 4] {
+        let [a, b, c, d, e, f] = self.coefs;
+        [
+            [e, f],
+            [a + e, c + f],
+            [a + b + e, c + d + f],
+            [b + e, d + f],
+        ]
+    }
+
+    /// The image of `(0.5, 0.5)`.
+    pub fn center(&self) -> [f32; 2] {
+        let [a, b, c, d, e, f] = self.coefs;
+        [(a + b) / 2.0 + e, (c + d) / 2.0 + f]
+    }
+
+    /// Where `handle` is drawn. [`Handle::Body`] is at the center.
+    pub fn handle_position(&self, handle: Handle) -> [f32; 2] {
+        let corners = self.corners();
+        match handle {
+            Handle::Body => self.center(),
+            Handle::Origin => corners[0],
+            Handle::XAxis => corners[1],
+            Handle::Corner => corners[2],
+            Handle::YAxis => corners[3],
+        }
+    }
+
+    /// Whether `point` lies within the parallelogram. Always false for maps that squash the
+    /// square onto a line.
+    pub fn contains(&self, [x, y]: [f32; 2]) -> bool {
+        let [a, b, c, d, e, f] = self.coefs;
+        let det = a * d - b * c;
+        if det.abs() < 1e-9 {
+            return false;
+        }
+
+        let (x, y) = (x - e, y - f);
+        let u = (d * x - b * y) / det;
+        let v = (a * y - c * x) / det;
+        (0.0..=1.0).contains(&u) && (0.0..=1.0).contains(&v)
+    }
+
+    pub fn translate(&mut self, dx: f32, dy: f32) {
+        self.coefs[4] += dx;
+        self.coefs[5] += dy;
+    }
+
+    /// Replaces the linear part `[[a, b], [c, d]]` by `m(a, b, c, d)`, keeping the center in
+    /// place.
+    fn transform_linear<F>(&mut self, m: F)
+    where
+        F: FnOnce([f32; 4]) -> [f32; 4],
+    {
+        let [cx, cy] = self.center();
+        let [a, b, c, d, _, _] = self.coefs;
+        let [a, b, c, d] = m([a, b, c, d]);
+        self.coefs = [a, b, c, d, cx - (a + b) / 2.0, cy - (c + d) / 2.0];
+    }
+
+    /// Rotates the parallelogram by `angle` radians counterclockwise and scales it by `factor`
+    /// about its center.
+    pub fn rotate_scale(&mut self, angle: f32, factor: f32) {
+        let (sin, cos) = angle.sin_cos();
+        let (sin, cos) = (sin * factor, cos * factor);
+        self.transform_linear(|[a, b, c, d]| {
+            [
+                cos * a - sin * c,
+                cos * b - sin * d,
+                sin * a + cos * c,
+                sin * b + cos * d,
+            ]
+        });
+    }
+
+    pub fn rotate(&mut self, angle: f32) {
+        self.rotate_scale(angle, 1.0);
+    }
+
+    /// Stretches the parallelogram by `sx` along its first side and by `sy` along its second,
+    /// keeping the center in place.
+    pub fn scale(&mut self, sx: f32, sy: f32) {
+        self.transform_linear(|[a, b, c, d]| [a * sx, b * sy, c * sx, d * sy]);
+    }
+
+    /// Slides the second side of the parallelogram along the first one by `amount` times its
+    /// length, keeping the center in place.
+    pub fn shear(&mut self, amount: f32) {
+        self.transform_linear(|[a, b, c, d]| [a, b + amount * a, c, d + amount * c]);
+    }
+
+    /// Changes the map as if `handle` was dragged from `from` to `to`.
+    pub fn drag(&mut self, handle: Handle, [x0, y0]: [f32; 2], [x1, y1]: [f32; 2]) {
+        let (dx, dy) = (x1 - x0, y1 - y0);
+        match handle {
+            Handle::Body | Handle::Origin => self.translate(dx, dy),
+            Handle::XAxis => {
+                self.coefs[0] += dx;
+                self.coefs[2] += dy;
+            }
+            Handle::YAxis => {
+                self.coefs[1] += dx;
+                self.coefs[3] += dy;
+            }
+            Handle::Corner => {
+                let [cx, cy] = self.center();
+                let (ux, uy) = (x0 - cx, y0 - cy);
+                let (vx, vy) = (x1 - cx, y1 - cy);
+                let (u, v) = (ux.hypot(uy), vx.hypot(vy));
+                // Too close to the center to tell the direction
+                if u < 1e-6 || v < 1e-6 {
+                    return;
+                }
+                self.rotate_scale(vy.atan2(vx) - uy.atan2(ux), v / u);
+            }
+        }
+    }
+}
+
+/// The maps being edited, and the drag in progress.
+#[derive(Clone, Debug)]
+pub struct Editor {
+    pub maps: Vec<EditorMap>,
+    pub selected: Option<usize>,
+    /// How the probabilities are picked. Anything but [`Probabilities::Given`] overrides the
+    /// probabilities of the maps.
+    pub probabilities: Probabilities,
+    /// Map and handle being dragged, and where the pointer was last.
+    drag: Option<(usize, Handle, [f32; 2])>,
+}
+
+impl Editor {
+    /// Starts editing the maps of `d`, in the 7-column layout.
+    pub fn new(d: &Array<f32, Ix2>) -> Self {
+        Self {
+            maps: d.rows().into_iter().map(EditorMap::from_row).collect(),
+            selected: None,
+            probabilities: Probabilities::Given,
+            drag: None,
+        }
+    }
+
+    /// The handle under `point`, preferring the maps drawn last. Point handles within `radius`
+    /// of `point` win over the body of a map.
+    pub fn pick(&self, point: [f32; 2], radius: f32) -> Option<(usize, Handle)> {
+        let near = |[x, y]: [f32; 2]| (x - point[0]).hypot(y - point[1]) <= radius;
+
+        let handles = self.maps.iter().enumerate().rev().find_map(|(i, map)| {
+            Handle::POINTS
+                .into_iter()
+                .find(|&h| near(map.handle_position(h)))
+                .map(|h| (i, h))
+        });
+        handles.or_else(|| {
+            self.maps
+                .iter()
+                .rposition(|map| map.contains(point))
+                .map(|i| (i, Handle::Body))
+        })
+    }
+
+    /// Selects the map under `point` and starts dragging it. Returns false, and clears the
+    /// selection, if there is nothing there.
+    pub fn begin_drag(&mut self, point: [f32; 2], radius: f32) -> bool {
+        let picked = self.pick(point, radius);
+        self.selected = picked.map(|(i, _)| i);
+        self.drag = picked.map(|(i, handle)| (i, handle, point));
+        picked.is_some()
+    }
+
+    /// Moves the handle being dragged to `point`. Returns true if a map changed.
+    pub fn drag_to(&mut self, point: [f32; 2]) -> bool {
+        match &mut self.drag {
+            Some((i, handle, last)) if *last != point => {
+                self.maps[*i].drag(*handle, *last, point);
+                *last = point;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub fn end_drag(&mut self) {
+        self.drag = None;
+    }
+
+    pub fn is_dragging(&self) -> bool {
+        self.drag.is_some()
+    }
+
+    /// Adds a copy of the selected map, or of the last one, shrunk about its center, and selects
+    /// it.
+    pub fn add_map(&mut self) {
+        let mut map = match self.selected.or(self.maps.len().checked_sub(1)) {
+            Some(i) => self.maps[i],
+            None => EditorMap {
+                coefs: [0.5, 0.0, 0.0, 0.5, 0.0, 0.0],
+                probability: 1.0,
+            },
+        };
+        map.scale(0.75, 0.75);
+        self.maps.push(map);
+        self.selected = Some(self.maps.len() - 1);
+    }
+
+    /// Removes the selected map, unless it is the only one.
+    pub fn remove_selected(&mut self) {
+        if let Some(i) = self.selected.filter(|_| self.maps.len() > 1) {
+            self.maps.remove(i);
+            self.selected = None;
+            self.drag = None;
+        }
+    }
+
+    /// The maps in the 7-column layout of [`apply_map`](crate::ifs::apply_map), with the
+    /// probabilities picked according to [`Editor::probabilities`].
+    pub fn matrix(&self) -> Array<f32, Ix2> {
+        let mut d = Array::zeros((self.maps.len(), 7));
+        for (mut row, map) in d.rows_mut().into_iter().zip(&self.maps) {
+            row.assign(&ArrayView1::from(&map.to_row()));
+        }
+
+        if let Some(computed) = self.probabilities.compute(&d) {
+            d.column_mut(6).assign(&ArrayView1::from(&computed));
+        }
+        d
+    }
+
+    pub fn ifs(&self) -> Result<Ifs, IfsError> {
+        Ifs::new(self.matrix())
+    }
+
+    /// The matrix as a Rust expression, ready to paste into a bin as `let d = ...;`. Values are
+    /// rounded to 4 decimals.
+    pub fn to_rust(&self) -> String {
+        let round = |v: f32| (v * 1e4).round() / 1e4 + 0.0;
+
+        let mut ret = String::from("array![\n");
+        for row in self.matrix().rows() {
+            let values: Vec<String> = row.iter().map(|&v| format!("{:?}", round(v))).collect();
+            writeln!(ret, "    [{}],", values.join(", ")).unwrap();
+        }
+        ret.push(']');
+        ret
+    }
+
+    /// A definition with a single layer holding the maps, see [`crate::ifs::format`].
+    pub fn to_definition(&self, color: [f32; 4], iterations: usize) -> IfsDefinition {
+        IfsDefinition {
+            point_size: None,
+            clear_color: None,
+            steps: vec![IfsStep::Sample(IfsLayer {
+                maps: self.matrix(),
+                color,
+                iterations,
+                ..IfsLayer::default()
+            })],
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ndarray::array;
+
+    use crate::ifs::editor::{Editor, EditorMap, Handle};
+
+    fn assert_close(a: [f32; 2], b: [f32; 2]) {
+        assert!(
+            (a[0] - b[0]).abs() < 1e-5 && (a[1] - b[1]).abs() < 1e-5,
+            "{:?} != {:?}",
+            a,
+            b
+        );
+    }
+
+    fn square() -> EditorMap {
+        EditorMap {
+            coefs: [0.5, 0.0, 0.0, 0.5, 0.0, 0.0],
+            probability: 1.0,
+        }
+    }
+
+    #[test]
+    fn handles() {
+        let mut map = square();
+
+        map.drag(Handle::XAxis, [0.5, 0.0], [0.5, 0.5]);
+        assert_eq!(map.coefs, [0.5, 0.0, 0.5, 0.5, 0.0, 0.0]);
+        assert_close(map.corners()[1], [0.5, 0.5]);
+
+        let mut map = square();
+        let center = map.center();
+        // A quarter turn, doubling the size
+        map.drag(Handle::Corner, [0.5, 0.5], [-0.25, 0.75]);
+        assert_close(map.center(), center);
+        assert_close(map.corners()[2], [-0.25, 0.75]);
+        assert_close(map.corners()[1], [0.75, 0.75]);
+
+        let mut map = square();
+        map.shear(1.0);
+        assert_close(map.center(), center);
+        assert_close(map.corners()[3], [0.25, 0.5]);
+    }
+
+    #[test]
+    fn round_trip() {
+        let d = array![
+            [0.0, 0.0, 0.0, 0.16, 0.0, 0.0, 0.01],
+            [0.85, 0.04, -0.04, 0.85, 0.0, 1.6, 0.85],
+            [0.2, -0.26, 0.23, 0.22, 0.0, 1.6, 0.07],
+            [-0.15, 0.28, 0.26, 0.24, 0.0, 0.44, 0.07],
+        ];
+        let editor = Editor::new(&d);
+        for (a, b) in editor.matrix().iter().zip(d.iter()) {
+            assert!((a - b).abs() < 1e-6);
+        }
+        assert!(editor
+            .to_rust()
+            .contains("    [0.85, 0.04, -0.04, 0.85, 0.0, 1.6, 0.85],\n"));
+
+        // Rotating the stem a quarter turn makes a = 0 but c != 0
+        let mut editor = editor;
+        editor.maps[0].coefs = [0.0, -0.16, 0.16, 0.0, 0.0, 0.0];
+        let row = editor.matrix().row(0).to_owned();
+        let [a, b, c, d, e, f] = crate::ifs::effective_map(row.view());
+        assert!(a > 0.0 && a < 0.01);
+        assert!((b + 0.16).abs() < 1e-6 && (c - 0.16).abs() < 1e-6);
+        assert!(d.abs() < 1e-6 && e == 0.0 && f == 0.0);
+    }
+
+    #[test]
+    fn pick_and_drag() {
+        let mut editor = Editor::new(&array![
+            [0.5, 0.0, 0.0, 0.5, 0.0, 0.0, 0.5],
+            [0.5, 0.0, 0.0, 0.5, 0.25, 0.0, 0.5],
+        ]);
+
+        // The second map is drawn on top
+        assert_eq!(editor.pick([0.4, 0.1], 0.01), Some((1, Handle::Body)));
+        assert_eq!(editor.pick([0.1, 0.1], 0.01), Some((0, Handle::Body)));
+        assert_eq!(editor.pick([0.5, 0.5], 0.01), Some((0, Handle::Corner)));
+        assert_eq!(editor.pick([0.9, 0.9], 0.01), None);
+
+        assert!(editor.begin_drag([0.1, 0.1], 0.01));
+        assert!(editor.drag_to([0.2, 0.3]));
+        assert!(!editor.drag_to([0.2, 0.3]));
+        editor.end_drag();
+        assert!(!editor.drag_to([0.5, 0.5]));
+        assert_eq!(editor.selected, Some(0));
+        assert_close(editor.maps[0].corners()[0], [0.1, 0.2]);
+
+        assert!(!editor.begin_drag([0.9, 0.9], 0.01));
+        assert_eq!(editor.selected, None);
+    }
+}
@@ -0,0 +1,365 @@
+//! Fractal flames, as described in "The Fractal Flame Algorithm" by Scott Draves and Erik
+//! Reckase.
+//!
+//! A flame is an IFS whose maps are an affine transform followed by a weighted blend of
+//! nonlinear variations. Instead of plotting points directly, every visit to a pixel is counted
+//! and the resulting histogram is tone mapped with a logarithmic density curve, which brings out
+//! detail that a flat point plot hides.
+
+use std::f32::consts::PI;
+
+use ndarray::{Array, ArrayView1, Ix2};
+use rand::distributions::{Distribution, WeightedIndex};
+use rand::Rng;
+
+use crate::args::ColorScheme;
+use crate::ifs::system::{Ifs, IfsError};
+use crate::ifs::{effective_map, map_distribution, Bounds};
+use crate::image::{to_rgba8, Image};
+
+/// Nonlinear functions applied after the affine part of a flame transform.
+#[derive(Copy, Clone, Debug, PartialEq, strum_macros::EnumString, strum_macros::Display)]
+#[strum(serialize_all = "snake_case")]
+pub enum Variation {
+    Linear,
+    Sinusoidal,
+    Spherical,
+    Swirl,
+    Horseshoe,
+    Polar,
+    Handkerchief,
+    Heart,
+    Disc,
+    Spiral,
+    Hyperbolic,
+    Diamond,
+    Julia,
+    Fisheye,
+    Exponential,
+    Cosine,
+}
+
+impl Variation {
+    pub fn apply<R: Rng + ?Sized>(self, x: f32, y: f32, rng: &mut R) -> (f32, f32) {
+        let r2 = x * x + y * y + 1e-10;
+        let r = r2.sqrt();
+        // The paper measures theta from the y axis
+        let theta = x.atan2(y);
+
+        match self {
+            Variation::Linear => (x, y),
+            Variation::Sinusoidal => (x.sin(), y.sin()),
+            Variation::Spherical => (x / r2, y / r2),
+            Variation::Swirl => {
+                let (sin, cos) = r2.sin_cos();
+                (x * sin - y * cos, x * cos + y * sin)
+            }
+            Variation::Horseshoe => ((x - y) * (x + y) / r, 2.0 * x * y / r),
+            Variation::Polar => (theta / PI, r - 1.0),
+            Variation::Handkerchief => (r * (theta + r).sin(), r * (theta - r).cos()),
+            Variation::Heart => (r * (theta * r).sin(), -r * (theta * r).cos()),
+            Variation::Disc => {
+                let (sin, cos) = (PI * r).sin_cos();
+                (theta / PI * sin, theta / PI * cos)
+            }
+            Variation::Spiral => ((theta.cos() + r.sin()) / r, (theta.sin() - r.cos()) / r),
+            Variation::Hyperbolic => (theta.sin() / r, r * theta.cos()),
+            Variation::Diamond => (theta.sin() * r.cos(), theta.cos() * r.sin()),
+            Variation::Julia => {
+                let omega = if rng.gen_bool(0.5) { 0.0 } else { PI };
+                let (sin, cos) = (theta / 2.0 + omega).sin_cos();
+                (r.sqrt() * cos, r.sqrt() * sin)
+            }
+            Variation::Fisheye => (2.0 / (r + 1.0) * y, 2.0 / (r + 1.0) * x),
+            Variation::Exponential => {
+                let (sin, cos) = (PI * y).sin_cos();
+                ((x - 1.0).exp() * cos, (x - 1.0).exp() * sin)
+            }
+            Variation::Cosine => ((PI * x).cos() * y.cosh(), -(PI * x).sin() * y.sinh()),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct FlameTransform {
+    /// Affine coefficients `a, b, c, d, e, f`: `x' = ax + by + e`, `y' = cx + dy + f`.
+    pub coefs: [f32; 6],
+    /// Position in the palette, between 0.0 and 1.0.
+    pub color: f32,
+    pub variations: Vec<(Variation, f32)>,
+}
+
+impl FlameTransform {
+    /// Creates a purely linear transform from a row in the 7-column IFS layout, see
+    /// [`effective_map`]. The probability column is ignored.
+    pub fn from_row(row: ArrayView1<f32>) -> Self {
+        Self {
+            coefs: effective_map(row),
+            color: 0.0,
+            variations: vec![(Variation::Linear, 1.0)],
+        }
+    }
+
+    pub fn apply<R: Rng + ?Sized>(&self, x: f32, y: f32, rng: &mut R) -> (f32, f32) {
+        let [a, b, c, d, e, f] = self.coefs;
+        let tx = a * x + b * y + e;
+        let ty = c * x + d * y + f;
+
+        self.variations
+            .iter()
+            .fold((0.0, 0.0), |(x, y), (variation, weight)| {
+                let (vx, vy) = variation.apply(tx, ty, rng);
+                (x + weight * vx, y + weight * vy)
+            })
+    }
+}
+
+pub struct Flame {
+    pub transforms: Vec<FlameTransform>,
+    pub weights: WeightedIndex<f32>,
+    /// Applied to every point before it is plotted, without feeding back into the iteration.
+    pub final_transform: Option<FlameTransform>,
+    pub palette: ColorScheme,
+    pub gamma: f32,
+    /// Between 0.0 and 1.0. Higher values keep colors saturated in sparse areas.
+    pub vibrancy: f32,
+    pub brightness: f32,
+    pub background: [f32; 4],
+}
+
+impl Flame {
+    /// Creates a flame from a matrix in the 7-column IFS layout, using the last column as the
+    /// weight of each transform, after checking it like [`Ifs::new`].
+    pub fn from_matrix(d: &Array<f32, Ix2>) -> Result<Self, IfsError> {
+        Ok(Self::from_ifs(&Ifs::new(d.clone())?))
+    }
+
+    /// Creates a flame with one transform per map of `ifs`, weighted by the map's probability.
+    /// Every transform starts out linear, with color indices spread evenly over the palette.
+    pub fn from_ifs(ifs: &Ifs) -> Self {
+        let d = ifs.maps();
+        let count = d.nrows();
+        let transforms = d
+            .rows()
+            .into_iter()
+            .enumerate()
+            .map(|(i, row)| FlameTransform {
+                color: if count > 1 {
+                    i as f32 / (count - 1) as f32
+                } else {
+                    0.0
+                },
+                ..FlameTransform::from_row(row)
+            })
+            .collect();
+
+        Self {
+            transforms,
+            weights: map_distribution(d),
+            final_transform: None,
+            palette: ColorScheme::Inferno,
+            gamma: 4.0,
+            vibrancy: 1.0,
+            brightness: 4.0,
+            background: [0.0, 0.0, 0.0, 1.0],
+        }
+    }
+
+    /// Runs the chaos game, calling `plot` with the position and color index of every point
+    /// after the initial iterations.
+    fn iterate<R, F>(&self, rng: &mut R, iters: usize, mut plot: F)
+    where
+        R: Rng + ?Sized,
+        F: FnMut(f32, f32, f32),
+    {
+        let mut x: f32 = rng.gen_range(-1.0..1.0);
+        let mut y: f32 = rng.gen_range(-1.0..1.0);
+        let mut color: f32 = rng.gen();
+
+        for i in 0..iters {
+            let transform = &self.transforms[self.weights.sample(rng)];
+            (x, y) = transform.apply(x, y, rng);
+            color = (color + transform.color) / 2.0;
+
+            if !x.is_finite() || !y.is_finite() {
+                // Some variations blow up near their singularities, start over
+                x = rng.gen_range(-1.0..1.0);
+                y = rng.gen_range(-1.0..1.0);
+                continue;
+            }
+
+            // Skip first few iterations
+            if i < 20 {
+                continue;
+            }
+
+            match &self.final_transform {
+                Some(last) => {
+                    let (fx, fy) = last.apply(x, y, rng);
+                    plot(fx, fy, (color + last.color) / 2.0);
+                }
+                None => plot(x, y, color),
+            }
+        }
+    }
+
+    /// Estimates the region containing the bulk of the flame from a short run.
+    pub fn estimate_bounds<R: Rng + ?Sized>(&self, rng: &mut R, samples: usize) -> Bounds {
+        let mut points = Vec::with_capacity(samples);
+        self.iterate(rng, samples, |x, y, _| points.push([x, y]));
+
+        Bounds::from_points_trimmed(&points, 0.99)
+            .map(|b| b.padded(0.05))
+            .unwrap_or(Bounds {
+                x_min: -1.0,
+                x_max: 1.0,
+                y_min: -1.0,
+                y_max: 1.0,
+            })
+    }
+
+    pub fn render<R: Rng + ?Sized>(
+        &self,
+        rng: &mut R,
+        width: u32,
+        height: u32,
+        iters: usize,
+        bounds: Bounds,
+    ) -> Image {
+        // Per pixel: visit count followed by the summed RGB color
+        let mut histogram = vec![[0.0_f32; 4]; (width * height) as usize];
+
+        self.iterate(rng, iters, |x, y, color| {
+            let (px, py) = bounds.to_pixel(x, y, width, height);
+            if px < 0.0 || py < 0.0 || px >= width as f32 || py >= height as f32 {
+                return;
+            }
+
+            let bucket = &mut histogram[py as usize * width as usize + px as usize];
+            let [r, g, b] = self.palette.color(color);
+            bucket[0] += 1.0;
+            bucket[1] += r;
+            bucket[2] += g;
+            bucket[3] += b;
+        });
+
+        let max_count = histogram.iter().map(|b| b[0]).fold(0.0, f32::max);
+        let mut image = Image::new(width, height, self.background);
+        if max_count == 0.0 {
+            return image;
+        }
+
+        let log_max = (1.0 + max_count).ln();
+        for (i, [count, r, g, b]) in histogram.into_iter().enumerate() {
+            if count == 0.0 {
+                continue;
+            }
+
+            let alpha = ((1.0 + count).ln() / log_max * self.brightness).min(1.0);
+            let gamma_alpha = alpha.powf(1.0 / self.gamma);
+
+            let mut pixel = [0.0; 4];
+            for (channel, sum) in [r, g, b].into_iter().enumerate() {
+                let average = sum / count;
+                // Vibrancy blends between gamma correcting the density alone, which keeps the
+                // palette color intact, and gamma correcting each channel separately
+                let value = self.vibrancy * average * gamma_alpha
+                    + (1.0 - self.vibrancy) * (average * alpha).powf(1.0 / self.gamma);
+                pixel[channel] = value + (1.0 - gamma_alpha) * self.background[channel];
+            }
+            pixel[3] = 1.0;
+
+            let x = (i % width as usize) as i64;
+            let y = (i / width as usize) as i64;
+            image.set_pixel(x, y, to_rgba8(pixel));
+        }
+
+        image
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ndarray::array;
+
+    use crate::ifs::flame::{Flame, Variation};
+    use crate::ifs::format::IfsDefinition;
+    use crate::ifs::system::IfsError;
+    use crate::ifs::{Bounds, IfsProgram};
+    use crate::utils::rng::seeded_rng;
+
+    #[test]
+    fn variation_names() {
+        assert_eq!("swirl".parse::<Variation>().unwrap(), Variation::Swirl);
+        assert_eq!(Variation::Horseshoe.to_string(), "horseshoe");
+    }
+
+    #[test]
+    fn invalid_matrix() {
+        let d = array![[0.5, 0.0, 0.0, 0.5, 0.0, 0.0, 0.0]];
+        assert_eq!(
+            Flame::from_matrix(&d).err(),
+            Some(IfsError::ZeroProbabilitySum)
+        );
+    }
+
+    #[test]
+    fn render_fills_attractor() {
+        let d = array![
+            [0.5, 0.0, 0.0, 0.5, -0.5, -0.5, 0.33],
+            [0.5, 0.0, 0.0, 0.5, 0.0, 0.5, 0.33],
+            [0.5, 0.0, 0.0, 0.5, 0.5, -0.5, 0.33]
+        ];
+        let mut flame = Flame::from_matrix(&d).unwrap();
+        flame.transforms[0].variations = vec![(Variation::Swirl, 0.5), (Variation::Linear, 0.5)];
+
+        let mut rng = seeded_rng(Some(1));
+        let bounds = flame.estimate_bounds(&mut rng, 10000);
+        let image = flame.render(&mut rng, 64, 64, 100000, bounds);
+
+        let lit = image
+            .data()
+            .chunks(4)
+            .filter(|p| p[..3] != [0, 0, 0])
+            .count();
+        assert!(lit > 64 * 64 / 10, "only {} pixels lit", lit);
+    }
+
+    #[test]
+    fn linear_flame_matches_ifs() {
+        let definition = IfsDefinition::load("fern").unwrap();
+        let fern = &definition.layers().next().unwrap().maps;
+
+        // Cells of a grid over the attractor that the chaos game visits
+        let mut program = IfsProgram::with_rng(seeded_rng(Some(1)));
+        program.sample(fern, [0.0, 0.0, 0.0, 1.0], 100000);
+        let points = program.points();
+        let bounds = Bounds::from_points_trimmed(&points, 1.0).unwrap();
+        let cell = |x: f32, y: f32| {
+            let (px, py) = bounds.to_pixel(x, y, 32, 32);
+            (px >= 0.0 && py >= 0.0 && px < 32.0 && py < 32.0)
+                .then_some(py as usize * 32 + px as usize)
+        };
+        let mut visited = [false; 32 * 32];
+        for [x, y] in points {
+            if let Some(i) = cell(x, y) {
+                visited[i] = true;
+            }
+        }
+
+        let flame = Flame::from_matrix(fern).unwrap();
+        let (mut inside, mut total) = (0, 0);
+        flame.iterate(&mut seeded_rng(Some(1)), 10000, |x, y, _| {
+            total += 1;
+            if cell(x, y).is_some_and(|i| visited[i]) {
+                inside += 1;
+            }
+        });
+        assert!(
+            inside as f32 > 0.99 * total as f32,
+            "{} of {} points on the attractor",
+            inside,
+            total
+        );
+    }
+}
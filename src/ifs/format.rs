@@ -0,0 +1,851 @@
+//! Plain-text format for IFS definitions.
+//!
+//! A definition is a list of directives, one per line. Everything after a `#` is a comment and
+//! blank lines are ignored. For example, the Barnsley fern:
+//!
+//! ```text
+//! # Barnsley fern
+//! point_size 1.3
+//! color 0 0.39215687 0 1
+//! iterations 30000
+//! map 0    0     0     0.16 0 0    0.01
+//! map 0.85 0.04  -0.04 0.85 0 1.6  0.85
+//! map 0.2  -0.26 0.23  0.22 0 1.6  0.07
+//! map -0.15 0.28 0.26  0.24 0 0.44 0.07
+//! ```
+//!
+//! Global directives may appear anywhere:
+//!
+//! * `point_size <size>` - size of each point in pixels when rendering.
+//! * `clear_color <r> <g> <b> <a>` - background color.
+//!
+//! Layer directives describe one call to [`IfsProgram::sample_affine`]:
+//!
+//! * `layer` - starts a new layer. Directives before the first `layer` belong to an implicit one.
+//! * `map <a> <b> <c> <d> <e> <f> <p>` - one affine map `x' = ax + by + e`, `y' = cx' + dy + f`
+//!   (see [`apply_map`](crate::ifs::apply_map)), chosen with weight `p`. A layer needs at least
+//!   one map. `p` may be left out if the layer has a `probabilities` directive.
+//! * `geometric [reflect] <scale_x> <scale_y> <rotation> <shear> <x> <y> <p>` - the same, but
+//!   described by what the map does, see [`GeometricMap`]: reflect in the x axis if `reflect`
+//!   is given, shear along x, scale, rotate counterclockwise by `rotation` degrees and move by
+//!   `x` and `y`. Layers with a `geometric` directive write all their maps this way. Maps that
+//!   turn the x axis onto the y axis, like a rotation by 90 degrees, are rejected since no `map`
+//!   row does the same.
+//! * `probabilities given|determinant [<floor>]|uniform [<floor>]` - replaces the probabilities
+//!   of the layer's maps by computed ones, see [`Probabilities`]. The floor defaults to 0.01.
+//! * `color <r> <g> <b> <a>` - color of the layer's points (default black).
+//! * `iterations <n>` - number of chaos game iterations (default 100000).
+//! * `transform <scale> <shift_x> <shift_y>` - shift and scale applied to each point
+//!   (default `1 0 0`).
+//! * `transition <w1> ... <wn>` - one row of a transition matrix, making the layer a recurrent
+//!   IFS (see [`recurrent`](crate::ifs::recurrent)). The `i`-th `transition` directive gives
+//!   the weights of applying each of the `n` maps right after map `i`, and 0 forbids that
+//!   transition. A layer has either no `transition` directive or one per map.
+//!
+//! Finally, `normalize [<x_min> <x_max> <y_min> <y_max>]` ends the current layer and maps all
+//! points sampled since the previous `normalize` into the given range (default `-1 1 -1 1`), see
+//! [`IfsProgram::normalize_points_to_ranges`]. This is how several fractals are stacked into one
+//! picture.
+
+use std::fmt::{Display, Formatter};
+use std::path::Path;
+use std::str::FromStr;
+
+use clap::ValueEnum;
+use ndarray::{Array, Ix2};
+
+use crate::ifs::geometry::GeometricMap;
+use crate::ifs::recurrent::{LayerSystem, RecurrentIfs};
+use crate::ifs::system::{Ifs, IfsWarning, Probabilities};
+use crate::ifs::IfsProgram;
+
+/// The bundled `.ifs` definitions by name, sorted alphabetically. They are compiled into the
+/// binaries so that they can be loaded from anywhere.
+const EXAMPLES: &[(&str, &str)] = &[
+    ("carpet", include_str!("examples/carpet.ifs")),
+    ("castle", include_str!("examples/castle.ifs")),
+    ("clouds", include_str!("examples/clouds.ifs")),
+    ("fern", include_str!("examples/fern.ifs")),
+    ("lattice", include_str!("examples/lattice.ifs")),
+    ("maze", include_str!("examples/maze.ifs")),
+    ("redwoods", include_str!("examples/redwoods.ifs")),
+    ("rocks", include_str!("examples/rocks.ifs")),
+    ("seals", include_str!("examples/seals.ifs")),
+    ("seaweed", include_str!("examples/seaweed.ifs")),
+    ("sierpinski", include_str!("examples/sierpinski.ifs")),
+    ("tree", include_str!("examples/tree.ifs")),
+];
+
+/// Names of the bundled definitions, sorted alphabetically.
+pub fn example_names() -> Vec<String> {
+    EXAMPLES.iter().map(|(name, _)| name.to_string()).collect()
+}
+
+/// Reads `name` if it is a path to an existing file, or else looks it up in `examples`.
+pub(crate) fn read_source(
+    name: &str,
+    examples: &[(&str, &str)],
+) -> Result<String, Box<dyn std::error::Error>> {
+    let path = Path::new(name);
+    if path.is_file() {
+        return std::fs::read_to_string(path)
+            .map_err(|e| format!("failed to read '{}': {}", path.display(), e).into());
+    }
+
+    examples
+        .iter()
+        .find(|(example, _)| *example == name)
+        .map(|(_, contents)| contents.to_string())
+        .ok_or_else(|| format!("'{}' is neither a file nor a bundled example", name).into())
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParseError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// How the maps of a layer are written.
+#[derive(Copy, Clone, Debug, PartialEq, ValueEnum)]
+pub enum MapNotation {
+    /// `map` directives with the coefficients of each row.
+    Matrix,
+    /// `geometric` directives with scales, rotation, shear, reflection and translation.
+    Geometric,
+}
+
+/// A single set of maps sampled with one color.
+#[derive(Clone, Debug, PartialEq)]
+pub struct IfsLayer {
+    /// One row per map, in the 7-column `a, b, c, d, e, f, p` layout.
+    pub maps: Array<f32, Ix2>,
+    pub color: [f32; 4],
+    pub iterations: usize,
+    pub scale: f32,
+    pub shift_x: f32,
+    pub shift_y: f32,
+    /// How the probability column of `maps` was filled in.
+    pub probabilities: Probabilities,
+    /// Transition matrix with one row and column per map, if the layer is a recurrent IFS.
+    pub transitions: Option<Array<f32, Ix2>>,
+    /// Only affects how the layer is written.
+    pub notation: MapNotation,
+}
+
+impl Default for IfsLayer {
+    fn default() -> Self {
+        Self {
+            maps: Array::zeros((0, 7)),
+            color: [0.0, 0.0, 0.0, 1.0],
+            iterations: 100000,
+            scale: 1.0,
+            shift_x: 0.0,
+            shift_y: 0.0,
+            probabilities: Probabilities::Given,
+            transitions: None,
+            notation: MapNotation::Matrix,
+        }
+    }
+}
+
+impl IfsLayer {
+    /// The maps ready for sampling, recurrent if the layer has transitions.
+    ///
+    /// Panics if the maps or the transitions are invalid, which parsed layers never are.
+    pub fn system(&self) -> LayerSystem {
+        let ifs = Ifs::new(self.maps.clone()).unwrap_or_else(|e| panic!("invalid IFS: {}", e));
+        match &self.transitions {
+            Some(transitions) => LayerSystem::Recurrent(
+                RecurrentIfs::new(ifs, transitions.clone())
+                    .unwrap_or_else(|e| panic!("invalid transitions: {}", e)),
+            ),
+            None => LayerSystem::Independent(ifs),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum IfsStep {
+    Sample(IfsLayer),
+    Normalize {
+        x_min: f32,
+        x_max: f32,
+        y_min: f32,
+        y_max: f32,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct IfsDefinition {
+    pub point_size: Option<f32>,
+    pub clear_color: Option<[f32; 4]>,
+    pub steps: Vec<IfsStep>,
+}
+
+impl IfsDefinition {
+    /// Loads a definition from a file path, or from the bundled examples if `name` is not a path
+    /// to an existing file.
+    pub fn load(name: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        Ok(read_source(name, EXAMPLES)?.parse()?)
+    }
+
+    /// Samples every layer into `program`, in order.
+    pub fn apply(&self, program: &mut IfsProgram) {
+        if let Some([r, g, b, a]) = self.clear_color {
+            program.set_clear_color((r, g, b, a));
+        }
+
+        for step in &self.steps {
+            match step {
+                IfsStep::Sample(layer) if layer.transitions.is_some() => program.sample_ifs(
+                    &layer.system(),
+                    layer.color,
+                    layer.iterations,
+                    layer.scale,
+                    layer.shift_x,
+                    layer.shift_y,
+                ),
+                IfsStep::Sample(layer) => program.sample_affine(
+                    &layer.maps,
+                    layer.color,
+                    layer.iterations,
+                    layer.scale,
+                    layer.shift_x,
+                    layer.shift_y,
+                ),
+                IfsStep::Normalize {
+                    x_min,
+                    x_max,
+                    y_min,
+                    y_max,
+                } => program.normalize_points_to_ranges(*x_min, *x_max, *y_min, *y_max),
+            }
+        }
+    }
+
+    /// Makes every layer write its maps in `notation`.
+    pub fn set_notation(&mut self, notation: MapNotation) {
+        for step in &mut self.steps {
+            if let IfsStep::Sample(layer) = step {
+                layer.notation = notation;
+            }
+        }
+    }
+
+    /// Iterates over the layers, skipping normalization steps.
+    pub fn layers(&self) -> impl Iterator<Item = &IfsLayer> {
+        self.steps.iter().filter_map(|step| match step {
+            IfsStep::Sample(layer) => Some(layer),
+            IfsStep::Normalize { .. } => None,
+        })
+    }
+
+    /// Warnings about the maps of every layer, tagged with the index of the layer.
+    pub fn warnings(&self) -> Vec<(usize, IfsWarning)> {
+        let mut ret = vec![];
+        for (i, layer) in self.layers().enumerate() {
+            if let Ok(ifs) = Ifs::new(layer.maps.clone()) {
+                ret.extend(ifs.warnings().iter().map(|w| (i, w.clone())));
+            }
+        }
+        ret
+    }
+}
+
+pub(crate) fn parse_args<T, const N: usize>(
+    line: usize,
+    directive: &str,
+    args: &[&str],
+) -> Result<[T; N], ParseError>
+where
+    T: FromStr + Default + Copy,
+{
+    if args.len() != N {
+        return Err(ParseError {
+            line,
+            message: format!(
+                "'{}' expects {} argument(s), found {}",
+                directive,
+                N,
+                args.len()
+            ),
+        });
+    }
+
+    let mut ret = [T::default(); N];
+    for (out, arg) in ret.iter_mut().zip(args) {
+        *out = arg.parse().map_err(|_| ParseError {
+            line,
+            message: format!("invalid number '{}'", arg),
+        })?;
+    }
+
+    Ok(ret)
+}
+
+fn parse_probabilities(line: usize, args: &[&str]) -> Result<Probabilities, ParseError> {
+    let floor = |args: &[&str]| -> Result<f32, ParseError> {
+        match args {
+            [] => Ok(Probabilities::DEFAULT_FLOOR),
+            _ => parse_args::<f32, 1>(line, "probabilities", args).map(|[floor]| floor),
+        }
+    };
+
+    match args.split_first() {
+        Some((&"given", [])) => Ok(Probabilities::Given),
+        Some((&"determinant", rest)) => Ok(Probabilities::Determinant {
+            floor: floor(rest)?,
+        }),
+        Some((&"uniform", rest)) => Ok(Probabilities::UniformDensity {
+            floor: floor(rest)?,
+        }),
+        _ => Err(ParseError {
+            line,
+            message: String::from(
+                "'probabilities' expects 'given', 'determinant [floor]' or 'uniform [floor]'",
+            ),
+        }),
+    }
+}
+
+/// Accumulates the directives of the layer currently being parsed.
+pub(crate) struct LayerBuilder {
+    pub(crate) layer: IfsLayer,
+    pub(crate) rows: Vec<f32>,
+    start_line: usize,
+    /// Line of the first `map` directive without a probability.
+    missing_probability: Option<usize>,
+    /// Line of each `map` directive, to point errors at the right map.
+    pub(crate) map_lines: Vec<usize>,
+    transitions: Vec<Vec<f32>>,
+    /// Line of each `transition` directive.
+    transition_lines: Vec<usize>,
+}
+
+impl LayerBuilder {
+    pub(crate) fn new(start_line: usize) -> Self {
+        Self {
+            layer: IfsLayer::default(),
+            rows: vec![],
+            start_line,
+            missing_probability: None,
+            map_lines: vec![],
+            transitions: vec![],
+            transition_lines: vec![],
+        }
+    }
+
+    /// Handles one of the layer directives, `map`, `geometric`, `color`, `iterations`,
+    /// `transform`, `probabilities` or `transition`.
+    pub(crate) fn directive(
+        &mut self,
+        line_no: usize,
+        directive: &str,
+        args: &[&str],
+    ) -> Result<(), ParseError> {
+        match directive {
+            "map" => {
+                if args.len() == 6 {
+                    let row: [f32; 6] = parse_args(line_no, directive, args)?;
+                    self.push_map(line_no, row, None);
+                } else {
+                    let [a, b, c, d, e, f, p] = parse_args(line_no, directive, args)?;
+                    self.push_map(line_no, [a, b, c, d, e, f], Some(p));
+                }
+            }
+            "geometric" => {
+                let (reflect, args) = match args.split_first() {
+                    Some((&"reflect", rest)) => (true, rest),
+                    _ => (false, args),
+                };
+                let ([scale_x, scale_y, rotation, shear, translate_x, translate_y], p) =
+                    if args.len() == 6 {
+                        (parse_args(line_no, directive, args)?, None)
+                    } else {
+                        let [sx, sy, rotation, shear, x, y, p] =
+                            parse_args(line_no, directive, args)?;
+                        ([sx, sy, rotation, shear, x, y], Some(p))
+                    };
+                let map = GeometricMap {
+                    scale_x,
+                    scale_y,
+                    rotation,
+                    shear,
+                    reflect,
+                    translate_x,
+                    translate_y,
+                };
+                let row = map.row().ok_or_else(|| ParseError {
+                    line: line_no,
+                    message: String::from(
+                        "map sends the x axis onto the y axis, which a row cannot express, \
+                         change the rotation slightly",
+                    ),
+                })?;
+                self.push_map(line_no, row, p);
+                self.layer.notation = MapNotation::Geometric;
+            }
+            "color" => self.layer.color = parse_args(line_no, directive, args)?,
+            "iterations" => {
+                let [iterations] = parse_args(line_no, directive, args)?;
+                self.layer.iterations = iterations;
+            }
+            "transform" => {
+                let [scale, shift_x, shift_y] = parse_args(line_no, directive, args)?;
+                self.layer.scale = scale;
+                self.layer.shift_x = shift_x;
+                self.layer.shift_y = shift_y;
+            }
+            "probabilities" => {
+                self.layer.probabilities = parse_probabilities(line_no, args)?;
+            }
+            "transition" => {
+                let row = args
+                    .iter()
+                    .map(|arg| {
+                        arg.parse().map_err(|_| ParseError {
+                            line: line_no,
+                            message: format!("invalid number '{}'", arg),
+                        })
+                    })
+                    .collect::<Result<Vec<f32>, _>>()?;
+                self.transitions.push(row);
+                self.transition_lines.push(line_no);
+            }
+            _ => unreachable!(),
+        }
+        Ok(())
+    }
+
+    /// Adds a row, with its probability filled in when the layer is finished if `p` is `None`.
+    fn push_map(&mut self, line_no: usize, row: [f32; 6], p: Option<f32>) {
+        self.rows.extend(row);
+        self.rows.push(p.unwrap_or(0.0));
+        if p.is_none() {
+            self.missing_probability.get_or_insert(line_no);
+        }
+        self.map_lines.push(line_no);
+    }
+
+    pub(crate) fn finish(self) -> Result<IfsLayer, ParseError> {
+        if self.rows.is_empty() {
+            return Err(ParseError {
+                line: self.start_line,
+                message: String::from("layer has no maps"),
+            });
+        }
+
+        if let (Some(line), Probabilities::Given) =
+            (self.missing_probability, self.layer.probabilities)
+        {
+            return Err(ParseError {
+                line,
+                message: String::from(
+                    "map has no probability, add one or use 'probabilities determinant' or \
+                     'probabilities uniform'",
+                ),
+            });
+        }
+
+        let maps = Array::from_shape_vec((self.rows.len() / 7, 7), self.rows).unwrap();
+        let ifs =
+            Ifs::with_probabilities(maps, self.layer.probabilities).map_err(|e| ParseError {
+                line: e.map().map_or(self.start_line, |i| self.map_lines[i]),
+                message: e.to_string(),
+            })?;
+
+        let transitions = match self.transitions.first() {
+            None => self.layer.transitions,
+            Some(first) => {
+                let columns = first.len();
+                if let Some(i) = self.transitions.iter().position(|row| row.len() != columns) {
+                    return Err(ParseError {
+                        line: self.transition_lines[i],
+                        message: format!(
+                            "'transition' expects {} weight(s) like the first one, found {}",
+                            columns,
+                            self.transitions[i].len()
+                        ),
+                    });
+                }
+                let rows = self.transitions.len();
+                let weights = self.transitions.into_iter().flatten().collect();
+                Some(Array::from_shape_vec((rows, columns), weights).unwrap())
+            }
+        };
+        if let Some(transitions) = &transitions {
+            RecurrentIfs::new(ifs.clone(), transitions.clone()).map_err(|e| ParseError {
+                line: e
+                    .row()
+                    .and_then(|i| self.transition_lines.get(i).copied())
+                    .unwrap_or(self.start_line),
+                message: e.to_string(),
+            })?;
+        }
+
+        Ok(IfsLayer {
+            maps: ifs.maps().clone(),
+            transitions,
+            ..self.layer
+        })
+    }
+}
+
+impl FromStr for IfsDefinition {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut ret = IfsDefinition {
+            point_size: None,
+            clear_color: None,
+            steps: vec![],
+        };
+
+        let mut current: Option<LayerBuilder> = None;
+
+        for (i, line) in s.lines().enumerate() {
+            let line_no = i + 1;
+            let line = line.split('#').next().unwrap();
+            let mut tokens = line.split_whitespace();
+            let directive = match tokens.next() {
+                Some(directive) => directive,
+                None => continue,
+            };
+            let args: Vec<&str> = tokens.collect();
+
+            match directive {
+                "point_size" => {
+                    let [size] = parse_args(line_no, directive, &args)?;
+                    ret.point_size = Some(size);
+                }
+                "clear_color" => {
+                    ret.clear_color = Some(parse_args(line_no, directive, &args)?);
+                }
+                "layer" => {
+                    parse_args::<f32, 0>(line_no, directive, &args)?;
+                    if let Some(builder) = current.replace(LayerBuilder::new(line_no)) {
+                        ret.steps.push(IfsStep::Sample(builder.finish()?));
+                    }
+                }
+                "normalize" => {
+                    let [x_min, x_max, y_min, y_max] = if args.is_empty() {
+                        [-1.0, 1.0, -1.0, 1.0]
+                    } else {
+                        parse_args(line_no, directive, &args)?
+                    };
+
+                    if let Some(builder) = current.take() {
+                        ret.steps.push(IfsStep::Sample(builder.finish()?));
+                    }
+
+                    ret.steps.push(IfsStep::Normalize {
+                        x_min,
+                        x_max,
+                        y_min,
+                        y_max,
+                    });
+                }
+                "map" | "geometric" | "color" | "iterations" | "transform" | "probabilities"
+                | "transition" => current
+                    .get_or_insert_with(|| LayerBuilder::new(line_no))
+                    .directive(line_no, directive, &args)?,
+                _ => {
+                    return Err(ParseError {
+                        line: line_no,
+                        message: format!("unknown directive '{}'", directive),
+                    })
+                }
+            }
+        }
+
+        if let Some(builder) = current {
+            ret.steps.push(IfsStep::Sample(builder.finish()?));
+        }
+
+        if ret.steps.is_empty() {
+            return Err(ParseError {
+                line: s.lines().count(),
+                message: String::from("no maps defined"),
+            });
+        }
+
+        Ok(ret)
+    }
+}
+
+pub(crate) fn write_floats(
+    f: &mut Formatter<'_>,
+    directive: &str,
+    values: &[f32],
+) -> std::fmt::Result {
+    f.write_str(directive)?;
+    for v in values {
+        write!(f, " {}", v)?;
+    }
+    writeln!(f)
+}
+
+/// Writes the directives describing `layer`, without the `layer` directive itself.
+pub(crate) fn write_layer(f: &mut Formatter<'_>, layer: &IfsLayer) -> std::fmt::Result {
+    write_floats(f, "color", &layer.color)?;
+    writeln!(f, "iterations {}", layer.iterations)?;
+    if (layer.scale, layer.shift_x, layer.shift_y) != (1.0, 0.0, 0.0) {
+        write_floats(f, "transform", &[layer.scale, layer.shift_x, layer.shift_y])?;
+    }
+    let coefficients = match layer.probabilities {
+        Probabilities::Given => 7,
+        Probabilities::Determinant { floor } => {
+            write_floats(f, "probabilities determinant", &[floor])?;
+            6
+        }
+        Probabilities::UniformDensity { floor } => {
+            write_floats(f, "probabilities uniform", &[floor])?;
+            6
+        }
+    };
+    for row in layer.maps.rows() {
+        let values = &row.to_vec()[..coefficients];
+        match layer.notation {
+            MapNotation::Matrix => write_floats(f, "map", values)?,
+            MapNotation::Geometric => {
+                let map = GeometricMap::from_row(row);
+                let directive = if map.reflect {
+                    "geometric reflect"
+                } else {
+                    "geometric"
+                };
+                let geometric = [
+                    map.scale_x,
+                    map.scale_y,
+                    map.rotation,
+                    map.shear,
+                    map.translate_x,
+                    map.translate_y,
+                ];
+                let probability = &values[6..];
+                write_floats(f, directive, &[&geometric[..], probability].concat())?
+            }
+        }
+    }
+    if let Some(transitions) = &layer.transitions {
+        for row in transitions.rows() {
+            write_floats(f, "transition", &row.to_vec())?;
+        }
+    }
+
+    Ok(())
+}
+
+impl Display for IfsDefinition {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if let Some(size) = self.point_size {
+            write_floats(f, "point_size", &[size])?;
+        }
+        if let Some(color) = self.clear_color {
+            write_floats(f, "clear_color", &color)?;
+        }
+
+        for step in &self.steps {
+            match step {
+                IfsStep::Sample(layer) => {
+                    writeln!(f, "layer")?;
+                    write_layer(f, layer)?;
+                }
+                IfsStep::Normalize {
+                    x_min,
+                    x_max,
+                    y_min,
+                    y_max,
+                } => write_floats(f, "normalize", &[*x_min, *x_max, *y_min, *y_max])?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ndarray::array;
+
+    use crate::ifs::format::{example_names, IfsDefinition, IfsStep, MapNotation};
+
+    #[test]
+    fn examples_are_bundled() {
+        let dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("src/ifs/examples");
+        let mut files: Vec<String> = dir
+            .read_dir()
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "ifs"))
+            .map(|path| path.file_stem().unwrap().to_str().unwrap().to_owned())
+            .collect();
+        files.sort();
+
+        assert_eq!(example_names(), files);
+    }
+
+    #[test]
+    fn load_errors() {
+        let err = IfsDefinition::load("no-such-fractal").unwrap_err();
+        assert!(err.to_string().contains("no-such-fractal"), "{}", err);
+    }
+
+    #[test]
+    fn examples_round_trip() {
+        let names = example_names();
+        assert!(!names.is_empty());
+
+        for name in names {
+            let def = IfsDefinition::load(&name).unwrap();
+            let reparsed: IfsDefinition = def.to_string().parse().unwrap();
+            assert_eq!(def, reparsed, "{} did not round-trip", name);
+        }
+    }
+
+    #[test]
+    fn fern_matches_bin() {
+        let def = IfsDefinition::load("fern").unwrap();
+        let layer = def.layers().next().unwrap();
+
+        assert_eq!(
+            layer.maps,
+            array![
+                [0.0, 0.0, 0.0, 0.16, 0.0, 0.0, 0.01],
+                [0.85, 0.04, -0.04, 0.85, 0.0, 1.6, 0.85],
+                [0.2, -0.26, 0.23, 0.22, 0.0, 1.6, 0.07],
+                [-0.15, 0.28, 0.26, 0.24, 0.0, 0.44, 0.07],
+            ]
+        );
+        assert_eq!(layer.color, [0.0, 100.0 / 255.0, 0.0, 1.0]);
+        assert_eq!(layer.iterations, 30000);
+        assert_eq!(def.point_size, Some(1.3));
+    }
+
+    #[test]
+    fn parse_errors() {
+        let err = "map 1 2 3".parse::<IfsDefinition>().unwrap_err();
+        assert_eq!(err.line, 1);
+
+        let err = "color 0 0 0 1\nfoo 1".parse::<IfsDefinition>().unwrap_err();
+        assert_eq!(err.line, 2);
+
+        let err = "layer\nlayer\nmap 1 0 0 1 0 0 1"
+            .parse::<IfsDefinition>()
+            .unwrap_err();
+        assert_eq!(err.line, 1);
+
+        let err = "map 0.5 0 0 0.5 0 0 1\nmap 0.5 0 0 0.5 0 0 -1"
+            .parse::<IfsDefinition>()
+            .unwrap_err();
+        assert_eq!(err.line, 2);
+        assert!(err.message.contains("negative probability"));
+    }
+
+    #[test]
+    fn computed_probabilities() {
+        let text = "probabilities determinant\nmap 0.5 0 0 0.5 0 0\nmap 0.25 0 0 0.5 0 0";
+        let def: IfsDefinition = text.parse().unwrap();
+        let layer = def.layers().next().unwrap();
+        assert_eq!(layer.maps.column(6).to_vec(), vec![2.0 / 3.0, 1.0 / 3.0]);
+        assert_eq!(def.to_string().parse::<IfsDefinition>().unwrap(), def);
+
+        let err = "map 0.5 0 0 0.5 0 0".parse::<IfsDefinition>().unwrap_err();
+        assert!(err.message.contains("no probability"));
+    }
+
+    #[test]
+    fn transitions() {
+        let def = IfsDefinition::load("lattice").unwrap();
+        let layer = def.layers().next().unwrap();
+        let transitions = layer.transitions.as_ref().unwrap();
+        assert_eq!(transitions.dim(), (4, 4));
+        assert!(transitions.diag().iter().all(|&w| w == 0.0));
+
+        let maps = "map 0.5 0 0 0.5 0 0 1\nmap 0.5 0 0 0.5 1 0 1\n";
+        let err = format!("{}transition 1 1\ntransition 1", maps)
+            .parse::<IfsDefinition>()
+            .unwrap_err();
+        assert_eq!(err.line, 4);
+
+        let err = format!("{}transition 1 1\ntransition 0 0", maps)
+            .parse::<IfsDefinition>()
+            .unwrap_err();
+        assert_eq!(err.line, 4);
+        assert!(err.message.contains("no allowed transition"));
+
+        let err = format!("{}transition 1 1", maps)
+            .parse::<IfsDefinition>()
+            .unwrap_err();
+        assert!(err.message.contains("expected 2x2"));
+    }
+
+    #[test]
+    fn geometric_maps() {
+        let text = "probabilities uniform\n\
+                    geometric 0.5 0.5 0 0 0 0\n\
+                    geometric reflect 0.5 0.25 180 0 1 0\n\
+                    geometric 0.5 0.5 45 0.5 0 1 # a comment";
+        let def: IfsDefinition = text.parse().unwrap();
+        let layer = def.layers().next().unwrap();
+        assert_eq!(layer.notation, MapNotation::Geometric);
+        assert_eq!(
+            layer.maps.row(0).to_vec()[..6],
+            [0.5, 0.0, 0.0, 0.5, 0.0, 0.0]
+        );
+        // Reflected in the x axis and turned around, which flips x
+        assert_eq!(
+            layer.maps.row(1).to_vec()[..6],
+            [-0.5, 0.0, 0.0, 0.25, 1.0, 0.0]
+        );
+
+        // Written back in the same notation, with the same maps up to rounding
+        let written = def.to_string();
+        assert!(
+            written.contains("geometric reflect 0.5 0.25 180 0 1 0"),
+            "{}",
+            written
+        );
+        let reparsed: IfsDefinition = written.parse().unwrap();
+        let maps = &reparsed.layers().next().unwrap().maps;
+        assert!(maps
+            .iter()
+            .zip(&layer.maps)
+            .all(|(a, b)| (a - b).abs() < 1e-5));
+
+        let mut def = IfsDefinition::load("sierpinski").unwrap();
+        def.set_notation(MapNotation::Geometric);
+        let converted: IfsDefinition = def.to_string().parse().unwrap();
+        assert_eq!(
+            converted.layers().next().unwrap().maps,
+            def.layers().next().unwrap().maps
+        );
+
+        let err = "geometric 1 1 0 0 0".parse::<IfsDefinition>().unwrap_err();
+        assert!(err.message.contains("found 5"), "{}", err);
+
+        // A quarter turn
+        let err = "map 0.5 0 0 0.5 0 0 1\ngeometric 0.5 0.5 90 0 0 0 1"
+            .parse::<IfsDefinition>()
+            .unwrap_err();
+        assert_eq!(err.line, 2);
+        assert!(err.message.contains("x axis onto the y axis"), "{}", err);
+    }
+
+    #[test]
+    fn normalize_splits_layers() {
+        let def: IfsDefinition = "map 0.5 0 0 0.5 0 0 1\nnormalize\nmap 0.5 0 0 0.5 1 1 1"
+            .parse()
+            .unwrap();
+        assert_eq!(def.steps.len(), 3);
+        assert!(matches!(def.steps[1], IfsStep::Normalize { x_min, .. } if x_min == -1.0));
+    }
+}
@@ -0,0 +1,182 @@
+//! Affine maps described by what they do to the plane instead of by their coefficients.
+//!
+//! Few people can tell that `0.42 -0.42 0.42 0.42` rotates by 45 degrees and scales by 0.59.
+//! A [`GeometricMap`] says so directly, and converts to and from the coefficients of
+//! [`effective_map`] without losing anything: every linear map is a rotation of a scaled, sheared
+//! and possibly reflected plane. Every row of [`apply_map`](crate::ifs::apply_map) converts too,
+//! but not every map converts back to a row, see [`GeometricMap::row`].
+
+use ndarray::ArrayView1;
+
+use crate::ifs::{effective_map, sequential_map};
+
+/// An affine map that reflects the plane in the x axis if `reflect` is set, then shears it
+/// along x by `shear` (`x += shear * y`), scales it by `scale_x` and `scale_y`, rotates it
+/// counterclockwise by `rotation` degrees and finally moves it by `translate_x` and
+/// `translate_y`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct GeometricMap {
+    pub scale_x: f32,
+    pub scale_y: f32,
+    pub rotation: f32,
+    pub shear: f32,
+    pub reflect: bool,
+    pub translate_x: f32,
+    pub translate_y: f32,
+}
+
+impl Default for GeometricMap {
+    /// The identity.
+    fn default() -> Self {
+        Self {
+            scale_x: 1.0,
+            scale_y: 1.0,
+            rotation: 0.0,
+            shear: 0.0,
+            reflect: false,
+            translate_x: 0.0,
+            translate_y: 0.0,
+        }
+    }
+}
+
+impl GeometricMap {
+    /// Decomposes the conventional affine map with coefficients `a, b, c, d, e, f`.
+    ///
+    /// The scales are never negative, and `reflect` is set exactly when the map flips the
+    /// orientation of the plane. A map squashing everything onto a line gets a `scale_y` of 0,
+    /// and one squashing everything onto a point also a `scale_x` of 0.
+    pub fn from_coefficients(coefs: [f32; 6]) -> Self {
+        let [a, b, c, d, e, f] = coefs.map(f64::from);
+        let reflect = a * d - b * c < 0.0;
+        // Undo the reflection, which negates the second column
+        let (b, d) = if reflect { (-b, -d) } else { (b, d) };
+
+        let scale_x = a.hypot(c);
+        let (rotation, scale_y, shear) = if scale_x > 1e-12 {
+            // Rotating the first column onto the x axis leaves [[scale_x, scale_x * shear],
+            // [0, scale_y]]
+            let rotation = c.atan2(a);
+            let (sin, cos) = rotation.sin_cos();
+            let top = cos * b + sin * d;
+            let bottom = cos * d - sin * b;
+            (rotation, bottom.max(0.0), top / scale_x)
+        } else {
+            // Only the second column is left, rotate the y axis onto it
+            ((0.0 - b).atan2(d), b.hypot(d), 0.0)
+        };
+
+        Self {
+            scale_x: snap(scale_x),
+            scale_y: snap(scale_y),
+            rotation: snap(rotation.to_degrees()),
+            shear: snap(shear),
+            reflect,
+            translate_x: e as f32,
+            translate_y: f as f32,
+        }
+    }
+
+    /// Same as [`GeometricMap::from_coefficients`] for a row in the 7-column IFS layout.
+    pub fn from_row(r: ArrayView1<f32>) -> Self {
+        Self::from_coefficients(effective_map(r))
+    }
+
+    /// Coefficients `a, b, c, d, e, f` of the conventional affine map `x' = ax + by + e`,
+    /// `y' = cx + dy + f`.
+    pub fn coefficients(&self) -> [f32; 6] {
+        let (sin, cos) = f64::from(self.rotation).to_radians().sin_cos();
+        let (sx, sy) = (f64::from(self.scale_x), f64::from(self.scale_y));
+        let sheared = sx * f64::from(self.shear);
+        let flip = if self.reflect { -1.0 } else { 1.0 };
+        [
+            snap(cos * sx),
+            snap(flip * (cos * sheared - sin * sy)),
+            snap(sin * sx),
+            snap(flip * (sin * sheared + cos * sy)),
+            self.translate_x,
+            self.translate_y,
+        ]
+    }
+
+    /// The first six columns of a row in the 7-column IFS layout, see [`sequential_map`].
+    ///
+    /// Returns `None` for maps with `a` = 0 but `c` != 0, like a rotation by 90 degrees, which
+    /// cannot be written as a row.
+    pub fn row(&self) -> Option<[f32; 6]> {
+        sequential_map(self.coefficients())
+    }
+}
+
+/// Rounds the leftovers of floating point error to 0, so that e.g. rotations by multiples of 90
+/// degrees give exact zeros instead of 6e-17.
+fn snap(v: f64) -> f32 {
+    if v.abs() < 1e-9 {
+        0.0
+    } else {
+        v as f32
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ndarray::{array, ArrayView1};
+
+    use crate::ifs::effective_map;
+    use crate::ifs::geometry::GeometricMap;
+
+    fn assert_close(a: [f32; 6], b: [f32; 6]) {
+        for (x, y) in a.iter().zip(&b) {
+            assert!((x - y).abs() < 1e-5, "{:?} != {:?}", a, b);
+        }
+    }
+
+    #[test]
+    fn decompose() {
+        let half = 0.5_f32.sqrt() * 0.6;
+        let map = GeometricMap::from_coefficients([half, -half, half, half, 1.0, 2.0]);
+        assert!((map.rotation - 45.0).abs() < 1e-4, "{:?}", map);
+        assert!((map.scale_x - 0.6).abs() < 1e-6 && (map.scale_y - 0.6).abs() < 1e-6);
+        assert!(map.shear.abs() < 1e-6 && !map.reflect);
+        assert_eq!((map.translate_x, map.translate_y), (1.0, 2.0));
+
+        let mirror = GeometricMap::from_coefficients([-0.5, 0.0, 0.0, 0.5, 0.0, 0.0]);
+        assert!(mirror.reflect);
+        assert_close(mirror.coefficients(), [-0.5, 0.0, 0.0, 0.5, 0.0, 0.0]);
+
+        let quarter = GeometricMap {
+            rotation: 90.0,
+            ..GeometricMap::default()
+        };
+        assert_eq!(quarter.coefficients(), [0.0, -1.0, 1.0, 0.0, 0.0, 0.0]);
+        // x' no longer depends on x, so y' can't get x from it
+        assert_eq!(quarter.row(), None);
+    }
+
+    #[test]
+    fn round_trip() {
+        let maps = [
+            // Barnsley fern, including the stem that squashes everything onto a line
+            [0.0, 0.0, 0.0, 0.16, 0.0, 0.0],
+            [0.85, 0.04, -0.04, 0.85, 0.0, 1.6],
+            [0.2, -0.26, 0.23, 0.22, 0.0, 1.6],
+            [-0.15, 0.28, 0.26, 0.24, 0.0, 0.44],
+            [0.3, 0.6, 0.15, 0.3, 0.0, 0.0],
+            [0.0, 0.0, 0.0, 0.0, 0.5, 0.5],
+        ];
+        for coefs in maps {
+            let map = GeometricMap::from_coefficients(coefs);
+            assert!(map.scale_x >= 0.0 && map.scale_y >= 0.0, "{:?}", map);
+            assert_close(map.coefficients(), coefs);
+        }
+
+        let row = array![0.42, -0.42, 0.42, 0.42, 0.0, 0.2, 0.4];
+        let map = GeometricMap::from_row(row.view());
+        let mut rebuilt = map.row().unwrap().to_vec();
+        rebuilt.push(0.4);
+        assert_close(
+            effective_map(ArrayView1::from(&rebuilt)),
+            effective_map(row.view()),
+        );
+    }
+}
@@ -0,0 +1,381 @@
+//! The inverse problem: finding an IFS whose attractor looks like a given shape.
+//!
+//! Rendering a candidate attractor for every step of a search would be far too slow. Instead the
+//! search relies on the collage theorem: if the union of the images of the target `T` under the
+//! maps (the collage) is close to `T`, then so is the attractor `A`. In terms of the Hausdorff
+//! distance `h(T, A) <= h(T, collage) / (1 - s)`, where `s` is the contraction factor of the
+//! IFS. Computing a collage only takes one pass over the target's pixels per map.
+
+use clap::ValueEnum;
+use ndarray::{Array, ArrayView1, Ix2};
+use rand::Rng;
+
+use crate::ifs::system::Probabilities;
+use crate::ifs::{apply_map, effective_map, operator_norm};
+use crate::image::Image;
+
+/// A binary image, indexed by `[row, column]` with row 0 at the top.
+pub type Mask = Array<bool, Ix2>;
+
+/// Thresholds an image: dark, opaque pixels are part of the shape, or light ones if `invert` is
+/// set.
+pub fn mask_from_image(image: &Image, invert: bool) -> Mask {
+    Mask::from_shape_fn(
+        (image.height() as usize, image.width() as usize),
+        |(r, c)| {
+            let [red, green, blue, alpha] = image.pixel(c as u32, r as u32);
+            let luminance = 0.299 * red as f32 + 0.587 * green as f32 + 0.114 * blue as f32;
+            alpha >= 128 && ((luminance < 128.0) != invert)
+        },
+    )
+}
+
+/// Shrinks a mask so that neither side exceeds `max_size`. A pixel is set if any pixel in the
+/// block it replaces is set, so thin features survive.
+pub fn downsample(mask: &Mask, max_size: usize) -> Mask {
+    let (rows, cols) = mask.dim();
+    let factor = rows.max(cols).div_ceil(max_size.max(1)).max(1);
+
+    let mut ret = Mask::from_elem((rows.div_ceil(factor), cols.div_ceil(factor)), false);
+    for ((r, c), &set) in mask.indexed_iter() {
+        if set {
+            ret[[r / factor, c / factor]] = true;
+        }
+    }
+    ret
+}
+
+/// Approximate Euclidean distance, in pixels, from every pixel to the nearest set pixel of
+/// `mask`, using two chamfer passes.
+fn distance_transform(mask: &Mask) -> Array<f32, Ix2> {
+    let (rows, cols) = mask.dim();
+    let mut dist = mask.map(|&set| if set { 0.0 } else { f32::MAX });
+
+    let neighbors = [
+        (-1, -1, 2_f32.sqrt()),
+        (-1, 0, 1.0),
+        (-1, 1, 2_f32.sqrt()),
+        (0, -1, 1.0),
+    ];
+    let relax = |r: usize, c: usize, sign: i64, dist: &mut Array<f32, Ix2>| {
+        for (dr, dc, cost) in neighbors {
+            let nr = r as i64 + dr * sign;
+            let nc = c as i64 + dc * sign;
+            if nr >= 0 && nc >= 0 && (nr as usize) < rows && (nc as usize) < cols {
+                let candidate = dist[[nr as usize, nc as usize]] + cost;
+                if candidate < dist[[r, c]] {
+                    dist[[r, c]] = candidate;
+                }
+            }
+        }
+    };
+
+    for r in 0..rows {
+        for c in 0..cols {
+            relax(r, c, 1, &mut dist);
+        }
+    }
+    for r in (0..rows).rev() {
+        for c in (0..cols).rev() {
+            relax(r, c, -1, &mut dist);
+        }
+    }
+
+    dist
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, ValueEnum, strum_macros::Display)]
+pub enum CollageMetric {
+    /// Pixels in the collage or the target but not both, relative to the size of the target.
+    Pixel,
+    /// Hausdorff distance between the collage and the target, relative to the image size.
+    Hausdorff,
+}
+
+/// Measures how well the collage of a set of maps covers a target shape.
+///
+/// The target lives in the unit square: its longer side spans 0.0 to 1.0, with y pointing up so
+/// that the resulting IFS is drawn the right way up.
+pub struct Collage {
+    target: Mask,
+    points: Vec<[f32; 2]>,
+    distance_to_target: Array<f32, Ix2>,
+    size: f32,
+    metric: CollageMetric,
+}
+
+impl Collage {
+    pub fn new(target: Mask, metric: CollageMetric) -> Self {
+        let (rows, cols) = target.dim();
+        let size = rows.max(cols) as f32;
+        let points = target
+            .indexed_iter()
+            .filter(|(_, &set)| set)
+            .map(|((r, c), _)| {
+                [
+                    (c as f32 + 0.5) / size,
+                    (rows - r) as f32 / size - 0.5 / size,
+                ]
+            })
+            .collect();
+
+        Self {
+            distance_to_target: distance_transform(&target),
+            target,
+            points,
+            size,
+            metric,
+        }
+    }
+
+    /// Number of pixels in the target shape.
+    pub fn area(&self) -> usize {
+        self.points.len()
+    }
+
+    /// Pixel containing a point in unit square coordinates, if it lies inside the image.
+    fn pixel(&self, p: (f32, f32)) -> Option<(usize, usize)> {
+        let (rows, cols) = self.target.dim();
+        let c = (p.0 * self.size).floor();
+        let r = rows as f32 - (p.1 * self.size).floor() - 1.0;
+        (c >= 0.0 && r >= 0.0 && (c as usize) < cols && (r as usize) < rows)
+            .then_some((r as usize, c as usize))
+    }
+
+    /// Error of the collage of `maps`, given in the 7-column IFS layout.
+    pub fn error(&self, maps: &Array<f32, Ix2>) -> f32 {
+        if self.points.is_empty() {
+            return 0.0;
+        }
+
+        let mut collage = Mask::from_elem(self.target.dim(), false);
+        // Distance from the farthest collage point to the target, in pixels
+        let mut stray: f32 = 0.0;
+        let mut outside = 0;
+
+        for row in maps.rows() {
+            for p in &self.points {
+                let q = apply_map(row, p[0], p[1]);
+                match self.pixel(q) {
+                    Some(pixel) => {
+                        collage[pixel] = true;
+                        stray = stray.max(self.distance_to_target[pixel]);
+                    }
+                    None => {
+                        outside += 1;
+                        stray = stray.max(self.size);
+                    }
+                }
+            }
+        }
+
+        match self.metric {
+            CollageMetric::Pixel => {
+                let mismatched = collage
+                    .iter()
+                    .zip(self.target.iter())
+                    .filter(|(a, b)| a != b)
+                    .count();
+                (mismatched + outside) as f32 / self.points.len() as f32
+            }
+            CollageMetric::Hausdorff => {
+                let distance_to_collage = distance_transform(&collage);
+                let missed = self
+                    .target
+                    .indexed_iter()
+                    .filter(|(_, &set)| set)
+                    .map(|(pixel, _)| distance_to_collage[pixel])
+                    .fold(0.0, f32::max);
+                stray.max(missed) / self.size
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct CollageFit {
+    /// The maps found, in the 7-column IFS layout, with probabilities proportional to area.
+    pub maps: Array<f32, Ix2>,
+    pub error: f32,
+    /// The largest operator norm of any map.
+    pub contraction: f32,
+}
+
+/// Searches for maps with a small collage error using simulated annealing.
+#[derive(Clone, Debug)]
+pub struct InverseSolver {
+    pub maps: usize,
+    /// Annealing steps per restart.
+    pub steps: usize,
+    pub restarts: usize,
+    pub metric: CollageMetric,
+    /// The target is downsampled to at most this many pixels per side before searching.
+    pub resolution: usize,
+    /// Maps with a larger operator norm are rejected, so the result is guaranteed to converge.
+    pub max_contraction: f32,
+}
+
+impl Default for InverseSolver {
+    fn default() -> Self {
+        Self {
+            maps: 4,
+            steps: 20000,
+            restarts: 4,
+            metric: CollageMetric::Pixel,
+            resolution: 64,
+            max_contraction: 0.9,
+        }
+    }
+}
+
+impl InverseSolver {
+    /// Panics if `maps` is 0.
+    pub fn fit<R: Rng + ?Sized>(&self, target: &Mask, rng: &mut R) -> CollageFit {
+        assert!(self.maps > 0, "an IFS needs at least one map");
+        let collage = Collage::new(downsample(target, self.resolution), self.metric);
+
+        let mut best: Option<(Array<f32, Ix2>, f32)> = None;
+        for _ in 0..self.restarts.max(1) {
+            let (maps, error) = self.anneal(&collage, rng);
+            if best
+                .as_ref()
+                .is_none_or(|(_, best_error)| error < *best_error)
+            {
+                best = Some((maps, error));
+            }
+        }
+
+        let (mut maps, error) = best.unwrap();
+        let probabilities = Probabilities::Determinant {
+            floor: Probabilities::DEFAULT_FLOOR,
+        }
+        .compute(&maps)
+        .unwrap();
+        maps.column_mut(6).assign(&ArrayView1::from(&probabilities));
+        let contraction = maps
+            .rows()
+            .into_iter()
+            .map(|row| operator_norm(effective_map(row)))
+            .fold(0.0, f32::max);
+
+        CollageFit {
+            maps,
+            error,
+            contraction,
+        }
+    }
+
+    /// A shrunken copy of the target placed at a random point inside it.
+    fn random_map<R: Rng + ?Sized>(collage: &Collage, rng: &mut R) -> [f32; 7] {
+        let n = collage.points.len().max(1) as f32;
+        let cx = collage.points.iter().map(|p| p[0]).sum::<f32>() / n;
+        let cy = collage.points.iter().map(|p| p[1]).sum::<f32>() / n;
+
+        let scale = rng.gen_range(0.3..0.6);
+        let [tx, ty] = if collage.points.is_empty() {
+            [0.5, 0.5]
+        } else {
+            collage.points[rng.gen_range(0..collage.points.len())]
+        };
+
+        [
+            scale,
+            0.0,
+            0.0,
+            scale,
+            tx - scale * cx,
+            ty - scale * cy,
+            1.0,
+        ]
+    }
+
+    fn anneal<R: Rng + ?Sized>(&self, collage: &Collage, rng: &mut R) -> (Array<f32, Ix2>, f32) {
+        let mut current = Array::zeros((self.maps, 7));
+        for mut row in current.rows_mut() {
+            row.assign(&ArrayView1::from(&Self::random_map(collage, rng)));
+        }
+        let mut current_error = collage.error(&current);
+        let mut best = (current.clone(), current_error);
+
+        let initial_temperature = 0.02;
+        for step in 0..self.steps {
+            let progress = step as f32 / self.steps as f32;
+            let temperature = initial_temperature * (1.0 - progress) + 1e-6;
+            // Large moves early on to explore, small ones at the end to refine
+            let step_size = 0.2 * (1.0 - progress) + 0.005;
+
+            let mut candidate = current.clone();
+            let map = rng.gen_range(0..self.maps);
+            if rng.gen::<f32>() < 0.02 {
+                // Occasionally start a map over, to get it out of a spot where it is stuck
+                let fresh = Self::random_map(collage, rng);
+                candidate.row_mut(map).assign(&ArrayView1::from(&fresh));
+            } else {
+                let coef = rng.gen_range(0..6);
+                candidate[[map, coef]] += rng.gen_range(-step_size..step_size);
+            }
+
+            if operator_norm(effective_map(candidate.row(map))) > self.max_contraction {
+                continue;
+            }
+
+            let error = collage.error(&candidate);
+            if error < current_error
+                || rng.gen::<f32>() < ((current_error - error) / temperature).exp()
+            {
+                current = candidate;
+                current_error = error;
+
+                if current_error < best.1 {
+                    best = (current.clone(), current_error);
+                }
+            }
+        }
+
+        best
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ndarray::array;
+
+    use crate::ifs::inverse::{Collage, CollageMetric, InverseSolver, Mask};
+    use crate::utils::rng::seeded_rng;
+
+    fn square() -> Mask {
+        Mask::from_shape_fn((32, 32), |(r, c)| {
+            (8..24).contains(&r) && (8..24).contains(&c)
+        })
+    }
+
+    #[test]
+    fn exact_collage() {
+        // The square from 0.25 to 0.75 is covered by four half-size copies of itself
+        let maps = array![
+            [0.5, 0.0, 0.0, 0.5, 0.125, 0.125, 0.25],
+            [0.5, 0.0, 0.0, 0.5, 0.375, 0.125, 0.25],
+            [0.5, 0.0, 0.0, 0.5, 0.125, 0.375, 0.25],
+            [0.5, 0.0, 0.0, 0.5, 0.375, 0.375, 0.25],
+        ];
+
+        for metric in [CollageMetric::Pixel, CollageMetric::Hausdorff] {
+            let collage = Collage::new(square(), metric);
+            assert_eq!(collage.error(&maps), 0.0);
+        }
+    }
+
+    #[test]
+    fn fit_square() {
+        let solver = InverseSolver {
+            steps: 3000,
+            restarts: 1,
+            ..InverseSolver::default()
+        };
+        let fit = solver.fit(&square(), &mut seeded_rng(Some(3)));
+
+        assert!(fit.error < 0.3, "collage error {}", fit.error);
+        assert!(fit.contraction <= solver.max_contraction);
+        assert!((fit.maps.column(6).sum() - 1.0).abs() < 1e-4);
+    }
+}
@@ -0,0 +1,1007 @@
+use std::collections::VecDeque;
+use std::ops::Range;
+
+use glium::glutin::dpi::LogicalSize;
+use glium::glutin::event::{Event, WindowEvent};
+use glium::glutin::event_loop::ControlFlow::Wait;
+use glium::glutin::event_loop::{ControlFlow, EventLoop};
+use glium::glutin::window::WindowBuilder;
+use glium::glutin::ContextBuilder;
+use glium::index::{NoIndices, PrimitiveType};
+use glium::uniforms::{UniformValue, Uniforms};
+use glium::{implement_vertex, Display, DrawParameters, Program, Surface, VertexBuffer};
+use ndarray::{s, Array, ArrayView1, Ix2};
+use rand::distributions::WeightedIndex;
+use rand::Rng;
+use rand_chacha::ChaCha8Rng;
+
+use crate::args::ColorScheme;
+use crate::ifs::system::{Ifs, MapSystem, Probabilities};
+use crate::image::{to_rgba8, Image};
+use crate::svg::Svg;
+use crate::utils::parallel::run_worker_ranges;
+use crate::utils::rng::seeded_rng;
+
+pub mod attractor;
+pub mod compression;
+pub mod density;
+pub mod deterministic;
+pub mod discover;
+pub mod editor;
+pub mod flame;
+pub mod format;
+pub mod geometry;
+pub mod inverse;
+pub mod morph;
+pub mod progressive;
+pub mod recurrent;
+pub mod scene;
+pub mod system;
+pub mod three_d;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct ColoredVertex {
+    position: [f32; 2],
+    color: [f32; 4],
+}
+
+implement_vertex!(ColoredVertex, position, color);
+
+#[derive(Copy, Clone, Debug)]
+struct MapParams {
+    x_min: f32,
+    x_max: f32,
+    y_min: f32,
+    y_max: f32,
+    normalized: bool,
+}
+
+impl MapParams {
+    /// Normalized device coordinates of a point, the same mapping as the vertex shader in
+    /// [`IfsProgram::run`].
+    fn ndc(&self, position: [f32; 2]) -> (f32, f32) {
+        if self.normalized {
+            (position[0], position[1])
+        } else {
+            (
+                map_f32(position[0], self.x_min, self.x_max, -1.0, 1.0),
+                map_f32(position[1], self.y_min, self.y_max, -1.0, 1.0),
+            )
+        }
+    }
+}
+
+impl Default for MapParams {
+    fn default() -> Self {
+        Self {
+            x_min: f32::MAX,
+            x_max: f32::MIN,
+            y_min: f32::MAX,
+            y_max: f32::MIN,
+            normalized: false,
+        }
+    }
+}
+
+/// How [`IfsProgram`] colors the points it samples.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ColorMode {
+    /// Every point gets the color passed to [`IfsProgram::sample_affine`].
+    Uniform,
+    /// By the indices of the last `history` maps applied to reach the point, most recent first.
+    /// With a history of 1 every copy of the attractor within itself gets its own color, longer
+    /// histories color the copies within the copies too.
+    Map {
+        history: usize,
+        palette: ColorScheme,
+    },
+    /// By the number of points landing in the same pixel, on a log scale. Applies to all points
+    /// when rendering, regardless of when they were sampled.
+    Density { palette: ColorScheme },
+    /// By how late in the chaos game the point was sampled.
+    Age { palette: ColorScheme },
+}
+
+/// Colors vertices by the number of vertices in the same pixel of a `width` x `height` image.
+fn color_by_density<F>(
+    vertices: &mut [ColoredVertex],
+    to_ndc: F,
+    width: u32,
+    height: u32,
+    palette: ColorScheme,
+) where
+    F: Fn([f32; 2]) -> (f32, f32),
+{
+    let pixel = |v: &ColoredVertex| {
+        let (x, y) = to_ndc(v.position);
+        // Points on the far edges of the bounds belong to the last row or column
+        let px = ((x + 1.0) / 2.0 * width as f32)
+            .floor()
+            .min(width as f32 - 1.0);
+        let py = ((1.0 - y) / 2.0 * height as f32)
+            .floor()
+            .min(height as f32 - 1.0);
+        (px >= 0.0 && py >= 0.0).then(|| py as usize * width as usize + px as usize)
+    };
+
+    let mut counts = vec![0_u32; (width * height) as usize];
+    for v in vertices.iter() {
+        if let Some(i) = pixel(v) {
+            counts[i] += 1;
+        }
+    }
+
+    let max = (*counts.iter().max().unwrap_or(&0) as f32).ln_1p();
+    for v in vertices.iter_mut() {
+        if let Some(i) = pixel(v) {
+            let t = if max > 0.0 {
+                (counts[i] as f32).ln_1p() / max
+            } else {
+                0.0
+            };
+            let [r, g, b] = palette.color(t);
+            v.color = [r, g, b, 1.0];
+        }
+    }
+}
+
+/// Runs the chaos game from the origin for the iterations in `iters`, out of `total` in the
+/// whole run, calling `plot` with every point after the first few and its color according to
+/// `color_mode`.
+fn chaos_game<R, S, F>(
+    rng: &mut R,
+    ifs: &S,
+    color: [f32; 4],
+    color_mode: ColorMode,
+    iters: Range<usize>,
+    total: usize,
+    mut plot: F,
+) where
+    R: Rng + ?Sized,
+    S: MapSystem,
+    F: FnMut(f32, f32, [f32; 4]),
+{
+    // Initial starting point
+    let mut x: f32 = 0.0;
+    let mut y: f32 = 0.0;
+    // Indices of the most recently applied maps, most recent first
+    let mut history: VecDeque<usize> = VecDeque::new();
+    let mut previous = None;
+
+    for (n, i) in iters.enumerate() {
+        let map = ifs.next_map(rng, previous);
+        previous = Some(map);
+        (x, y) = ifs.apply(map, x, y);
+
+        let color = match color_mode {
+            ColorMode::Uniform | ColorMode::Density { .. } => color,
+            ColorMode::Map {
+                history: length,
+                palette,
+            } => {
+                history.push_front(map);
+                history.truncate(length.max(1));
+
+                // Read the indices as the digits of a number in base n, so that points sharing
+                // their most recent maps get similar colors
+                let n = ifs.map_count() as f32;
+                let (t, digit) = history.iter().fold((0.0, 1.0), |(t, digit), &m| {
+                    (t + m as f32 * digit / n, digit / n)
+                });
+                let [r, g, b] = palette.color(t + digit / 2.0);
+                [r, g, b, color[3]]
+            }
+            ColorMode::Age { palette } => {
+                let [r, g, b] = palette.color(i as f32 / total as f32);
+                [r, g, b, color[3]]
+            }
+        };
+
+        // Skip first few iterations
+        if n >= 10 {
+            plot(x, y, color);
+        }
+    }
+}
+
+/// An axis-aligned rectangle in the plane.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Bounds {
+    pub x_min: f32,
+    pub x_max: f32,
+    pub y_min: f32,
+    pub y_max: f32,
+}
+
+impl Bounds {
+    /// Bounds containing the central `fraction` of the points along each axis, which keeps a
+    /// handful of outliers from shrinking the interesting part of the picture.
+    pub fn from_points_trimmed(points: &[[f32; 2]], fraction: f32) -> Option<Self> {
+        if points.is_empty() {
+            return None;
+        }
+
+        let trimmed = |axis: usize| {
+            let mut values: Vec<f32> = points
+                .iter()
+                .map(|p| p[axis])
+                .filter(|v| v.is_finite())
+                .collect();
+            values.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+            let skip = ((1.0 - fraction.clamp(0.0, 1.0)) / 2.0 * values.len() as f32) as usize;
+            Some((*values.get(skip)?, values[values.len() - 1 - skip]))
+        };
+
+        let (x_min, x_max) = trimmed(0)?;
+        let (y_min, y_max) = trimmed(1)?;
+        Some(Self {
+            x_min,
+            x_max,
+            y_min,
+            y_max,
+        })
+    }
+
+    pub fn width(&self) -> f32 {
+        self.x_max - self.x_min
+    }
+
+    pub fn height(&self) -> f32 {
+        self.y_max - self.y_min
+    }
+
+    /// Grows the bounds by `fraction` of their size on every side.
+    pub fn padded(&self, fraction: f32) -> Self {
+        let dx = self.width() * fraction;
+        let dy = self.height() * fraction;
+        Self {
+            x_min: self.x_min - dx,
+            x_max: self.x_max + dx,
+            y_min: self.y_min - dy,
+            y_max: self.y_max + dy,
+        }
+    }
+
+    /// Maps a point to pixel coordinates in a `width` x `height` image, y pointing down.
+    pub fn to_pixel(&self, x: f32, y: f32, width: u32, height: u32) -> (f32, f32) {
+        (
+            map_f32(x, self.x_min, self.x_max, 0.0, width as f32),
+            map_f32(y, self.y_min, self.y_max, height as f32, 0.0),
+        )
+    }
+
+    /// Smallest bounds containing both.
+    pub fn union(&self, other: &Bounds) -> Self {
+        Self {
+            x_min: self.x_min.min(other.x_min),
+            x_max: self.x_max.max(other.x_max),
+            y_min: self.y_min.min(other.y_min),
+            y_max: self.y_max.max(other.y_max),
+        }
+    }
+
+    /// The inverse of [`Bounds::to_pixel`].
+    pub fn from_pixel(&self, px: f32, py: f32, width: u32, height: u32) -> (f32, f32) {
+        (
+            map_f32(px, 0.0, width as f32, self.x_min, self.x_max),
+            map_f32(py, height as f32, 0.0, self.y_min, self.y_max),
+        )
+    }
+}
+
+/// Applies one row of an IFS matrix, in the 7-column `a, b, c, d, e, f, p` layout, to a point.
+///
+/// Note that `y` is computed from the already updated `x`, i.e. `x' = ax + by + e` followed by
+/// `y' = cx' + dy + f`. All of the book's pictures were drawn this way, so it is kept as is.
+/// [`effective_map`] gives the equivalent conventional affine map.
+pub fn apply_map(r: ArrayView1<f32>, x: f32, y: f32) -> (f32, f32) {
+    let x = r[0] * x + r[1] * y + r[4];
+    let y = r[2] * x + r[3] * y + r[5];
+    (x, y)
+}
+
+/// Coefficients `a, b, c, d, e, f` of the conventional affine map `x' = ax + by + e`,
+/// `y' = cx + dy + f` that does the same thing as [`apply_map`] with row `r`.
+pub fn effective_map(r: ArrayView1<f32>) -> [f32; 6] {
+    [
+        r[0],
+        r[1],
+        r[2] * r[0],
+        r[2] * r[1] + r[3],
+        r[4],
+        r[2] * r[4] + r[5],
+    ]
+}
+
+/// The inverse of [`effective_map`]: the first six columns of the row that makes [`apply_map`]
+/// do the same thing as the conventional affine map with coefficients `a, b, c, d, e, f`.
+///
+/// Returns `None` if `a` is 0 but `c` is not. Such maps cannot be written as a row, because `y'`
+/// only depends on `x` through `x'`.
+pub fn sequential_map(coefs: [f32; 6]) -> Option<[f32; 6]> {
+    let [a, b, c, d, e, f] = coefs;
+    let k = if a != 0.0 {
+        c / a
+    } else if c == 0.0 {
+        0.0
+    } else {
+        return None;
+    };
+    Some([a, b, k, d - k * b, e, f - k * e])
+}
+
+/// Same as [`sequential_map`], but maps that cannot be written as a row get `a` nudged away from
+/// 0 first, which changes them only slightly.
+pub fn nearest_sequential_map(coefs: [f32; 6]) -> [f32; 6] {
+    sequential_map(coefs).unwrap_or_else(|| {
+        let [_, b, c, d, e, f] = coefs;
+        sequential_map([1e-3, b, c, d, e, f]).unwrap()
+    })
+}
+
+/// Largest factor by which the linear part `[[a, b], [c, d]]` of an affine map stretches any
+/// vector, i.e. its largest singular value. A map is a contraction if this is below 1.
+pub fn operator_norm(coefs: [f32; 6]) -> f32 {
+    let [a, b, c, d, _, _] = coefs;
+    // Eigenvalues of M^T M are (t ± sqrt(t² - 4 det²)) / 2
+    let t = a * a + b * b + c * c + d * d;
+    let det = a * d - b * c;
+    ((t + (t * t - 4.0 * det * det).max(0.0).sqrt()) / 2.0).sqrt()
+}
+
+/// The point that the conventional affine map with coefficients `a, b, c, d, e, f` leaves in
+/// place, or `None` if there is no single such point. Contractions always have one, and the
+/// attractor of an IFS contains the fixed point of each of its maps.
+pub fn fixed_point(coefs: [f32; 6]) -> Option<[f32; 2]> {
+    let [a, b, c, d, e, f] = coefs;
+    // Solve (I - M) p = (e, f)
+    let det = (1.0 - a) * (1.0 - d) - b * c;
+    if det.abs() < 1e-6 {
+        return None;
+    }
+    Some([((1.0 - d) * e + b * f) / det, (c * e + (1.0 - a) * f) / det])
+}
+
+/// Distribution used to pick a row of `d`, weighted by the probabilities in its last column.
+pub(crate) fn map_distribution(d: &Array<f32, Ix2>) -> WeightedIndex<f32> {
+    let probs: Vec<f32> = d.slice(s![.., -1]).to_vec();
+    WeightedIndex::new(probs).unwrap()
+}
+
+fn map_f32(x: f32, in_min: f32, in_max: f32, out_min: f32, out_max: f32) -> f32 {
+    (x - in_min) * (out_max - out_min) / (in_max - in_min) + out_min
+}
+
+/// Shader program drawing [`ColoredVertex`] points with the bounds in [`MapParams`].
+fn point_program(display: &Display) -> Program {
+    Program::from_source(
+        display,
+        r##"#version 140
+uniform float x_min;
+uniform float x_max;
+uniform float y_min;
+uniform float y_max;
+uniform bool normalized;
+
+in vec4 color;
+out vec4 v_color;
+
+float map(float x, float in_min, float in_max, float out_min, float out_max) {
+    return (x - in_min) * (out_max - out_min) / (in_max - in_min) + out_min;
+}
+
+in vec2 position;
+void main() {
+    if (normalized) {
+	    gl_Position = vec4(position.xy, 0.0, 1.0);
+	} else {
+	    gl_Position = vec4(map(position.x, x_min, x_max, -1.0, 1.0), map(position.y, y_min, y_max, -1.0, 1.0), 0.0, 1.0);
+	}
+
+	v_color = color;
+}
+"##,
+        r##"#version 130
+in vec4 v_color;
+out vec4 color;
+void main() {
+	color = v_color;
+}
+"##,
+        None,
+    )
+    .unwrap()
+}
+
+pub struct IfsProgram {
+    uniforms: MapParams,
+    vertices: Vec<ColoredVertex>,
+    normalized_vertices: Vec<ColoredVertex>,
+    clear_color: (f32, f32, f32, f32),
+    rng: ChaCha8Rng,
+    probabilities: Probabilities,
+    color_mode: ColorMode,
+    threads: usize,
+    view: Option<Bounds>,
+}
+
+impl Default for IfsProgram {
+    fn default() -> Self {
+        Self::with_rng(seeded_rng(None))
+    }
+}
+
+impl IfsProgram {
+    /// Creates a program whose sampling is driven by `rng`, so that seeded programs produce
+    /// identical points on every run.
+    pub fn with_rng(rng: ChaCha8Rng) -> Self {
+        Self {
+            uniforms: MapParams::default(),
+            vertices: vec![],
+            normalized_vertices: vec![],
+            clear_color: (255.0, 255.0, 255.0, 1.0),
+            rng,
+            probabilities: Probabilities::Given,
+            color_mode: ColorMode::Uniform,
+            threads: 1,
+            view: None,
+        }
+    }
+
+    pub fn set_clear_color(&mut self, color: (f32, f32, f32, f32)) {
+        self.clear_color = color;
+    }
+
+    /// Sets how [`IfsProgram::sample_affine`] picks the probability of each map. With anything
+    /// but [`Probabilities::Given`] the probability column of the matrices may be left out.
+    pub fn set_probabilities(&mut self, probabilities: Probabilities) {
+        self.probabilities = probabilities;
+    }
+
+    /// Sets how points are colored. [`ColorMode::Map`] and [`ColorMode::Age`] apply to points
+    /// sampled afterwards, [`ColorMode::Density`] to all points once they are drawn.
+    pub fn set_color_mode(&mut self, color_mode: ColorMode) {
+        self.color_mode = color_mode;
+    }
+
+    /// Splits sampling between `threads` threads, each running its own chaos game with a
+    /// generator seeded from the program's. Seeded programs still produce the same points on
+    /// every run, as long as the number of threads stays the same. Defaults to 1.
+    pub fn set_threads(&mut self, threads: usize) {
+        self.threads = threads.max(1);
+    }
+
+    /// Draws the points within `view` instead of the bounds of the points themselves, e.g. to
+    /// keep the frames of an animation aligned. Ignored once points have been normalized.
+    pub fn set_view(&mut self, view: Bounds) {
+        self.view = Some(view);
+    }
+
+    /// Bounds the points are drawn within, see [`IfsProgram::set_view`].
+    fn draw_uniforms(&self) -> MapParams {
+        match self.view {
+            Some(view) if !self.uniforms.normalized => MapParams {
+                x_min: view.x_min,
+                x_max: view.x_max,
+                y_min: view.y_min,
+                y_max: view.y_max,
+                normalized: false,
+            },
+            _ => self.uniforms,
+        }
+    }
+
+    /// Normalizes points against `bounds` instead of the bounds of the points sampled so far,
+    /// e.g. the ones from [`MapSystem::attractor_bounds`]. Points outside of `bounds` still
+    /// grow them. Only affects points sampled afterwards.
+    pub fn set_bounds(&mut self, bounds: Bounds) {
+        self.uniforms = MapParams {
+            x_min: bounds.x_min,
+            x_max: bounds.x_max,
+            y_min: bounds.y_min,
+            y_max: bounds.y_max,
+            normalized: self.uniforms.normalized,
+        };
+    }
+
+    pub fn normalize_points(&mut self) {
+        self.normalize_points_to_ranges(-1.0, 1.0, -1.0, 1.0);
+    }
+
+    pub fn normalize_points_to_ranges(&mut self, x_min: f32, x_max: f32, y_min: f32, y_max: f32) {
+        let normalized = self.vertices.drain(..).map(|v| ColoredVertex {
+            position: [
+                map_f32(
+                    v.position[0],
+                    self.uniforms.x_min,
+                    self.uniforms.x_max,
+                    x_min,
+                    x_max,
+                ),
+                map_f32(
+                    v.position[1],
+                    self.uniforms.y_min,
+                    self.uniforms.y_max,
+                    y_min,
+                    y_max,
+                ),
+            ],
+            ..v
+        });
+
+        self.normalized_vertices.extend(normalized.into_iter());
+
+        self.uniforms = MapParams {
+            normalized: true,
+            ..MapParams::default()
+        };
+    }
+
+    /// Samples the attractor of the IFS `d` with the chaos game.
+    ///
+    /// Panics if `d` is not a valid IFS, see [`Ifs::new`].
+    pub fn sample_affine(
+        &mut self,
+        d: &Array<f32, Ix2>,
+        color: [f32; 4],
+        iters: usize,
+        scale: f32,
+        shift_x: f32,
+        shift_y: f32,
+    ) {
+        let mut rng = self.rng.clone();
+        self.sample_affine_with_rng(&mut rng, d, color, iters, scale, shift_x, shift_y);
+        self.rng = rng;
+    }
+
+    /// Same as [`IfsProgram::sample_affine`], but draws random numbers from `rng` instead of the
+    /// program's own generator.
+    #[allow(clippy::too_many_arguments)]
+    pub fn sample_affine_with_rng<R: Rng + ?Sized>(
+        &mut self,
+        rng: &mut R,
+        d: &Array<f32, Ix2>,
+        color: [f32; 4],
+        iters: usize,
+        scale: f32,
+        shift_x: f32,
+        shift_y: f32,
+    ) {
+        let ifs = Ifs::with_probabilities(d.clone(), self.probabilities)
+            .unwrap_or_else(|e| panic!("invalid IFS: {}", e));
+        self.sample_ifs_with_rng(rng, &ifs, color, iters, scale, shift_x, shift_y);
+    }
+
+    /// Samples the attractor of an IFS that has already been validated, so unlike
+    /// [`IfsProgram::sample_affine`] this cannot panic. Also samples recurrent IFS, see
+    /// [`recurrent`].
+    pub fn sample_ifs<S: MapSystem>(
+        &mut self,
+        ifs: &S,
+        color: [f32; 4],
+        iters: usize,
+        scale: f32,
+        shift_x: f32,
+        shift_y: f32,
+    ) {
+        let mut rng = self.rng.clone();
+        self.sample_ifs_with_rng(&mut rng, ifs, color, iters, scale, shift_x, shift_y);
+        self.rng = rng;
+    }
+
+    /// Same as [`IfsProgram::sample_ifs`], but draws random numbers from `rng` instead of the
+    /// program's own generator.
+    #[allow(clippy::too_many_arguments)]
+    pub fn sample_ifs_with_rng<R: Rng + ?Sized, S: MapSystem>(
+        &mut self,
+        rng: &mut R,
+        ifs: &S,
+        color: [f32; 4],
+        iters: usize,
+        scale: f32,
+        shift_x: f32,
+        shift_y: f32,
+    ) {
+        let color_mode = self.color_mode;
+        let transform = |x: f32, y: f32| ((x + shift_x) * scale, (y + shift_y) * scale);
+
+        if self.threads > 1 {
+            let chunks = run_worker_ranges(rng, self.threads, iters as u64, |rng, range| {
+                let range = range.start as usize..range.end as usize;
+                let mut points = Vec::with_capacity(range.len());
+                chaos_game(rng, ifs, color, color_mode, range, iters, |x, y, color| {
+                    points.push((x, y, color))
+                });
+                points
+            });
+            for (x, y, color) in chunks.into_iter().flatten() {
+                let (x, y) = transform(x, y);
+                self.push_point(x, y, color);
+            }
+        } else {
+            chaos_game(
+                rng,
+                ifs,
+                color,
+                color_mode,
+                0..iters,
+                iters,
+                |x, y, color| {
+                    let (x, y) = transform(x, y);
+                    self.push_point(x, y, color);
+                },
+            );
+        }
+    }
+
+    /// Adds points computed elsewhere, e.g. by the deterministic algorithm in
+    /// [`deterministic`], so they can be normalized, rendered and viewed like sampled ones.
+    pub fn add_points(&mut self, points: &[[f32; 2]], color: [f32; 4]) {
+        for &[x, y] in points {
+            self.push_point(x, y, color);
+        }
+    }
+
+    fn push_point(&mut self, x: f32, y: f32, color: [f32; 4]) {
+        self.uniforms.x_min = self.uniforms.x_min.min(x);
+        self.uniforms.x_max = self.uniforms.x_max.max(x);
+        self.uniforms.y_min = self.uniforms.y_min.min(y);
+        self.uniforms.y_max = self.uniforms.y_max.max(y);
+
+        self.vertices.push(ColoredVertex {
+            position: [x, y],
+            color,
+        })
+    }
+
+    pub fn sample(&mut self, d: &Array<f32, Ix2>, color: [f32; 4], iters: usize) {
+        self.sample_affine(d, color, iters, 1.0, 0.0, 0.0);
+    }
+
+    /// Positions of all sampled points. If any points were normalized, all of them are returned
+    /// in normalized coordinates.
+    pub fn points(&mut self) -> Vec<[f32; 2]> {
+        self.finish_positions().iter().map(|v| v.position).collect()
+    }
+
+    /// Returns the points to draw. If any points were normalized, the remaining ones are
+    /// normalized too so that everything ends up in the same coordinate space.
+    fn finish_positions(&mut self) -> &mut [ColoredVertex] {
+        if self.uniforms.normalized {
+            // Normalize any remaining points
+            self.normalize_points();
+            assert!(self.vertices.is_empty());
+            &mut self.normalized_vertices
+        } else {
+            assert!(self.normalized_vertices.is_empty());
+            &mut self.vertices
+        }
+    }
+
+    /// Same as [`IfsProgram::finish_positions`], and also colors the points by density if
+    /// needed, for drawing them in a `width` x `height` image.
+    fn finish_vertices(&mut self, width: u32, height: u32) -> &[ColoredVertex] {
+        self.finish_positions();
+
+        let uniforms = self.draw_uniforms();
+        let vertices = if uniforms.normalized {
+            &mut self.normalized_vertices
+        } else {
+            &mut self.vertices
+        };
+        if let ColorMode::Density { palette } = self.color_mode {
+            color_by_density(vertices, |p| uniforms.ndc(p), width, height, palette);
+        }
+        vertices
+    }
+
+    /// Renders the sampled points on the CPU, matching what [`IfsProgram::run`] would show in a
+    /// `width` x `height` window.
+    pub fn render(&mut self, width: u32, height: u32, point_size: Option<f32>) -> Image {
+        let (r, g, b, a) = self.clear_color;
+        let mut image = Image::new(width, height, [r, g, b, a]);
+        let point_size = point_size.unwrap_or(1.0);
+
+        let uniforms = self.draw_uniforms();
+        for v in self.finish_vertices(width, height) {
+            let (x, y) = uniforms.ndc(v.position);
+            let (px, py) = image.ndc_to_pixel(x, y);
+            image.draw_point(px, py, point_size, to_rgba8(v.color));
+        }
+
+        image
+    }
+
+    /// Same as [`IfsProgram::render`], but produces a vector image. See [`Svg::add_points`] for
+    /// `merge`.
+    pub fn render_svg(
+        &mut self,
+        width: u32,
+        height: u32,
+        point_size: Option<f32>,
+        merge: bool,
+    ) -> Svg {
+        let (r, g, b, a) = self.clear_color;
+        let mut svg = Svg::new(width, height);
+        svg.set_background([r, g, b, a]);
+
+        let uniforms = self.draw_uniforms();
+        let points = self.finish_vertices(width, height).iter().map(|v| {
+            let (x, y) = uniforms.ndc(v.position);
+            ([x, y], v.color)
+        });
+        svg.add_points(points, point_size.unwrap_or(1.0), merge);
+
+        svg
+    }
+
+    pub fn run(&mut self, point_size: Option<f32>) {
+        let event_loop = EventLoop::new();
+
+        let wb = WindowBuilder::new()
+            .with_inner_size(LogicalSize::new(768.0_f32, 768.0_f32))
+            .with_title("IFS viewer");
+
+        let cb = ContextBuilder::new();
+
+        let display = Display::new(wb, cb, &event_loop).unwrap();
+
+        let (width, height) = display.get_framebuffer_dimensions();
+        let vertex_buffer =
+            VertexBuffer::new(&display, self.finish_vertices(width, height)).unwrap();
+
+        let indices = NoIndices(PrimitiveType::Points);
+
+        let program = point_program(&display);
+
+        let uniforms = self.draw_uniforms();
+        let clear_color = self.clear_color;
+        event_loop.run(move |ev, _, control_flow| {
+            *control_flow = Wait;
+
+            match ev {
+                Event::WindowEvent { event, .. } => match event {
+                    WindowEvent::CloseRequested => {
+                        *control_flow = ControlFlow::Exit;
+                        return;
+                    }
+                    _ => return,
+                },
+                _ => (),
+            }
+
+            let mut target = display.draw();
+            target.clear(None, Some(clear_color), false, None, None);
+
+            let mut p = DrawParameters::default();
+            p.point_size = point_size;
+            target
+                .draw(&vertex_buffer, indices, &program, &uniforms, &p)
+                .unwrap();
+            target.finish().unwrap();
+        });
+    }
+}
+
+impl Uniforms for MapParams {
+    fn visit_values<'a, F: FnMut(&str, UniformValue<'a>)>(&'a self, mut f: F) {
+        f("x_max", UniformValue::Float(self.x_max));
+        f("y_min", UniformValue::Float(self.y_min));
+        f("x_min", UniformValue::Float(self.x_min));
+        f("y_max", UniformValue::Float(self.y_max));
+        f("normalized", UniformValue::Bool(self.normalized));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ndarray::array;
+
+    use crate::args::ColorScheme;
+    use crate::ifs::{
+        apply_map, effective_map, fixed_point, sequential_map, ColorMode, ColoredVertex, IfsProgram,
+    };
+    use crate::image::test::assert_golden;
+    use crate::utils::rng::seeded_rng;
+
+    #[test]
+    fn seeded_sampling_is_reproducible() {
+        let d = array![
+            [0.5, 0.0, 0.0, 0.5, -0.5, -0.5, 0.33],
+            [0.5, 0.0, 0.0, 0.5, 0.0, 0.5, 0.33],
+            [0.5, 0.0, 0.0, 0.5, 0.5, -0.5, 0.33]
+        ];
+
+        let sample = |seed| {
+            let mut program = IfsProgram::with_rng(seeded_rng(Some(seed)));
+            program.sample(&d, [0.0, 0.0, 0.0, 1.0], 1000);
+            program.sample(&d, [1.0, 0.0, 0.0, 1.0], 1000);
+            program.vertices
+        };
+
+        assert_eq!(sample(7), sample(7));
+        assert_ne!(sample(7), sample(8));
+    }
+
+    #[test]
+    fn threaded_sampling_is_reproducible() {
+        let d = array![
+            [0.5, 0.0, 0.0, 0.5, -0.5, -0.5, 0.33],
+            [0.5, 0.0, 0.0, 0.5, 0.0, 0.5, 0.33],
+            [0.5, 0.0, 0.0, 0.5, 0.5, -0.5, 0.33]
+        ];
+
+        let sample = |threads| {
+            let mut program = IfsProgram::with_rng(seeded_rng(Some(7)));
+            program.set_threads(threads);
+            program.sample(&d, [0.0, 0.0, 0.0, 1.0], 1000);
+            program.vertices
+        };
+
+        let threaded = sample(4);
+        // Every thread skips its own first few points
+        assert_eq!(threaded.len(), 1000 - 4 * 10);
+        assert_eq!(threaded, sample(4));
+        assert_ne!(threaded, sample(3));
+    }
+
+    #[test]
+    fn render_maps_bounds_to_corners() {
+        let mut program = IfsProgram::default();
+        program.set_clear_color((0.0, 0.0, 0.0, 1.0));
+
+        for (x, y) in [(-3.0, -1.0), (5.0, 7.0)] {
+            program.uniforms.x_min = program.uniforms.x_min.min(x);
+            program.uniforms.x_max = program.uniforms.x_max.max(x);
+            program.uniforms.y_min = program.uniforms.y_min.min(y);
+            program.uniforms.y_max = program.uniforms.y_max.max(y);
+            program.vertices.push(ColoredVertex {
+                position: [x, y],
+                color: [1.0, 0.0, 0.0, 1.0],
+            });
+        }
+
+        let image = program.render(10, 10, Some(2.0));
+        let red = [255, 0, 0, 255];
+        let black = [0, 0, 0, 255];
+
+        // The minimum ends up in the bottom left corner, the maximum in the top right
+        assert_eq!(image.pixel(0, 9), red);
+        assert_eq!(image.pixel(9, 0), red);
+        assert_eq!(image.pixel(1, 8), black);
+        assert_eq!(image.pixel(5, 5), black);
+    }
+
+    #[test]
+    fn render_normalized() {
+        let mut program = IfsProgram::default();
+        program.normalized_vertices.push(ColoredVertex {
+            position: [0.1, -0.1],
+            color: [0.0, 0.0, 1.0, 1.0],
+        });
+        program.uniforms.normalized = true;
+
+        let image = program.render(4, 4, None);
+        assert_eq!(image.pixel(2, 2), [0, 0, 255, 255]);
+        assert_eq!(image.pixel(1, 1), [255, 255, 255, 255]);
+    }
+
+    #[test]
+    fn render_sierpinski() {
+        let d = array![
+            [0.5, 0.0, 0.0, 0.5, -0.5, -0.5, 0.33],
+            [0.5, 0.0, 0.0, 0.5, 0.0, 0.5, 0.33],
+            [0.5, 0.0, 0.0, 0.5, 0.5, -0.5, 0.33]
+        ];
+        let mut program = IfsProgram::with_rng(seeded_rng(Some(1)));
+        program.set_clear_color((0.0, 0.0, 0.0, 1.0));
+        program.sample(&d, [1.0, 1.0, 1.0, 1.0], 5000);
+
+        assert_golden(&program.render(64, 64, None), "sierpinski");
+    }
+
+    #[test]
+    fn color_by_map() {
+        // Two maps sending everything to the left or the right half of the interval
+        let d = array![
+            [0.5, 0.0, 0.0, 0.5, 0.0, 0.0, 0.5],
+            [0.5, 0.0, 0.0, 0.5, 0.5, 0.0, 0.5],
+        ];
+        let palette = ColorScheme::Viridis;
+        let mut program = IfsProgram::with_rng(seeded_rng(Some(1)));
+        program.set_color_mode(ColorMode::Map {
+            history: 1,
+            palette,
+        });
+        program.sample(&d, [0.0, 0.0, 0.0, 1.0], 1000);
+
+        let [r, g, b] = palette.color(0.25);
+        let left = [r, g, b, 1.0];
+        let [r, g, b] = palette.color(0.75);
+        let right = [r, g, b, 1.0];
+        for v in &program.vertices {
+            assert_eq!(v.color, if v.position[0] < 0.5 { left } else { right });
+        }
+    }
+
+    #[test]
+    fn color_by_age_across_threads() {
+        let d = array![
+            [0.5, 0.0, 0.0, 0.5, 0.0, 0.0, 0.5],
+            [0.5, 0.0, 0.0, 0.5, 0.5, 0.0, 0.5],
+        ];
+        let palette = ColorScheme::Viridis;
+        let mut program = IfsProgram::with_rng(seeded_rng(Some(1)));
+        program.set_threads(4);
+        program.set_color_mode(ColorMode::Age { palette });
+        program.sample(&d, [0.0, 0.0, 0.0, 1.0], 1000);
+
+        // One gradient over the whole run, not one per thread
+        let expected: Vec<[f32; 4]> = (0..4)
+            .flat_map(|thread| thread * 250 + 10..(thread + 1) * 250)
+            .map(|i| {
+                let [r, g, b] = palette.color(i as f32 / 1000.0);
+                [r, g, b, 1.0]
+            })
+            .collect();
+        let colors: Vec<[f32; 4]> = program.vertices.iter().map(|v| v.color).collect();
+        assert_eq!(colors, expected);
+    }
+
+    #[test]
+    fn color_by_density() {
+        let mut program = IfsProgram::default();
+        program.set_color_mode(ColorMode::Density {
+            palette: ColorScheme::Inferno,
+        });
+        program.add_points(&[[0.0, 0.0], [0.0, 0.0], [1.0, 1.0]], [0.0, 0.0, 0.0, 1.0]);
+
+        let colors: Vec<[f32; 4]> = program
+            .finish_vertices(4, 4)
+            .iter()
+            .map(|v| v.color)
+            .collect();
+        let [r, g, b] = ColorScheme::Inferno.color(1.0);
+        assert_eq!(colors[0], [r, g, b, 1.0]);
+        assert_eq!(colors[0], colors[1]);
+        assert_ne!(colors[0], colors[2]);
+    }
+
+    #[test]
+    fn sequential_map_round_trip() {
+        let row = array![0.2, -0.26, 0.23, 0.22, 0.0, 1.6];
+        let coefs = sequential_map(effective_map(row.view())).unwrap();
+        for (a, b) in coefs.iter().zip(row.iter()) {
+            assert!((a - b).abs() < 1e-6, "{:?} != {}", coefs, row);
+        }
+
+        assert_eq!(
+            sequential_map([0.0, 0.5, 0.0, 0.5, 1.0, 2.0]),
+            Some([0.0, 0.5, 0.0, 0.5, 1.0, 2.0])
+        );
+        assert_eq!(sequential_map([0.0, 0.5, 0.5, 0.0, 0.0, 0.0]), None);
+    }
+
+    #[test]
+    fn fixed_points() {
+        let fern = array![
+            [0.0, 0.0, 0.0, 0.16, 0.0, 0.0],
+            [0.85, 0.04, -0.04, 0.85, 0.0, 1.6],
+            [0.2, -0.26, 0.23, 0.22, 0.0, 1.6],
+            [-0.15, 0.28, 0.26, 0.24, 0.0, 0.44],
+        ];
+        for row in fern.rows() {
+            let [x, y] = fixed_point(effective_map(row)).unwrap();
+            let (fx, fy) = apply_map(row, x, y);
+            assert!((fx - x).abs() < 1e-5 && (fy - y).abs() < 1e-5, "{}", row);
+        }
+
+        assert_eq!(
+            fixed_point([0.5, 0.0, 0.0, 0.5, 1.0, 0.0]),
+            Some([2.0, 0.0])
+        );
+        // Translations move every point
+        assert_eq!(fixed_point([1.0, 0.0, 0.0, 1.0, 1.0, 0.0]), None);
+    }
+}
@@ -0,0 +1,331 @@
+//! Morphing between IFS by interpolating their maps.
+//!
+//! The attractor depends continuously on the maps, so a morph between two definitions shows how
+//! one shape turns into another. Maps are paired up by their order in the definitions. If one
+//! definition has fewer maps, some of its maps are repeated with their probability split between
+//! the copies, which changes neither its attractor nor how the points are spread over it.
+//!
+//! Transition matrices of recurrent layers are interpolated as well. A layer without one counts
+//! as choosing every map with its probability regardless of the previous one.
+
+use std::f32::consts::PI;
+
+use ndarray::{Array, Ix2};
+use rand::Rng;
+
+use crate::ifs::density::estimate_bounds;
+use crate::ifs::format::IfsLayer;
+use crate::ifs::{effective_map, nearest_sequential_map, Bounds};
+
+/// How the coefficients of two maps are blended.
+#[derive(Copy, Clone, Debug, PartialEq, clap::ValueEnum, strum_macros::Display)]
+#[strum(serialize_all = "snake_case")]
+pub enum Interpolation {
+    /// Every coefficient on its own. Halfway between two rotations is a map that also shrinks.
+    Linear,
+    /// The rotation angle and the remaining scale and shear from [`polar_decomposition`]
+    /// separately, so rotations stay rotations all the way.
+    Polar,
+}
+
+/// Splits the linear part `[[a, b], [c, d]]` of a map into a rotation by an angle, followed by
+/// a symmetric matrix `[[s11, s12], [s12, s22]]` that scales and shears. Returns the angle and
+/// `[s11, s12, s22]`.
+///
+/// For maps that preserve orientation this is the polar decomposition, with the symmetric matrix
+/// positive definite. Maps that mirror the plane get a symmetric matrix with a negative
+/// eigenvalue instead of a reflection, so that every map has a rotation to interpolate.
+pub fn polar_decomposition([a, b, c, d]: [f32; 4]) -> (f32, [f32; 3]) {
+    let angle = (c - b).atan2(a + d);
+    let (sin, cos) = angle.sin_cos();
+    // Rotate back: S = R(-angle) M
+    let s11 = cos * a + sin * c;
+    let s12 = cos * b + sin * d;
+    let s22 = cos * d - sin * b;
+    (angle, [s11, s12, s22])
+}
+
+/// The inverse of [`polar_decomposition`].
+pub fn polar_composition(angle: f32, [s11, s12, s22]: [f32; 3]) -> [f32; 4] {
+    let (sin, cos) = angle.sin_cos();
+    [
+        cos * s11 - sin * s12,
+        cos * s12 - sin * s22,
+        sin * s11 + cos * s12,
+        sin * s12 + cos * s22,
+    ]
+}
+
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    a + (b - a) * t
+}
+
+/// Conventional coefficients and probability of each of `n` maps, repeating maps of `d` if it
+/// has fewer.
+fn padded_maps(d: &Array<f32, Ix2>, n: usize) -> Vec<([f32; 6], f32)> {
+    let m = d.nrows();
+    (0..n)
+        .map(|k| {
+            let row = d.row(k % m);
+            // Number of times the same map is used
+            let copies = (n - k % m).div_ceil(m);
+            (effective_map(row), row[6] / copies as f32)
+        })
+        .collect()
+}
+
+/// Transition matrix between `n` maps, repeating maps of `layer` like [`padded_maps`] does. Rows
+/// are normalized to sum to 1, so that they weigh the same when blended.
+fn padded_transitions(layer: &IfsLayer, n: usize) -> Array<f32, Ix2> {
+    let m = layer.maps.nrows();
+    let mut ret = Array::from_shape_fn((n, n), |(i, j)| {
+        let weight = match &layer.transitions {
+            Some(transitions) => transitions[(i % m, j % m)],
+            None => layer.maps[(j % m, 6)],
+        };
+        weight / (n - j % m).div_ceil(m) as f32
+    });
+    for mut row in ret.rows_mut() {
+        let sum = row.sum();
+        row /= sum;
+    }
+    ret
+}
+
+/// Blends two maps in conventional form, `t` = 0 giving `a` and 1 giving `b`.
+pub fn interpolate_map(a: [f32; 6], b: [f32; 6], t: f32, interpolation: Interpolation) -> [f32; 6] {
+    match interpolation {
+        Interpolation::Linear => std::array::from_fn(|i| lerp(a[i], b[i], t)),
+        Interpolation::Polar => {
+            let (angle_a, s_a) = polar_decomposition([a[0], a[1], a[2], a[3]]);
+            let (angle_b, s_b) = polar_decomposition([b[0], b[1], b[2], b[3]]);
+            // Turn the shorter way around
+            let mut turn = (angle_b - angle_a) % (2.0 * PI);
+            if turn > PI {
+                turn -= 2.0 * PI;
+            } else if turn < -PI {
+                turn += 2.0 * PI;
+            }
+
+            let s = std::array::from_fn(|i| lerp(s_a[i], s_b[i], t));
+            let [a11, a12, a21, a22] = polar_composition(angle_a + turn * t, s);
+            [a11, a12, a21, a22, lerp(a[4], b[4], t), lerp(a[5], b[5], t)]
+        }
+    }
+}
+
+/// Interpolates between the layers of a sequence of keyframes.
+#[derive(Clone, Debug)]
+pub struct Morph {
+    keyframes: Vec<IfsLayer>,
+    interpolation: Interpolation,
+}
+
+impl Morph {
+    /// Needs at least one keyframe.
+    pub fn new(keyframes: Vec<IfsLayer>, interpolation: Interpolation) -> Self {
+        assert!(!keyframes.is_empty(), "a morph needs at least one keyframe");
+        Self {
+            keyframes,
+            interpolation,
+        }
+    }
+
+    /// The layer at time `t`, from 0 at the first keyframe to 1 at the last one. Every pair of
+    /// neighboring keyframes takes the same time.
+    pub fn at(&self, t: f32) -> IfsLayer {
+        let segments = self.keyframes.len() - 1;
+        if segments == 0 {
+            return self.keyframes[0].clone();
+        }
+
+        let t = t.clamp(0.0, 1.0) * segments as f32;
+        let i = (t as usize).min(segments - 1);
+
+        let (from, to) = (&self.keyframes[i], &self.keyframes[i + 1]);
+        let t = t - i as f32;
+        let n = from.maps.nrows().max(to.maps.nrows());
+
+        let mut maps = Array::zeros((n, 7));
+        let pairs = padded_maps(&from.maps, n)
+            .into_iter()
+            .zip(padded_maps(&to.maps, n));
+        for (mut row, ((a, pa), (b, pb))) in maps.rows_mut().into_iter().zip(pairs) {
+            let coefs = nearest_sequential_map(interpolate_map(a, b, t, self.interpolation));
+            row.as_slice_mut().unwrap()[..6].copy_from_slice(&coefs);
+            row[6] = lerp(pa, pb, t);
+        }
+
+        let transitions = (from.transitions.is_some() || to.transitions.is_some()).then(|| {
+            let a = padded_transitions(from, n);
+            let b = padded_transitions(to, n);
+            &a + &(&b - &a) * t
+        });
+
+        IfsLayer {
+            maps,
+            transitions,
+            color: std::array::from_fn(|c| lerp(from.color[c], to.color[c], t)),
+            iterations: lerp(from.iterations as f32, to.iterations as f32, t).round() as usize,
+            scale: lerp(from.scale, to.scale, t),
+            shift_x: lerp(from.shift_x, to.shift_x, t),
+            shift_y: lerp(from.shift_y, to.shift_y, t),
+            ..from.clone()
+        }
+    }
+
+    /// Time of frame `frame` out of `frames`, so that the first and last frames show the first
+    /// and last keyframes.
+    pub fn frame_time(frame: usize, frames: usize) -> f32 {
+        if frames > 1 {
+            frame as f32 / (frames - 1) as f32
+        } else {
+            0.0
+        }
+    }
+
+    /// Bounds containing nearly all of every frame, from a short run of the chaos game per
+    /// frame. Drawing every frame within the same bounds keeps the animation from jumping around.
+    pub fn bounds<R: Rng + ?Sized>(&self, rng: &mut R, frames: usize, samples: usize) -> Bounds {
+        (0..frames.max(1))
+            .map(|frame| {
+                let layer = self.at(Self::frame_time(frame, frames));
+                let b = estimate_bounds(&layer.system(), rng, samples);
+                let (scale, dx, dy) = (layer.scale, layer.shift_x, layer.shift_y);
+                Bounds {
+                    x_min: (b.x_min + dx) * scale,
+                    x_max: (b.x_max + dx) * scale,
+                    y_min: (b.y_min + dy) * scale,
+                    y_max: (b.y_max + dy) * scale,
+                }
+            })
+            .reduce(|a, b| Bounds {
+                x_min: a.x_min.min(b.x_min),
+                x_max: a.x_max.max(b.x_max),
+                y_min: a.y_min.min(b.y_min),
+                y_max: a.y_max.max(b.y_max),
+            })
+            .unwrap()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::f32::consts::FRAC_PI_2;
+
+    use ndarray::array;
+
+    use crate::ifs::format::IfsLayer;
+    use crate::ifs::morph::{
+        interpolate_map, polar_composition, polar_decomposition, Interpolation, Morph,
+    };
+
+    fn assert_close(a: &[f32], b: &[f32]) {
+        assert!(
+            a.iter().zip(b).all(|(a, b)| (a - b).abs() < 1e-5),
+            "{:?} != {:?}",
+            a,
+            b
+        );
+    }
+
+    #[test]
+    fn polar() {
+        for m in [
+            [0.85, 0.04, -0.04, 0.85],
+            [0.2, -0.26, 0.23, 0.22],
+            [-0.5, 0.0, 0.0, 0.5],
+            [0.0, 0.0, 0.0, 0.16],
+        ] {
+            let (angle, s) = polar_decomposition(m);
+            assert_close(&polar_composition(angle, s), &m);
+            // Otherwise a half turn would have been left in the symmetric factor
+            assert!(s[0] + s[2] >= 0.0);
+        }
+
+        // A scaled rotation is all rotation
+        let (angle, s) = polar_decomposition([0.0, -0.5, 0.5, 0.0]);
+        assert!((angle - FRAC_PI_2).abs() < 1e-6);
+        assert_close(&s, &[0.5, 0.0, 0.5]);
+    }
+
+    #[test]
+    fn halfway_between_rotations() {
+        let a = [0.5, 0.0, 0.0, 0.5, 0.0, 0.0];
+        let b = [0.0, -0.5, 0.5, 0.0, 1.0, 0.0];
+
+        let linear = interpolate_map(a, b, 0.5, Interpolation::Linear);
+        assert_close(&linear, &[0.25, -0.25, 0.25, 0.25, 0.5, 0.0]);
+
+        // Rotated by 45 degrees without shrinking
+        let polar = interpolate_map(a, b, 0.5, Interpolation::Polar);
+        let h = 0.5 * std::f32::consts::FRAC_1_SQRT_2;
+        assert_close(&polar, &[h, -h, h, h, 0.5, 0.0]);
+    }
+
+    #[test]
+    fn keyframes() {
+        let triangle = IfsLayer {
+            maps: array![
+                [0.5, 0.0, 0.0, 0.5, 0.0, 0.0, 0.3],
+                [0.5, 0.0, 0.0, 0.5, 0.5, 0.0, 0.3],
+                [0.5, 0.0, 0.0, 0.5, 0.0, 0.5, 0.4],
+            ],
+            ..IfsLayer::default()
+        };
+        let fern = IfsLayer {
+            maps: array![
+                [0.0, 0.0, 0.0, 0.16, 0.0, 0.0, 0.01],
+                [0.85, 0.04, -0.04, 0.85, 0.0, 1.6, 0.85],
+                [0.2, -0.26, 0.23, 0.22, 0.0, 1.6, 0.07],
+                [-0.15, 0.28, 0.26, 0.24, 0.0, 0.44, 0.07],
+            ],
+            color: [0.0, 1.0, 0.0, 1.0],
+            ..IfsLayer::default()
+        };
+        let morph = Morph::new(vec![triangle, fern.clone()], Interpolation::Polar);
+
+        // The first map of the triangle is used twice, with half the probability each
+        let start = morph.at(0.0);
+        assert_eq!(start.maps.nrows(), 4);
+        assert_close(&start.maps.column(6).to_vec(), &[0.15, 0.3, 0.4, 0.15]);
+        assert_close(
+            &start.maps.row(3).to_vec()[..6],
+            &[0.5, 0.0, 0.0, 0.5, 0.0, 0.0],
+        );
+
+        let end = morph.at(1.0);
+        assert_close(end.maps.as_slice().unwrap(), fern.maps.as_slice().unwrap());
+        assert_eq!(end.color, fern.color);
+
+        assert_eq!(morph.at(0.5).color, [0.0, 0.5, 0.0, 1.0]);
+        assert_eq!(Morph::frame_time(9, 10), 1.0);
+        assert_eq!(end.transitions, None);
+    }
+
+    #[test]
+    fn transitions() {
+        let triangle = IfsLayer {
+            maps: array![
+                [0.5, 0.0, 0.0, 0.5, 0.0, 0.0, 1.0],
+                [0.5, 0.0, 0.0, 0.5, 0.5, 0.0, 1.0],
+            ],
+            transitions: Some(array![[0.0, 2.0], [1.0, 1.0]]),
+            ..IfsLayer::default()
+        };
+        let square = IfsLayer {
+            maps: array![
+                [0.5, 0.0, 0.0, 0.5, 0.0, 0.0, 1.0],
+                [0.5, 0.0, 0.0, 0.5, 0.5, 0.0, 3.0],
+            ],
+            ..IfsLayer::default()
+        };
+        let morph = Morph::new(vec![triangle, square], Interpolation::Linear);
+
+        let start = morph.at(0.0).transitions.unwrap();
+        assert_close(start.as_slice().unwrap(), &[0.0, 1.0, 0.5, 0.5]);
+        // Without transitions the rows are the probabilities
+        let end = morph.at(1.0).transitions.unwrap();
+        assert_close(end.as_slice().unwrap(), &[0.25, 0.75, 0.25, 0.75]);
+    }
+}
@@ -0,0 +1,345 @@
+//! Viewing an attractor while it is being sampled.
+//!
+//! [`IfsProgram::run`] only opens its window once every point has been sampled. A
+//! [`ProgressiveSampler`] instead samples in batches, which the viewer in
+//! [`ProgressiveSampler::run`] streams to the GPU one frame at a time. Sampling stops once a point
+//! budget is used up, or once a batch lights up hardly any pixels that were not lit before.
+
+use glium::glutin::dpi::LogicalSize;
+use glium::glutin::event::{Event, WindowEvent};
+use glium::glutin::event_loop::{ControlFlow, EventLoop};
+use glium::glutin::window::WindowBuilder;
+use glium::glutin::ContextBuilder;
+use glium::index::{NoIndices, PrimitiveType};
+use glium::{Display, DrawParameters, Surface, VertexBuffer};
+
+use crate::ifs::system::{Ifs, MapSystem};
+use crate::ifs::{point_program, Bounds, ColoredVertex, IfsProgram};
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Progressive {
+    /// Chaos game iterations per batch.
+    pub batch: usize,
+    /// Stop after this many iterations in total. Every batch skips its first few points, so
+    /// slightly fewer points are drawn.
+    pub budget: usize,
+    /// Stop once the fraction of points in a batch that land on a pixel no earlier point has
+    /// lit drops below this.
+    pub threshold: f32,
+}
+
+impl Default for Progressive {
+    fn default() -> Self {
+        Self {
+            batch: 50_000,
+            budget: 5_000_000,
+            threshold: 1e-4,
+        }
+    }
+}
+
+/// Pixels lit by at least one point, within the bounds of the attractor if they are known and
+/// bounds fixed by the first batch otherwise.
+struct Coverage {
+    bounds: Bounds,
+    width: u32,
+    height: u32,
+    lit: Vec<bool>,
+}
+
+impl Coverage {
+    fn new(bounds: Option<Bounds>, points: &[ColoredVertex], width: u32, height: u32) -> Self {
+        let bounds = bounds.unwrap_or_else(|| {
+            let positions: Vec<[f32; 2]> = points.iter().map(|v| v.position).collect();
+            Bounds::from_points_trimmed(&positions, 1.0)
+                .map(|b| b.padded(0.05))
+                .unwrap_or(Bounds {
+                    x_min: -1.0,
+                    x_max: 1.0,
+                    y_min: -1.0,
+                    y_max: 1.0,
+                })
+        });
+
+        Self {
+            bounds,
+            width,
+            height,
+            lit: vec![false; (width * height) as usize],
+        }
+    }
+
+    /// Lights the pixel of a point. Returns true if it was not lit yet, which includes points
+    /// outside the bounds.
+    fn light(&mut self, [x, y]: [f32; 2]) -> bool {
+        let (px, py) = self.bounds.to_pixel(x, y, self.width, self.height);
+        if !(px >= 0.0 && py >= 0.0 && px < self.width as f32 && py < self.height as f32) {
+            return true;
+        }
+
+        let i = py as usize * self.width as usize + px as usize;
+        !std::mem::replace(&mut self.lit[i], true)
+    }
+}
+
+pub struct ProgressiveSampler<S: MapSystem = Ifs> {
+    ifs: S,
+    color: [f32; 4],
+    settings: Progressive,
+    resolution: (u32, u32),
+    coverage: Option<Coverage>,
+    iterations: usize,
+    points: usize,
+    /// Fraction of newly lit pixels in the last batch.
+    new_pixels: Option<f32>,
+    done: bool,
+}
+
+impl<S: MapSystem> ProgressiveSampler<S> {
+    /// Convergence is measured on a `width` x `height` grid, usually the size of the window.
+    pub fn new(ifs: S, color: [f32; 4], settings: Progressive, width: u32, height: u32) -> Self {
+        Self {
+            ifs,
+            color,
+            settings,
+            resolution: (width, height),
+            coverage: None,
+            iterations: 0,
+            points: 0,
+            new_pixels: None,
+            done: false,
+        }
+    }
+
+    pub fn points(&self) -> usize {
+        self.points
+    }
+
+    pub fn new_pixels(&self) -> Option<f32> {
+        self.new_pixels
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.done
+    }
+
+    /// Samples the next batch into `program` and returns the number of points it added, or 0
+    /// once sampling is done.
+    pub fn step(&mut self, program: &mut IfsProgram) -> usize {
+        if self.done {
+            return 0;
+        }
+
+        let start = program.vertices.len();
+        let iters = self
+            .settings
+            .batch
+            .min(self.settings.budget - self.iterations);
+        program.sample_ifs(&self.ifs, self.color, iters, 1.0, 0.0, 0.0);
+        let batch = &program.vertices[start..];
+
+        let (width, height) = self.resolution;
+        let ifs = &self.ifs;
+        let coverage = self.coverage.get_or_insert_with(|| {
+            let bounds = ifs.attractor_bounds().map(|b| b.padded(0.05));
+            Coverage::new(bounds, batch, width, height)
+        });
+        let new = batch.iter().filter(|v| coverage.light(v.position)).count();
+
+        self.iterations += iters;
+        self.points += batch.len();
+        let fraction = new as f32 / batch.len().max(1) as f32;
+        // The first batch lights up everything, so it cannot tell whether sampling converged
+        let converged = self.new_pixels.is_some() && fraction < self.settings.threshold;
+        self.new_pixels = Some(fraction);
+        self.done = converged || batch.is_empty() || self.iterations >= self.settings.budget;
+
+        batch.len()
+    }
+
+    /// Samples until done.
+    pub fn finish(&mut self, program: &mut IfsProgram) {
+        while self.step(program) > 0 {}
+    }
+
+    /// A one line summary of the progress, e.g. for a window title.
+    pub fn status(&self) -> String {
+        let state = if self.done { "done" } else { "sampling" };
+        match self.new_pixels {
+            Some(fraction) => format!(
+                "{} points, {:.3}% new pixels in the last batch, {}",
+                self.points,
+                fraction * 100.0,
+                state
+            ),
+            None => format!("{} points, {}", self.points, state),
+        }
+    }
+
+    /// Opens a viewer right away and adds a batch of points to it every frame until sampling is
+    /// done. The status is shown in the window title. Unless `program` has a view already, the
+    /// attractor is framed by its bounds from [`MapSystem::attractor_bounds`], so the picture
+    /// stays in place while it fills in.
+    pub fn run(mut self, mut program: IfsProgram, point_size: Option<f32>)
+    where
+        S: 'static,
+    {
+        let event_loop = EventLoop::new();
+
+        let wb = WindowBuilder::new()
+            .with_inner_size(LogicalSize::new(768.0_f32, 768.0_f32))
+            .with_title("IFS viewer");
+
+        let cb = ContextBuilder::new();
+
+        let display = Display::new(wb, cb, &event_loop).unwrap();
+
+        if program.view.is_none() {
+            if let Some(bounds) = self.ifs.attractor_bounds() {
+                program.set_view(bounds.padded(0.05));
+            }
+        }
+
+        // Room for the whole budget, plus the points that were there before. Every batch stays
+        // within what is left of the budget.
+        let capacity = program.vertices.len() + self.settings.budget;
+        let vertex_buffer: VertexBuffer<ColoredVertex> =
+            VertexBuffer::empty_dynamic(&display, capacity.max(1)).unwrap();
+        let mut uploaded = program.vertices.len();
+        if uploaded > 0 {
+            vertex_buffer
+                .slice(0..uploaded)
+                .unwrap()
+                .write(&program.vertices);
+        }
+
+        let indices = NoIndices(PrimitiveType::Points);
+        let shader = point_program(&display);
+        let (r, g, b, a) = program.clear_color;
+
+        event_loop.run(move |ev, _, control_flow| {
+            match ev {
+                Event::WindowEvent {
+                    event: WindowEvent::CloseRequested,
+                    ..
+                } => {
+                    *control_flow = ControlFlow::Exit;
+                    return;
+                }
+                Event::MainEventsCleared if !self.done => {
+                    if self.step(&mut program) > 0 {
+                        let end = program.vertices.len();
+                        vertex_buffer
+                            .slice(uploaded..end)
+                            .unwrap()
+                            .write(&program.vertices[uploaded..]);
+                        uploaded = end;
+                    }
+                    display
+                        .gl_window()
+                        .window()
+                        .set_title(&format!("IFS viewer - {}", self.status()));
+                }
+                Event::RedrawRequested(_) => (),
+                _ => return,
+            }
+
+            *control_flow = if self.done {
+                ControlFlow::Wait
+            } else {
+                ControlFlow::Poll
+            };
+
+            let mut target = display.draw();
+            target.clear(None, Some((r, g, b, a)), false, None, None);
+
+            let params = DrawParameters {
+                point_size,
+                ..Default::default()
+            };
+            target
+                .draw(
+                    vertex_buffer.slice(0..uploaded).unwrap(),
+                    indices,
+                    &shader,
+                    &program.draw_uniforms(),
+                    &params,
+                )
+                .unwrap();
+            target.finish().unwrap();
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ndarray::array;
+
+    use crate::ifs::progressive::{Progressive, ProgressiveSampler};
+    use crate::ifs::system::Ifs;
+    use crate::ifs::IfsProgram;
+    use crate::utils::rng::seeded_rng;
+
+    fn sierpinski() -> Ifs {
+        Ifs::new(array![
+            [0.5, 0.0, 0.0, 0.5, 0.0, 0.0, 1.0],
+            [0.5, 0.0, 0.0, 0.5, 0.5, 0.0, 1.0],
+            [0.5, 0.0, 0.0, 0.5, 0.0, 0.5, 1.0],
+        ])
+        .unwrap()
+    }
+
+    #[test]
+    fn stops_at_budget() {
+        let settings = Progressive {
+            batch: 1000,
+            budget: 2500,
+            threshold: 0.0,
+        };
+        let mut program = IfsProgram::with_rng(seeded_rng(Some(1)));
+        let mut sampler =
+            ProgressiveSampler::new(sierpinski(), [0.0, 0.0, 0.0, 1.0], settings, 64, 64);
+
+        let mut batches = vec![];
+        while !sampler.is_done() {
+            batches.push(sampler.step(&mut program));
+        }
+        assert_eq!(batches, vec![990, 990, 490]);
+        assert_eq!(sampler.points(), program.vertices.len());
+        assert_eq!(sampler.step(&mut program), 0);
+    }
+
+    #[test]
+    fn stops_when_converged() {
+        let settings = Progressive {
+            batch: 10_000,
+            budget: 10_000_000,
+            threshold: 0.001,
+        };
+        let mut program = IfsProgram::with_rng(seeded_rng(Some(1)));
+        let mut sampler =
+            ProgressiveSampler::new(sierpinski(), [0.0, 0.0, 0.0, 1.0], settings, 32, 32);
+        sampler.finish(&mut program);
+
+        // A 32x32 grid is covered long before the budget runs out
+        assert!(sampler.points() < 100_000, "{}", sampler.status());
+        assert!(sampler.new_pixels().unwrap() < 0.001);
+    }
+
+    #[test]
+    fn coverage_uses_attractor_bounds() {
+        let settings = Progressive {
+            batch: 1000,
+            budget: 1000,
+            threshold: 0.0,
+        };
+        let mut program = IfsProgram::with_rng(seeded_rng(Some(1)));
+        let mut sampler =
+            ProgressiveSampler::new(sierpinski(), [0.0, 0.0, 0.0, 1.0], settings, 16, 16);
+        sampler.step(&mut program);
+
+        let bounds = sampler.coverage.as_ref().unwrap().bounds;
+        assert!((bounds.x_min + 0.05).abs() < 1e-3, "{:?}", bounds);
+        assert!((bounds.x_max - 1.05).abs() < 1e-3, "{:?}", bounds);
+    }
+}
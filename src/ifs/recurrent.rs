@@ -0,0 +1,306 @@
+//! Recurrent IFS, also known as graph-directed IFS.
+//!
+//! The chaos game on an [`Ifs`] picks every map independently of the previous one. A
+//! [`RecurrentIfs`] picks the next map from a row of a transition matrix instead: entry `(i, j)`
+//! is the weight of applying map `j` right after map `i`. A weight of 0 forbids the transition,
+//! which removes every part of the attractor whose address contains it. This gives shapes that
+//! no IFS with the same maps can make.
+
+use std::fmt::{Display, Formatter};
+
+use ndarray::{Array, Ix2};
+use rand::distributions::{Distribution, WeightedIndex};
+use rand::Rng;
+
+use crate::ifs::attractor::attractor_bounds;
+use crate::ifs::system::{Ifs, MapSystem};
+use crate::ifs::Bounds;
+
+/// Reasons a transition matrix does not fit an IFS.
+#[derive(Clone, Debug, PartialEq)]
+pub enum TransitionError {
+    /// The matrix needs one row and one column per map.
+    WrongShape {
+        maps: usize,
+        rows: usize,
+        columns: usize,
+    },
+    NotFinite {
+        from: usize,
+        to: usize,
+    },
+    NegativeWeight {
+        from: usize,
+        to: usize,
+        weight: f32,
+    },
+    /// Every transition out of map `from` is forbidden, so the chaos game would get stuck after
+    /// applying it.
+    DeadEnd {
+        from: usize,
+    },
+}
+
+impl TransitionError {
+    /// Index of the row the error is about, if it is about a single row.
+    pub fn row(&self) -> Option<usize> {
+        match self {
+            TransitionError::WrongShape { .. } => None,
+            TransitionError::NotFinite { from, .. }
+            | TransitionError::NegativeWeight { from, .. }
+            | TransitionError::DeadEnd { from } => Some(*from),
+        }
+    }
+}
+
+impl Display for TransitionError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TransitionError::WrongShape {
+                maps,
+                rows,
+                columns,
+            } => write!(
+                f,
+                "transition matrix is {}x{}, expected {}x{} for {} maps",
+                rows, columns, maps, maps, maps
+            ),
+            TransitionError::NotFinite { from, to } => write!(
+                f,
+                "transition weight from map {} to map {} is not a finite number",
+                from, to
+            ),
+            TransitionError::NegativeWeight { from, to, weight } => write!(
+                f,
+                "negative transition weight {} from map {} to map {}",
+                weight, from, to
+            ),
+            TransitionError::DeadEnd { from } => {
+                write!(f, "map {} has no allowed transition to any map", from)
+            }
+        }
+    }
+}
+
+impl std::error::Error for TransitionError {}
+
+/// An [`Ifs`] whose maps are picked according to a transition matrix.
+#[derive(Clone, Debug)]
+pub struct RecurrentIfs {
+    ifs: Ifs,
+    transitions: Array<f32, Ix2>,
+    /// One distribution per row of `transitions`.
+    successors: Vec<WeightedIndex<f32>>,
+}
+
+impl RecurrentIfs {
+    /// Checks that `transitions` has one row and column per map of `ifs`, that its weights are
+    /// finite and non-negative, and that every row allows at least one transition. The rows need
+    /// not sum to 1. The probabilities of `ifs` only pick the very first map.
+    pub fn new(ifs: Ifs, transitions: Array<f32, Ix2>) -> Result<Self, TransitionError> {
+        let maps = ifs.map_count();
+        if transitions.dim() != (maps, maps) {
+            return Err(TransitionError::WrongShape {
+                maps,
+                rows: transitions.nrows(),
+                columns: transitions.ncols(),
+            });
+        }
+
+        for ((from, to), &weight) in transitions.indexed_iter() {
+            if !weight.is_finite() {
+                return Err(TransitionError::NotFinite { from, to });
+            }
+            if weight < 0.0 {
+                return Err(TransitionError::NegativeWeight { from, to, weight });
+            }
+        }
+
+        let successors = transitions
+            .rows()
+            .into_iter()
+            .enumerate()
+            .map(|(from, row)| {
+                WeightedIndex::new(row.iter()).map_err(|_| TransitionError::DeadEnd { from })
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self {
+            ifs,
+            transitions,
+            successors,
+        })
+    }
+
+    pub fn ifs(&self) -> &Ifs {
+        &self.ifs
+    }
+
+    pub fn transitions(&self) -> &Array<f32, Ix2> {
+        &self.transitions
+    }
+
+    /// Maps that no transition leads to. They are at most applied once, at the very start, so
+    /// they don't contribute to the attractor.
+    pub fn unreachable_maps(&self) -> Vec<usize> {
+        self.transitions
+            .columns()
+            .into_iter()
+            .enumerate()
+            .filter(|(_, column)| column.iter().all(|&w| w == 0.0))
+            .map(|(to, _)| to)
+            .collect()
+    }
+}
+
+impl MapSystem for RecurrentIfs {
+    fn map_count(&self) -> usize {
+        self.ifs.map_count()
+    }
+
+    fn next_map<R: Rng + ?Sized>(&self, rng: &mut R, previous: Option<usize>) -> usize {
+        match previous {
+            Some(map) => self.successors[map].sample(rng),
+            None => self.ifs.choose(rng),
+        }
+    }
+
+    fn apply(&self, map: usize, x: f32, y: f32) -> (f32, f32) {
+        self.ifs.apply(map, x, y)
+    }
+
+    /// Tighter than the bounds of the IFS alone, because forbidden transitions are left out.
+    fn attractor_bounds(&self) -> Option<Bounds> {
+        attractor_bounds(&self.ifs.effective_maps(), |from, to| {
+            self.transitions[(from, to)] > 0.0
+        })
+    }
+}
+
+/// The maps of a layer, sampled either independently or through a transition matrix.
+#[derive(Clone, Debug)]
+pub enum LayerSystem {
+    Independent(Ifs),
+    Recurrent(RecurrentIfs),
+}
+
+impl MapSystem for LayerSystem {
+    fn map_count(&self) -> usize {
+        match self {
+            LayerSystem::Independent(ifs) => ifs.map_count(),
+            LayerSystem::Recurrent(ifs) => ifs.map_count(),
+        }
+    }
+
+    fn next_map<R: Rng + ?Sized>(&self, rng: &mut R, previous: Option<usize>) -> usize {
+        match self {
+            LayerSystem::Independent(ifs) => ifs.choose(rng),
+            LayerSystem::Recurrent(ifs) => ifs.next_map(rng, previous),
+        }
+    }
+
+    fn apply(&self, map: usize, x: f32, y: f32) -> (f32, f32) {
+        match self {
+            LayerSystem::Independent(ifs) => ifs.apply(map, x, y),
+            LayerSystem::Recurrent(ifs) => ifs.apply(map, x, y),
+        }
+    }
+
+    fn attractor_bounds(&self) -> Option<Bounds> {
+        match self {
+            LayerSystem::Independent(ifs) => ifs.attractor_bounds(),
+            LayerSystem::Recurrent(ifs) => ifs.attractor_bounds(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ndarray::{array, Array};
+
+    use crate::ifs::recurrent::{RecurrentIfs, TransitionError};
+    use crate::ifs::system::{Ifs, MapSystem};
+    use crate::ifs::IfsProgram;
+    use crate::utils::rng::seeded_rng;
+
+    fn sierpinski() -> Ifs {
+        Ifs::new(array![
+            [0.5, 0.0, 0.0, 0.5, 0.0, 0.0, 1.0],
+            [0.5, 0.0, 0.0, 0.5, 0.5, 0.0, 1.0],
+            [0.5, 0.0, 0.0, 0.5, 0.0, 0.5, 1.0],
+        ])
+        .unwrap()
+    }
+
+    #[test]
+    fn validation() {
+        let err = RecurrentIfs::new(sierpinski(), Array::ones((3, 2))).unwrap_err();
+        assert_eq!(
+            err,
+            TransitionError::WrongShape {
+                maps: 3,
+                rows: 3,
+                columns: 2
+            }
+        );
+
+        let mut transitions = Array::ones((3, 3));
+        transitions[(1, 2)] = -1.0;
+        let err = RecurrentIfs::new(sierpinski(), transitions).unwrap_err();
+        assert_eq!(err.row(), Some(1));
+
+        let mut transitions = Array::ones((3, 3));
+        transitions[(0, 1)] = f32::NAN;
+        let err = RecurrentIfs::new(sierpinski(), transitions).unwrap_err();
+        assert_eq!(err, TransitionError::NotFinite { from: 0, to: 1 });
+
+        let transitions = array![[1.0, 1.0, 0.0], [0.0, 0.0, 0.0], [1.0, 1.0, 0.0]];
+        let err = RecurrentIfs::new(sierpinski(), transitions).unwrap_err();
+        assert_eq!(err, TransitionError::DeadEnd { from: 1 });
+
+        let transitions = array![[1.0, 1.0, 0.0], [1.0, 0.0, 0.0], [1.0, 1.0, 0.0]];
+        let recurrent = RecurrentIfs::new(sierpinski(), transitions).unwrap();
+        assert_eq!(recurrent.unreachable_maps(), vec![2]);
+    }
+
+    #[test]
+    fn forbidden_transitions_are_never_taken() {
+        // Map 1 never directly follows map 0
+        let transitions = array![[1.0, 0.0, 1.0], [1.0, 1.0, 1.0], [1.0, 1.0, 1.0]];
+        let recurrent = RecurrentIfs::new(sierpinski(), transitions).unwrap();
+
+        let mut rng = seeded_rng(Some(3));
+        let mut previous = None;
+        for _ in 0..10000 {
+            let map = recurrent.next_map(&mut rng, previous);
+            assert!(!(previous == Some(0) && map == 1));
+            previous = Some(map);
+        }
+    }
+
+    #[test]
+    fn sampled_through_program() {
+        let transitions = array![[1.0, 0.0, 1.0], [1.0, 1.0, 1.0], [1.0, 1.0, 1.0]];
+        let recurrent = RecurrentIfs::new(sierpinski(), transitions).unwrap();
+
+        for threads in [1, 4] {
+            let mut program = IfsProgram::with_rng(seeded_rng(Some(1)));
+            program.set_threads(threads);
+            program.sample_ifs(&recurrent, [0.0, 0.0, 0.0, 1.0], 20000, 1.0, 0.0, 0.0);
+
+            // The copy of the bottom left corner within the bottom right corner is missing, but
+            // the copy of the top corner is there
+            let points = program.points();
+            let count = |x_min: f32, y_min: f32| {
+                points
+                    .iter()
+                    .filter(|&&[x, y]| {
+                        (x_min..x_min + 0.23).contains(&x) && (y_min..y_min + 0.23).contains(&y)
+                    })
+                    .count()
+            };
+            assert_eq!(count(0.51, 0.01), 0);
+            assert!(count(0.51, 0.26) > 100);
+        }
+    }
+}
@@ -0,0 +1,605 @@
+//! Composite pictures made of several IFS layers and forests.
+//!
+//! Stacking fractals with [`IfsProgram`] directly means interleaving sampling with
+//! [`IfsProgram::normalize_points_to_ranges`] in just the right order. A [`Scene`] instead lists
+//! layers that each say where they go and in which order they are drawn:
+//!
+//! ```text
+//! point_size 1.5
+//!
+//! layer forest
+//! trees 75
+//! target -1 1 -1 0.5
+//!
+//! layer ifs
+//! z 1
+//! source redwoods
+//! ```
+//!
+//! The format follows the one in [`format`](crate::ifs::format):
+//!
+//! * `point_size <size>` and `clear_color <r> <g> <b> <a>` - as in an IFS definition.
+//! * `layer ifs|forest` - starts a new layer.
+//! * `target <x_min> <x_max> <y_min> <y_max>` - rectangle the layer is stretched to fill, in
+//!   normalized device coordinates (default `-1 1 -1 1`).
+//! * `z <n>` - layers with a higher z are drawn on top (default 0). Layers with the same z are
+//!   drawn in the order they appear.
+//! * `color <r> <g> <b> <a>` - color of the layer's points. Forests without a color mix green,
+//!   yellow and dead trees.
+//! * `iterations <n>` - chaos game iterations of an IFS layer, or of every tree of a forest.
+//!
+//! IFS layers also take the `map`, `geometric`, `probabilities`, `transition` and `transform`
+//! directives of an IFS definition, or `source <name>` to use the maps of the first layer of a
+//! bundled or saved definition. Forests take the `map`, `geometric`, `probabilities` and `source`
+//! directives for the tree they are made of, and also, see [`ForestConfig`]:
+//!
+//! * `trees <n>` - number of trees (default 75).
+//! * `palette <r> <g> <b> <a> <weight>` - a tree color and how often it is picked relative to the
+//!   others. The first one replaces the default mix of colors.
+//! * `scale <min> <max>`, `shift_x <min> <max>` and `shift_y <min> <max>` - ranges the size and
+//!   position of every tree are picked from (defaults `1 10`, `-0.5 0.5` and `-0.5 0.5`).
+//! * `haze <r> <g> <b> <a> <strength>` - tints smaller trees, which count as farther away,
+//!   toward a color.
+//! * `seed <n>` - places the trees the same way on every run.
+
+use std::fmt::{Display, Formatter};
+use std::ops::RangeInclusive;
+use std::str::FromStr;
+
+use rand::Rng;
+
+use crate::forest::{ForestConfig, Haze};
+use crate::ifs::format::{
+    parse_args, read_source, write_floats, write_layer, IfsDefinition, IfsLayer, LayerBuilder,
+    ParseError,
+};
+use crate::ifs::system::Probabilities;
+use crate::ifs::{Bounds, IfsProgram};
+
+/// The whole window in normalized device coordinates, the default target of a layer.
+const SCREEN: Bounds = Bounds {
+    x_min: -1.0,
+    x_max: 1.0,
+    y_min: -1.0,
+    y_max: 1.0,
+};
+
+/// What a [`SceneLayer`] draws.
+#[derive(Clone, Debug, PartialEq)]
+pub enum SceneElement {
+    Ifs(IfsLayer),
+    /// Randomly placed trees, see [`ForestConfig::draw`].
+    Forest(ForestConfig),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SceneLayer {
+    pub element: SceneElement,
+    /// Rectangle in normalized device coordinates the layer is stretched to fill.
+    pub target: Bounds,
+    pub z: i32,
+}
+
+impl SceneLayer {
+    pub fn new(element: SceneElement) -> Self {
+        Self {
+            element,
+            target: SCREEN,
+            z: 0,
+        }
+    }
+}
+
+/// The bundled `.scene` files by name.
+const EXAMPLES: &[(&str, &str)] = &[("redmoscl", include_str!("examples/redmoscl.scene"))];
+
+#[derive(Clone, Debug, PartialEq, Default)]
+pub struct Scene {
+    pub point_size: Option<f32>,
+    pub clear_color: Option<[f32; 4]>,
+    pub layers: Vec<SceneLayer>,
+}
+
+impl Scene {
+    /// Loads a scene from a file path, or from the bundled `.scene` files next to the IFS
+    /// examples if `name` is not a path to an existing file.
+    pub fn load(name: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let source = read_source(name, EXAMPLES)?;
+        source
+            .parse()
+            .map_err(|e| format!("{}: {}", name, e).into())
+    }
+
+    /// Layers in the order they are drawn, from the bottom up.
+    pub fn draw_order(&self) -> Vec<&SceneLayer> {
+        let mut layers: Vec<&SceneLayer> = self.layers.iter().collect();
+        layers.sort_by_key(|layer| layer.z);
+        layers
+    }
+
+    /// Samples every layer into `program` and normalizes it to its target, bottom layer first.
+    /// Points already in `program` that were not normalized end up in the first layer.
+    pub fn apply<R: Rng + ?Sized>(&self, program: &mut IfsProgram, rng: &mut R) {
+        if let Some([r, g, b, a]) = self.clear_color {
+            program.set_clear_color((r, g, b, a));
+        }
+
+        for layer in self.draw_order() {
+            match &layer.element {
+                SceneElement::Ifs(ifs) if ifs.transitions.is_some() => program.sample_ifs_with_rng(
+                    rng,
+                    &ifs.system(),
+                    ifs.color,
+                    ifs.iterations,
+                    ifs.scale,
+                    ifs.shift_x,
+                    ifs.shift_y,
+                ),
+                SceneElement::Ifs(ifs) => program.sample_affine_with_rng(
+                    rng,
+                    &ifs.maps,
+                    ifs.color,
+                    ifs.iterations,
+                    ifs.scale,
+                    ifs.shift_x,
+                    ifs.shift_y,
+                ),
+                SceneElement::Forest(forest) => forest.draw(program, rng),
+            }
+
+            let Bounds {
+                x_min,
+                x_max,
+                y_min,
+                y_max,
+            } = layer.target;
+            program.normalize_points_to_ranges(x_min, x_max, y_min, y_max);
+        }
+    }
+}
+
+/// Accumulates the directives of the scene layer currently being parsed.
+enum ElementBuilder {
+    Ifs(Box<LayerBuilder>),
+    Forest {
+        config: ForestConfig,
+        /// The tree's maps, if any were given.
+        tree: Option<Box<LayerBuilder>>,
+        /// Whether a `palette` directive replaced the default palette yet.
+        palette: bool,
+    },
+}
+
+struct SceneLayerBuilder {
+    element: ElementBuilder,
+    target: Bounds,
+    z: i32,
+}
+
+impl SceneLayerBuilder {
+    fn finish(self) -> Result<SceneLayer, ParseError> {
+        let element = match self.element {
+            ElementBuilder::Ifs(builder) => SceneElement::Ifs(builder.finish()?),
+            ElementBuilder::Forest {
+                mut config, tree, ..
+            } => {
+                if let Some(tree) = tree {
+                    config.tree = tree.finish()?.maps;
+                }
+                SceneElement::Forest(config)
+            }
+        };
+
+        Ok(SceneLayer {
+            element,
+            target: self.target,
+            z: self.z,
+        })
+    }
+}
+
+/// Handles the directives of a forest that are not about its tree.
+fn forest_directive(
+    config: &mut ForestConfig,
+    palette: &mut bool,
+    line_no: usize,
+    directive: &str,
+    args: &[&str],
+) -> Result<(), ParseError> {
+    let range = |args: &[&str]| -> Result<RangeInclusive<f32>, ParseError> {
+        let [min, max]: [f32; 2] = parse_args(line_no, directive, args)?;
+        if min > max {
+            return Err(ParseError {
+                line: line_no,
+                message: format!(
+                    "'{}' expects a minimum no larger than the maximum",
+                    directive
+                ),
+            });
+        }
+        Ok(min..=max)
+    };
+
+    match directive {
+        "trees" => [config.trees] = parse_args(line_no, directive, args)?,
+        "iterations" => [config.iterations] = parse_args(line_no, directive, args)?,
+        "color" => {
+            config.palette = vec![(parse_args(line_no, directive, args)?, 1.0)];
+            *palette = true;
+        }
+        "palette" => {
+            let [r, g, b, a, weight] = parse_args(line_no, directive, args)?;
+            if weight <= 0.0 {
+                return Err(ParseError {
+                    line: line_no,
+                    message: String::from("'palette' expects a positive weight"),
+                });
+            }
+            if !std::mem::replace(palette, true) {
+                config.palette.clear();
+            }
+            config.palette.push(([r, g, b, a], weight));
+        }
+        "scale" => config.scale = range(args)?,
+        "shift_x" => config.shift_x = range(args)?,
+        "shift_y" => config.shift_y = range(args)?,
+        "haze" => {
+            let [r, g, b, a, strength] = parse_args(line_no, directive, args)?;
+            config.haze = Some(Haze {
+                color: [r, g, b, a],
+                strength,
+            });
+        }
+        "seed" => {
+            let [seed] = parse_args(line_no, directive, args)?;
+            config.seed = Some(seed);
+        }
+        _ => {
+            return Err(ParseError {
+                line: line_no,
+                message: format!("unknown directive '{}'", directive),
+            })
+        }
+    }
+    Ok(())
+}
+
+/// Replaces the layer being built by the first layer of the definition `name`.
+fn load_source(builder: &mut LayerBuilder, line: usize, name: &str) -> Result<(), ParseError> {
+    let definition = IfsDefinition::load(name).map_err(|e| ParseError {
+        line,
+        message: e.to_string(),
+    })?;
+    let layer = definition.layers().next().unwrap().clone();
+
+    builder.rows = layer.maps.iter().copied().collect();
+    builder.map_lines = vec![line; layer.maps.nrows()];
+    // The probabilities were computed when the definition was loaded
+    builder.layer = IfsLayer {
+        probabilities: Probabilities::Given,
+        ..layer
+    };
+    Ok(())
+}
+
+impl FromStr for Scene {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut ret = Scene::default();
+        let mut current: Option<SceneLayerBuilder> = None;
+
+        for (i, line) in s.lines().enumerate() {
+            let line_no = i + 1;
+            let line = line.split('#').next().unwrap();
+            let mut tokens = line.split_whitespace();
+            let directive = match tokens.next() {
+                Some(directive) => directive,
+                None => continue,
+            };
+            let args: Vec<&str> = tokens.collect();
+
+            match directive {
+                "point_size" => {
+                    let [size] = parse_args(line_no, directive, &args)?;
+                    ret.point_size = Some(size);
+                }
+                "clear_color" => {
+                    ret.clear_color = Some(parse_args(line_no, directive, &args)?);
+                }
+                "layer" => {
+                    let element = match args[..] {
+                        ["ifs"] => ElementBuilder::Ifs(Box::new(LayerBuilder::new(line_no))),
+                        ["forest"] => ElementBuilder::Forest {
+                            config: ForestConfig::default(),
+                            tree: None,
+                            palette: false,
+                        },
+                        _ => {
+                            return Err(ParseError {
+                                line: line_no,
+                                message: String::from("'layer' expects 'ifs' or 'forest'"),
+                            })
+                        }
+                    };
+
+                    let builder = SceneLayerBuilder {
+                        element,
+                        target: SCREEN,
+                        z: 0,
+                    };
+                    if let Some(builder) = current.replace(builder) {
+                        ret.layers.push(builder.finish()?);
+                    }
+                }
+                _ => {
+                    let builder = current.as_mut().ok_or_else(|| ParseError {
+                        line: line_no,
+                        message: format!("'{}' outside of a layer", directive),
+                    })?;
+
+                    match (directive, &mut builder.element) {
+                        ("target", _) => {
+                            let [x_min, x_max, y_min, y_max] =
+                                parse_args(line_no, directive, &args)?;
+                            builder.target = Bounds {
+                                x_min,
+                                x_max,
+                                y_min,
+                                y_max,
+                            };
+                        }
+                        ("z", _) => {
+                            let [z] = parse_args(line_no, directive, &args)?;
+                            builder.z = z;
+                        }
+                        ("source", ElementBuilder::Ifs(layer)) => match args[..] {
+                            [name] => load_source(layer, line_no, name)?,
+                            _ => {
+                                return Err(ParseError {
+                                    line: line_no,
+                                    message: String::from("'source' expects a definition name"),
+                                })
+                            }
+                        },
+                        (
+                            "map" | "geometric" | "color" | "iterations" | "transform"
+                            | "probabilities" | "transition",
+                            ElementBuilder::Ifs(layer),
+                        ) => layer.directive(line_no, directive, &args)?,
+                        ("source", ElementBuilder::Forest { tree, .. }) => match args[..] {
+                            [name] => {
+                                let tree = tree
+                                    .get_or_insert_with(|| Box::new(LayerBuilder::new(line_no)));
+                                load_source(tree, line_no, name)?
+                            }
+                            _ => {
+                                return Err(ParseError {
+                                    line: line_no,
+                                    message: String::from("'source' expects a definition name"),
+                                })
+                            }
+                        },
+                        (
+                            "map" | "geometric" | "probabilities",
+                            ElementBuilder::Forest { tree, .. },
+                        ) => tree
+                            .get_or_insert_with(|| Box::new(LayerBuilder::new(line_no)))
+                            .directive(line_no, directive, &args)?,
+                        (
+                            _,
+                            ElementBuilder::Forest {
+                                config, palette, ..
+                            },
+                        ) => forest_directive(config, palette, line_no, directive, &args)?,
+                        _ => {
+                            return Err(ParseError {
+                                line: line_no,
+                                message: format!("unknown directive '{}'", directive),
+                            })
+                        }
+                    }
+                }
+            }
+        }
+
+        if let Some(builder) = current {
+            ret.layers.push(builder.finish()?);
+        }
+
+        if ret.layers.is_empty() {
+            return Err(ParseError {
+                line: s.lines().count(),
+                message: String::from("no layers defined"),
+            });
+        }
+
+        Ok(ret)
+    }
+}
+
+/// Writes the directives describing `forest`, leaving out the ranges and tree it shares with
+/// [`ForestConfig::default`].
+fn write_forest(f: &mut Formatter<'_>, forest: &ForestConfig) -> std::fmt::Result {
+    let default = ForestConfig::default();
+    writeln!(f, "trees {}", forest.trees)?;
+    writeln!(f, "iterations {}", forest.iterations)?;
+    for ([r, g, b, a], weight) in &forest.palette {
+        write_floats(f, "palette", &[*r, *g, *b, *a, *weight])?;
+    }
+    for (directive, range, default_range) in [
+        ("scale", &forest.scale, &default.scale),
+        ("shift_x", &forest.shift_x, &default.shift_x),
+        ("shift_y", &forest.shift_y, &default.shift_y),
+    ] {
+        if range != default_range {
+            write_floats(f, directive, &[*range.start(), *range.end()])?;
+        }
+    }
+    if let Some(Haze { color, strength }) = forest.haze {
+        let [r, g, b, a] = color;
+        write_floats(f, "haze", &[r, g, b, a, strength])?;
+    }
+    if let Some(seed) = forest.seed {
+        writeln!(f, "seed {}", seed)?;
+    }
+    if forest.tree != default.tree {
+        for row in forest.tree.rows() {
+            write_floats(f, "map", &row.to_vec())?;
+        }
+    }
+
+    Ok(())
+}
+
+impl Display for Scene {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if let Some(size) = self.point_size {
+            write_floats(f, "point_size", &[size])?;
+        }
+        if let Some(color) = self.clear_color {
+            write_floats(f, "clear_color", &color)?;
+        }
+
+        for layer in &self.layers {
+            writeln!(f)?;
+            match &layer.element {
+                SceneElement::Ifs(ifs) => {
+                    writeln!(f, "layer ifs")?;
+                    write_layer(f, ifs)?;
+                }
+                SceneElement::Forest(forest) => {
+                    writeln!(f, "layer forest")?;
+                    write_forest(f, forest)?;
+                }
+            }
+
+            let t = layer.target;
+            write_floats(f, "target", &[t.x_min, t.x_max, t.y_min, t.y_max])?;
+            writeln!(f, "z {}", layer.z)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::forest::{ForestConfig, Haze};
+    use crate::ifs::scene::{Scene, SceneElement};
+    use crate::ifs::IfsProgram;
+    use crate::utils::rng::seeded_rng;
+
+    const SCENE: &str = "
+        point_size 2
+        layer ifs
+        z 1
+        color 1 0 0 1
+        iterations 1000
+        map 0.5 0 0 0.5 0 0 0.5
+        map 0.5 0 0 0.5 0.5 0.5 0.5
+        target 0 1 0 1
+
+        layer forest
+        trees 3
+        iterations 100
+        target -1 1 -1 0
+    ";
+
+    #[test]
+    fn parse_scene() {
+        let scene: Scene = SCENE.parse().unwrap();
+        assert_eq!(scene.point_size, Some(2.0));
+        assert_eq!(scene.layers.len(), 2);
+        assert_eq!(scene.layers[0].z, 1);
+        assert_eq!(scene.layers[1].target.y_max, 0.0);
+        assert_eq!(
+            scene.layers[1].element,
+            SceneElement::Forest(ForestConfig {
+                trees: 3,
+                iterations: 100,
+                ..ForestConfig::default()
+            })
+        );
+
+        // Drawn bottom up
+        let order: Vec<i32> = scene.draw_order().iter().map(|l| l.z).collect();
+        assert_eq!(order, vec![0, 1]);
+
+        assert_eq!(scene.to_string().parse::<Scene>().unwrap(), scene);
+    }
+
+    #[test]
+    fn parse_forest() {
+        let scene: Scene = "
+            layer forest
+            palette 0 0.5 0 1 3
+            palette 0.5 0.5 0 1 1
+            scale 2 4
+            shift_y -0.2 0
+            haze 0.8 0.8 0.9 1 0.6
+            seed 5
+            map 0.5 0 0 0.5 0 0 0.5
+            geometric 0.5 0.5 0 0 0.5 0.5 0.5
+        "
+        .parse()
+        .unwrap();
+
+        let SceneElement::Forest(forest) = &scene.layers[0].element else {
+            panic!("expected a forest");
+        };
+        assert_eq!(forest.palette.len(), 2);
+        assert_eq!(forest.palette[0], ([0.0, 0.5, 0.0, 1.0], 3.0));
+        assert_eq!(forest.scale, 2.0..=4.0);
+        assert_eq!(forest.shift_x, ForestConfig::default().shift_x);
+        assert_eq!(
+            forest.haze,
+            Some(Haze {
+                color: [0.8, 0.8, 0.9, 1.0],
+                strength: 0.6
+            })
+        );
+        assert_eq!(forest.seed, Some(5));
+        assert_eq!(forest.tree.nrows(), 2);
+
+        assert_eq!(scene.to_string().parse::<Scene>().unwrap(), scene);
+
+        let err = "layer forest\nscale 4 2".parse::<Scene>().unwrap_err();
+        assert_eq!(err.line, 2);
+        let err = "layer forest\npalette 1 0 0 1 0"
+            .parse::<Scene>()
+            .unwrap_err();
+        assert_eq!(err.line, 2);
+    }
+
+    #[test]
+    fn parse_errors() {
+        let err = "layer lake".parse::<Scene>().unwrap_err();
+        assert_eq!(err.line, 1);
+
+        let err = "layer forest\ntransform 1 0 0"
+            .parse::<Scene>()
+            .unwrap_err();
+        assert_eq!(err.line, 2);
+
+        let err = "target 0 1 0 1".parse::<Scene>().unwrap_err();
+        assert_eq!(err.line, 1);
+    }
+
+    #[test]
+    fn layers_fill_targets() {
+        let scene: Scene = SCENE.parse().unwrap();
+        let mut program = IfsProgram::default();
+        scene.apply(&mut program, &mut seeded_rng(Some(1)));
+
+        let image = program.render(8, 8, None);
+        // The IFS is drawn last, on the diagonal of the top right quarter
+        assert_eq!(image.pixel(7, 0), [255, 0, 0, 255]);
+        assert_eq!(image.pixel(5, 2), [255, 0, 0, 255]);
+        for x in 0..3 {
+            for y in 0..8 {
+                assert_ne!(image.pixel(x, y), [255, 0, 0, 255]);
+            }
+        }
+    }
+}
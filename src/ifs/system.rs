@@ -0,0 +1,470 @@
+//! Validated IFS matrices.
+//!
+//! The bins write their maps as raw matrices in the 7-column `a, b, c, d, e, f, p` layout.
+//! [`Ifs::new`] checks such a matrix once, up front, so that sampling never has to deal with a
+//! malformed one.
+
+use std::fmt::{Display, Formatter};
+
+use ndarray::{Array, ArrayView1, Ix2};
+use rand::distributions::{Distribution, WeightedIndex};
+use rand::Rng;
+
+use crate::dimension::moran_exponent;
+use crate::ifs::attractor::{attractor_bounds, bounding_ball, Ball};
+use crate::ifs::{apply_map, effective_map, fixed_point, operator_norm, Bounds};
+
+/// Reasons a matrix does not describe an IFS that can be sampled.
+#[derive(Clone, Debug, PartialEq)]
+pub enum IfsError {
+    /// The matrix does not have one column per coefficient plus one for the probability.
+    WrongColumnCount {
+        expected: usize,
+        found: usize,
+    },
+    NoMaps,
+    /// A coefficient or probability is infinite or NaN.
+    NotFinite {
+        map: usize,
+        column: usize,
+    },
+    NegativeProbability {
+        map: usize,
+        probability: f32,
+    },
+    /// All probabilities are zero, so no map can ever be chosen.
+    ZeroProbabilitySum,
+}
+
+impl IfsError {
+    /// Index of the map the error is about, if it is about a single map.
+    pub fn map(&self) -> Option<usize> {
+        match self {
+            IfsError::NotFinite { map, .. } | IfsError::NegativeProbability { map, .. } => {
+                Some(*map)
+            }
+            IfsError::WrongColumnCount { .. } | IfsError::NoMaps | IfsError::ZeroProbabilitySum => {
+                None
+            }
+        }
+    }
+}
+
+impl Display for IfsError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IfsError::WrongColumnCount { expected, found } => {
+                write!(f, "expected {} columns, found {}", expected, found)
+            }
+            IfsError::NoMaps => write!(f, "an IFS needs at least one map"),
+            IfsError::NotFinite { map, column } => {
+                write!(f, "map {}: column {} is not a finite number", map, column)
+            }
+            IfsError::NegativeProbability { map, probability } => {
+                write!(f, "map {}: negative probability {}", map, probability)
+            }
+            IfsError::ZeroProbabilitySum => write!(f, "the probabilities add up to zero"),
+        }
+    }
+}
+
+impl std::error::Error for IfsError {}
+
+/// Problems that do not stop an IFS from being sampled, but probably deserve a look.
+#[derive(Clone, Debug, PartialEq)]
+pub enum IfsWarning {
+    /// The map stretches some distances, i.e. its operator norm is at least 1. The IFS can still
+    /// have an attractor if it contracts on average, but the chaos game may also fly off.
+    NonContractive { map: usize, norm: f32 },
+    /// The map has probability zero and is never chosen.
+    Unused { map: usize },
+}
+
+impl Display for IfsWarning {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IfsWarning::NonContractive { map, norm } => {
+                write!(
+                    f,
+                    "map {}: not a contraction (operator norm {:.3})",
+                    map, norm
+                )
+            }
+            IfsWarning::Unused { map } => write!(f, "map {}: probability is zero", map),
+        }
+    }
+}
+
+/// How the probability of each map is chosen.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum Probabilities {
+    /// The last column of the matrix, as given.
+    #[default]
+    Given,
+    /// Proportional to `|det|`, the factor by which each map scales areas. Every map gets at
+    /// least `floor`, so that maps squashing the plane onto a line (like the stem of the fern)
+    /// are still drawn.
+    Determinant { floor: f32 },
+    /// `rᵢᴰ`, where `rᵢ = sqrt(|det|)` is the scaling ratio of each map and `D` solves the Moran
+    /// equation `Σ rᵢᴰ = 1`. For similarities whose images don't overlap, this spreads the points
+    /// evenly over the attractor. Every map gets at least `floor`.
+    UniformDensity { floor: f32 },
+}
+
+impl Probabilities {
+    /// Floor used when none is given, small enough not to matter for non-singular maps.
+    pub const DEFAULT_FLOOR: f32 = 0.01;
+
+    /// Computes probabilities for the maps of `d`, which must have 6 or 7 columns. They add up
+    /// to 1. Returns `None` for [`Probabilities::Given`].
+    pub fn compute(&self, d: &Array<f32, Ix2>) -> Option<Vec<f32>> {
+        let dets = || {
+            d.rows().into_iter().map(|row| {
+                let [a, b, c, d, _, _] = effective_map(row);
+                (a * d - b * c).abs()
+            })
+        };
+
+        let (weights, floor): (Vec<f32>, f32) = match *self {
+            Probabilities::Given => return None,
+            Probabilities::Determinant { floor } => (dets().collect(), floor),
+            Probabilities::UniformDensity { floor } => {
+                let ratios: Vec<f64> = dets().map(|det| (det as f64).sqrt()).collect();
+                // Singular maps have ratio 0 and don't take part in the equation, they only get
+                // the floor
+                let nonzero: Vec<f64> = ratios.iter().copied().filter(|&r| r > 0.0).collect();
+                match moran_exponent(&nonzero) {
+                    Some(exponent) => (
+                        ratios
+                            .iter()
+                            .map(|&r| {
+                                if r > 0.0 {
+                                    r.powf(exponent) as f32
+                                } else {
+                                    0.0
+                                }
+                            })
+                            .collect(),
+                        floor,
+                    ),
+                    // Not contracting, so there is no dimension to aim for
+                    None => (dets().collect(), floor),
+                }
+            }
+        };
+
+        let total: f32 = weights.iter().sum();
+        let floored: Vec<f32> = weights
+            .iter()
+            .map(|&w| {
+                if total > 0.0 {
+                    (w / total).max(floor)
+                } else {
+                    1.0
+                }
+            })
+            .collect();
+        let total: f32 = floored.iter().sum();
+        Some(floored.into_iter().map(|w| w / total).collect())
+    }
+}
+
+/// Checks a matrix with `columns` columns, the last one holding probabilities, and returns the
+/// distribution for picking maps. `norm` gives the operator norm of the map in a row.
+pub(crate) fn validate<F>(
+    maps: &Array<f32, Ix2>,
+    columns: usize,
+    norm: F,
+) -> Result<(WeightedIndex<f32>, Vec<IfsWarning>), IfsError>
+where
+    F: Fn(ArrayView1<f32>) -> f32,
+{
+    if maps.ncols() != columns {
+        return Err(IfsError::WrongColumnCount {
+            expected: columns,
+            found: maps.ncols(),
+        });
+    }
+    if maps.nrows() == 0 {
+        return Err(IfsError::NoMaps);
+    }
+
+    let mut warnings = vec![];
+    for (map, row) in maps.rows().into_iter().enumerate() {
+        if let Some(column) = row.iter().position(|v| !v.is_finite()) {
+            return Err(IfsError::NotFinite { map, column });
+        }
+
+        let probability = row[columns - 1];
+        if probability < 0.0 {
+            return Err(IfsError::NegativeProbability { map, probability });
+        }
+        if probability == 0.0 {
+            warnings.push(IfsWarning::Unused { map });
+        }
+
+        let norm = norm(row);
+        if norm >= 1.0 {
+            warnings.push(IfsWarning::NonContractive { map, norm });
+        }
+    }
+
+    let distribution = WeightedIndex::new(maps.column(columns - 1).to_vec())
+        .map_err(|_| IfsError::ZeroProbabilitySum)?;
+
+    Ok((distribution, warnings))
+}
+
+/// A matrix in the 7-column IFS layout that has been checked by [`Ifs::new`].
+#[derive(Clone, Debug)]
+pub struct Ifs {
+    maps: Array<f32, Ix2>,
+    distribution: WeightedIndex<f32>,
+    warnings: Vec<IfsWarning>,
+}
+
+impl Ifs {
+    pub fn new(maps: Array<f32, Ix2>) -> Result<Self, IfsError> {
+        let (distribution, warnings) = validate(&maps, 7, |row| operator_norm(effective_map(row)))?;
+
+        Ok(Self {
+            maps,
+            distribution,
+            warnings,
+        })
+    }
+
+    /// Same as [`Ifs::new`], but with the probabilities replaced according to `probabilities`.
+    /// Unless they are [`Probabilities::Given`], the probability column may be left out.
+    pub fn with_probabilities(
+        maps: Array<f32, Ix2>,
+        probabilities: Probabilities,
+    ) -> Result<Self, IfsError> {
+        let mut maps = maps;
+        if maps.ncols() != 6 && maps.ncols() != 7 {
+            return Err(IfsError::WrongColumnCount {
+                expected: 7,
+                found: maps.ncols(),
+            });
+        }
+
+        if let Some(computed) = probabilities.compute(&maps) {
+            if maps.ncols() == 6 {
+                maps.push_column(ArrayView1::from(&computed)).unwrap();
+            } else {
+                maps.column_mut(6).assign(&ArrayView1::from(&computed));
+            }
+        }
+
+        Self::new(maps)
+    }
+
+    pub fn maps(&self) -> &Array<f32, Ix2> {
+        &self.maps
+    }
+
+    pub fn map_count(&self) -> usize {
+        self.maps.nrows()
+    }
+
+    pub fn warnings(&self) -> &[IfsWarning] {
+        &self.warnings
+    }
+
+    /// Whether every map is a contraction, which guarantees that the IFS has an attractor.
+    pub fn is_contractive(&self) -> bool {
+        !self
+            .warnings
+            .iter()
+            .any(|w| matches!(w, IfsWarning::NonContractive { .. }))
+    }
+
+    /// Picks the index of a map according to the probabilities.
+    pub fn choose<R: Rng + ?Sized>(&self, rng: &mut R) -> usize {
+        self.distribution.sample(rng)
+    }
+
+    /// Applies map `map` to a point, see [`apply_map`].
+    pub fn apply(&self, map: usize, x: f32, y: f32) -> (f32, f32) {
+        apply_map(self.maps.row(map), x, y)
+    }
+
+    /// Coefficients of every map in conventional form, see [`effective_map`].
+    pub fn effective_maps(&self) -> Vec<[f32; 6]> {
+        self.maps.rows().into_iter().map(effective_map).collect()
+    }
+
+    /// The point each map leaves in place, see [`fixed_point`].
+    pub fn fixed_points(&self) -> Vec<Option<[f32; 2]>> {
+        self.effective_maps().into_iter().map(fixed_point).collect()
+    }
+
+    /// A ball containing the attractor, see [`bounding_ball`].
+    pub fn bounding_ball(&self) -> Option<Ball> {
+        bounding_ball(&self.effective_maps())
+    }
+
+    /// Bounds containing the attractor, computed without sampling it. `None` unless every map is
+    /// a contraction. See [`attractor_bounds`].
+    pub fn attractor_bounds(&self) -> Option<Bounds> {
+        attractor_bounds(&self.effective_maps(), |_, map| self.maps[(map, 6)] > 0.0)
+    }
+}
+
+/// Maps together with the rule for picking the next one to apply, so that the chaos game can run
+/// on an [`Ifs`] and a [`RecurrentIfs`](crate::ifs::recurrent::RecurrentIfs) alike.
+pub trait MapSystem: Sync {
+    fn map_count(&self) -> usize;
+
+    /// Picks the index of the next map, given the one applied last if there is one.
+    fn next_map<R: Rng + ?Sized>(&self, rng: &mut R, previous: Option<usize>) -> usize;
+
+    /// Applies map `map` to a point.
+    fn apply(&self, map: usize, x: f32, y: f32) -> (f32, f32);
+
+    /// Bounds containing the attractor, known before sampling. `None` if they can't be computed,
+    /// e.g. because a map is not a contraction.
+    fn attractor_bounds(&self) -> Option<Bounds>;
+}
+
+impl MapSystem for Ifs {
+    fn map_count(&self) -> usize {
+        Ifs::map_count(self)
+    }
+
+    /// Every map is picked independently of the previous one.
+    fn next_map<R: Rng + ?Sized>(&self, rng: &mut R, _previous: Option<usize>) -> usize {
+        self.choose(rng)
+    }
+
+    fn apply(&self, map: usize, x: f32, y: f32) -> (f32, f32) {
+        Ifs::apply(self, map, x, y)
+    }
+
+    fn attractor_bounds(&self) -> Option<Bounds> {
+        Ifs::attractor_bounds(self)
+    }
+}
+
+impl TryFrom<Array<f32, Ix2>> for Ifs {
+    type Error = IfsError;
+
+    fn try_from(maps: Array<f32, Ix2>) -> Result<Self, Self::Error> {
+        Self::new(maps)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ndarray::{array, Array};
+
+    use crate::ifs::system::{Ifs, IfsError, IfsWarning, Probabilities};
+
+    #[test]
+    fn valid() {
+        let ifs = Ifs::new(array![
+            [0.5, 0.0, 0.0, 0.5, 0.0, 0.0, 0.5],
+            [0.5, 0.0, 0.0, 0.5, 0.5, 0.0, 0.5],
+        ])
+        .unwrap();
+
+        assert_eq!(ifs.map_count(), 2);
+        assert!(ifs.warnings().is_empty());
+        assert!(ifs.is_contractive());
+        assert_eq!(ifs.apply(1, 1.0, 1.0), (1.0, 0.5));
+    }
+
+    #[test]
+    fn invalid() {
+        let errors = [
+            (
+                Array::zeros((2, 6)),
+                IfsError::WrongColumnCount {
+                    expected: 7,
+                    found: 6,
+                },
+            ),
+            (Array::zeros((0, 7)), IfsError::NoMaps),
+            (
+                array![[0.5, 0.0, 0.0, f32::NAN, 0.0, 0.0, 1.0]],
+                IfsError::NotFinite { map: 0, column: 3 },
+            ),
+            (
+                array![
+                    [0.5, 0.0, 0.0, 0.5, 0.0, 0.0, 1.0],
+                    [0.5, 0.0, 0.0, 0.5, 0.0, 0.0, -0.5],
+                ],
+                IfsError::NegativeProbability {
+                    map: 1,
+                    probability: -0.5,
+                },
+            ),
+            (
+                array![[0.5, 0.0, 0.0, 0.5, 0.0, 0.0, 0.0]],
+                IfsError::ZeroProbabilitySum,
+            ),
+        ];
+
+        for (maps, error) in errors {
+            assert_eq!(Ifs::new(maps).unwrap_err(), error);
+        }
+    }
+
+    #[test]
+    fn warnings() {
+        let ifs = Ifs::new(array![
+            [1.2, 0.0, 0.0, 0.5, 0.0, 0.0, 1.0],
+            [0.5, 0.0, 0.0, 0.5, 0.5, 0.0, 0.0],
+        ])
+        .unwrap();
+
+        assert_eq!(
+            ifs.warnings(),
+            [
+                IfsWarning::NonContractive { map: 0, norm: 1.2 },
+                IfsWarning::Unused { map: 1 },
+            ]
+        );
+        assert!(!ifs.is_contractive());
+    }
+
+    #[test]
+    fn determinant_probabilities() {
+        // Barnsley fern without its probabilities, the stem is singular
+        let fern = array![
+            [0.0, 0.0, 0.0, 0.16, 0.0, 0.0],
+            [0.85, 0.04, -0.04, 0.85, 0.0, 1.6],
+            [0.2, -0.26, 0.23, 0.22, 0.0, 1.6],
+            [-0.15, 0.28, 0.26, 0.24, 0.0, 0.44],
+        ];
+        let ifs = Ifs::with_probabilities(
+            fern,
+            Probabilities::Determinant {
+                floor: Probabilities::DEFAULT_FLOOR,
+            },
+        )
+        .unwrap();
+
+        let p = ifs.maps().column(6).to_vec();
+        assert!((p.iter().sum::<f32>() - 1.0).abs() < 1e-5);
+        assert!(p[0] > 0.009 && p[0] < 0.011, "stem probability {}", p[0]);
+        assert!(p[1] > 0.8, "main frond probability {}", p[1]);
+    }
+
+    #[test]
+    fn uniform_density_probabilities() {
+        // Ratios 1/2 and 1/4, so D solves 2^-D + 4^-D = 1, i.e. 2^-D is the golden ratio - 1
+        let maps = array![
+            [0.5, 0.0, 0.0, 0.5, 0.0, 0.0, 1.0],
+            [0.25, 0.0, 0.0, 0.25, 0.75, 0.0, 1.0],
+        ];
+        let p = Probabilities::UniformDensity { floor: 0.0 }
+            .compute(&maps)
+            .unwrap();
+
+        let golden = (5_f32.sqrt() - 1.0) / 2.0;
+        assert!((p[0] - golden).abs() < 1e-4, "{:?}", p);
+        assert!((p[1] - golden * golden).abs() < 1e-4, "{:?}", p);
+        assert_eq!(Probabilities::Given.compute(&maps), None);
+    }
+}
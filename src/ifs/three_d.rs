@@ -0,0 +1,541 @@
+//! Three-dimensional IFS, like the 3D fern or the Sierpinski tetrahedron.
+//!
+//! Each map is an affine map of space, written as a 3x4 matrix row by row followed by the
+//! probability, giving 13 columns:
+//!
+//! ```text
+//! a11 a12 a13 t1  a21 a22 a23 t2  a31 a32 a33 t3  p
+//! ```
+//!
+//! Unlike the 2D [`apply_map`](crate::ifs::apply_map) every coordinate is computed from the old
+//! point. The sampled point cloud is viewed through a [`Camera`], either in a window where it can
+//! be rotated with the mouse, or rendered to an [`Image`].
+
+use std::f32::consts::FRAC_PI_2;
+
+use glium::glutin::dpi::LogicalSize;
+use glium::glutin::event::{
+    ElementState, Event, MouseButton, MouseScrollDelta, VirtualKeyCode, WindowEvent,
+};
+use glium::glutin::event_loop::{ControlFlow, EventLoop};
+use glium::glutin::window::WindowBuilder;
+use glium::glutin::ContextBuilder;
+use glium::index::{NoIndices, PrimitiveType};
+use glium::{
+    implement_vertex, uniform, Depth, DepthTest, Display, DrawParameters, Program, Surface,
+    VertexBuffer,
+};
+use ndarray::{Array, ArrayView1, Ix2};
+use rand::distributions::{Distribution, WeightedIndex};
+use rand::Rng;
+use rand_chacha::ChaCha8Rng;
+
+use crate::ifs::system::{validate, IfsError, IfsWarning};
+use crate::image::{to_rgba8, Image};
+use crate::utils::rng::seeded_rng;
+
+/// Number of columns of a 3D IFS matrix.
+pub const COLUMNS: usize = 13;
+
+/// Distance from the camera to the center of the point cloud, which is scaled to fit in the unit
+/// ball, in perspective projection.
+const CAMERA_DISTANCE: f32 = 3.0;
+
+/// Applies one row of a 3D IFS matrix to a point.
+pub fn apply_map_3d(r: ArrayView1<f32>, p: [f32; 3]) -> [f32; 3] {
+    let [x, y, z] = p;
+    [
+        r[0] * x + r[1] * y + r[2] * z + r[3],
+        r[4] * x + r[5] * y + r[6] * z + r[7],
+        r[8] * x + r[9] * y + r[10] * z + r[11],
+    ]
+}
+
+/// Largest singular value of the linear part of a row, found by power iteration on `MᵀM`.
+fn operator_norm_3d(r: ArrayView1<f32>) -> f32 {
+    let m = [[r[0], r[1], r[2]], [r[4], r[5], r[6]], [r[8], r[9], r[10]]];
+    let mul =
+        |v: [f32; 3]| -> [f32; 3] { m.map(|row| row[0] * v[0] + row[1] * v[1] + row[2] * v[2]) };
+    let mul_transposed = |v: [f32; 3]| -> [f32; 3] {
+        [0, 1, 2].map(|j| m[0][j] * v[0] + m[1][j] * v[1] + m[2][j] * v[2])
+    };
+    let length = |v: [f32; 3]| (v[0] * v[0] + v[1] * v[1] + v[2] * v[2]).sqrt();
+
+    let mut v = [0.8, 0.5, 0.3];
+    for _ in 0..100 {
+        let w = mul_transposed(mul(v));
+        let l = length(w);
+        if l == 0.0 {
+            return 0.0;
+        }
+        v = w.map(|c| c / l);
+    }
+    length(mul(v))
+}
+
+/// A 3D IFS matrix that has been checked by [`Ifs3d::new`].
+#[derive(Clone, Debug)]
+pub struct Ifs3d {
+    maps: Array<f32, Ix2>,
+    distribution: WeightedIndex<f32>,
+    warnings: Vec<IfsWarning>,
+}
+
+impl Ifs3d {
+    pub fn new(maps: Array<f32, Ix2>) -> Result<Self, IfsError> {
+        let (distribution, warnings) = validate(&maps, COLUMNS, operator_norm_3d)?;
+        Ok(Self {
+            maps,
+            distribution,
+            warnings,
+        })
+    }
+
+    pub fn maps(&self) -> &Array<f32, Ix2> {
+        &self.maps
+    }
+
+    pub fn warnings(&self) -> &[IfsWarning] {
+        &self.warnings
+    }
+
+    /// Picks the index of a map according to the probabilities.
+    pub fn choose<R: Rng + ?Sized>(&self, rng: &mut R) -> usize {
+        self.distribution.sample(rng)
+    }
+
+    pub fn apply(&self, map: usize, p: [f32; 3]) -> [f32; 3] {
+        apply_map_3d(self.maps.row(map), p)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Projection {
+    Orthographic,
+    /// Perspective with a vertical field of view of `fov` radians.
+    Perspective {
+        fov: f32,
+    },
+}
+
+/// Orientation and projection used to look at a point cloud scaled to fit the unit ball.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Camera {
+    /// Rotation about the vertical axis, in radians.
+    pub yaw: f32,
+    /// Rotation about the horizontal axis, in radians, between -π/2 and π/2.
+    pub pitch: f32,
+    pub zoom: f32,
+    pub projection: Projection,
+}
+
+impl Default for Camera {
+    fn default() -> Self {
+        Self {
+            yaw: 0.5,
+            pitch: 0.3,
+            zoom: 1.0,
+            projection: Projection::Orthographic,
+        }
+    }
+}
+
+impl Camera {
+    pub fn rotate(&mut self, yaw: f32, pitch: f32) {
+        self.yaw += yaw;
+        self.pitch = (self.pitch + pitch).clamp(-FRAC_PI_2, FRAC_PI_2);
+    }
+
+    /// Rotation matrix, row by row: the yaw is applied first, then the pitch.
+    pub fn rotation(&self) -> [[f32; 3]; 3] {
+        let (sy, cy) = self.yaw.sin_cos();
+        let (sp, cp) = self.pitch.sin_cos();
+        [
+            [cy, 0.0, sy],
+            [sp * sy, cp, -sp * cy],
+            [-cp * sy, sp, cp * cy],
+        ]
+    }
+
+    /// Factors applied to x and y so that the unit ball fits in a viewport of the given aspect
+    /// ratio (width / height).
+    fn aspect_scale(aspect: f32) -> [f32; 2] {
+        if aspect >= 1.0 {
+            [1.0 / aspect, 1.0]
+        } else {
+            [1.0, aspect]
+        }
+    }
+
+    /// `tan(fov / 2)` for perspective projection, 0 for orthographic.
+    fn perspective_factor(&self) -> f32 {
+        match self.projection {
+            Projection::Orthographic => 0.0,
+            Projection::Perspective { fov } => (fov / 2.0).tan(),
+        }
+    }
+
+    /// Projects a point in the unit ball to normalized device coordinates. The third coordinate
+    /// is the depth, from -1.0 (nearest) to 1.0.
+    pub fn project(&self, p: [f32; 3], aspect: f32) -> [f32; 3] {
+        let [x, y, z] = self
+            .rotation()
+            .map(|row| row[0] * p[0] + row[1] * p[1] + row[2] * p[2]);
+        let [sx, sy] = Self::aspect_scale(aspect);
+
+        let perspective = self.perspective_factor();
+        let w = if perspective > 0.0 {
+            (CAMERA_DISTANCE - z) * perspective
+        } else {
+            1.0
+        };
+
+        [x * self.zoom * sx / w, y * self.zoom * sy / w, -z]
+    }
+}
+
+/// Brightness of a point at the given depth, so that nearer points stand out.
+fn shade(depth: f32) -> f32 {
+    1.0 - 0.45 * (depth + 1.0) / 2.0
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct ColoredVertex3d {
+    position: [f32; 3],
+    color: [f32; 4],
+}
+
+implement_vertex!(ColoredVertex3d, position, color);
+
+/// Samples 3D IFS attractors with the chaos game and displays them. The 3D counterpart of
+/// [`IfsProgram`](crate::ifs::IfsProgram).
+pub struct IfsProgram3d {
+    vertices: Vec<ColoredVertex3d>,
+    clear_color: (f32, f32, f32, f32),
+    rng: ChaCha8Rng,
+}
+
+impl Default for IfsProgram3d {
+    fn default() -> Self {
+        Self::with_rng(seeded_rng(None))
+    }
+}
+
+impl IfsProgram3d {
+    pub fn with_rng(rng: ChaCha8Rng) -> Self {
+        Self {
+            vertices: vec![],
+            clear_color: (1.0, 1.0, 1.0, 1.0),
+            rng,
+        }
+    }
+
+    pub fn set_clear_color(&mut self, color: (f32, f32, f32, f32)) {
+        self.clear_color = color;
+    }
+
+    pub fn sample(&mut self, ifs: &Ifs3d, color: [f32; 4], iters: usize) {
+        let mut rng = self.rng.clone();
+        self.sample_with_rng(&mut rng, ifs, color, iters);
+        self.rng = rng;
+    }
+
+    pub fn sample_with_rng<R: Rng + ?Sized>(
+        &mut self,
+        rng: &mut R,
+        ifs: &Ifs3d,
+        color: [f32; 4],
+        iters: usize,
+    ) {
+        let mut p = [0.0; 3];
+        for i in 0..iters {
+            p = ifs.apply(ifs.choose(rng), p);
+
+            // Skip first few iterations
+            if i >= 10 {
+                self.vertices.push(ColoredVertex3d { position: p, color });
+            }
+        }
+    }
+
+    pub fn points(&self) -> Vec<[f32; 3]> {
+        self.vertices.iter().map(|v| v.position).collect()
+    }
+
+    /// Center and radius of a ball containing all points, used to scale them to the unit ball.
+    fn bounding_ball(&self) -> ([f32; 3], f32) {
+        if self.vertices.is_empty() {
+            return ([0.0; 3], 1.0);
+        }
+
+        let mut min = [f32::MAX; 3];
+        let mut max = [f32::MIN; 3];
+        for v in &self.vertices {
+            for i in 0..3 {
+                min[i] = min[i].min(v.position[i]);
+                max[i] = max[i].max(v.position[i]);
+            }
+        }
+
+        let center = [0, 1, 2].map(|i| (min[i] + max[i]) / 2.0);
+        let radius = [0, 1, 2]
+            .map(|i| (max[i] - min[i]) / 2.0)
+            .iter()
+            .map(|h| h * h)
+            .sum::<f32>()
+            .sqrt();
+        (center, radius.max(f32::MIN_POSITIVE))
+    }
+
+    /// Renders the points on the CPU, as [`IfsProgram3d::run`] would show them with `camera`.
+    pub fn render(
+        &self,
+        width: u32,
+        height: u32,
+        camera: &Camera,
+        point_size: Option<f32>,
+    ) -> Image {
+        let (r, g, b, a) = self.clear_color;
+        let mut image = Image::new(width, height, [r, g, b, a]);
+        let aspect = width as f32 / height as f32;
+        let (center, radius) = self.bounding_ball();
+
+        let mut projected: Vec<([f32; 3], [f32; 4])> = self
+            .vertices
+            .iter()
+            .map(|v| {
+                let p = [0, 1, 2].map(|i| (v.position[i] - center[i]) / radius);
+                (camera.project(p, aspect), v.color)
+            })
+            .collect();
+
+        // Far to near, so nearer points are drawn over farther ones like with a depth buffer
+        projected.sort_by(|a, b| b.0[2].total_cmp(&a.0[2]));
+
+        for ([x, y, depth], color) in projected {
+            let shade = shade(depth);
+            let color = [
+                color[0] * shade,
+                color[1] * shade,
+                color[2] * shade,
+                color[3],
+            ];
+            let (px, py) = image.ndc_to_pixel(x, y);
+            image.draw_point(px, py, point_size.unwrap_or(1.0), to_rgba8(color));
+        }
+
+        image
+    }
+
+    /// Opens a window showing the points. Drag with the left mouse button to rotate, scroll to
+    /// zoom, press P to switch between orthographic and perspective projection and space to
+    /// reset the view.
+    pub fn run(&self, camera: Camera, point_size: Option<f32>) {
+        let event_loop = EventLoop::new();
+
+        let wb = WindowBuilder::new()
+            .with_inner_size(LogicalSize::new(768.0_f32, 768.0_f32))
+            .with_title("3D IFS viewer");
+
+        let cb = ContextBuilder::new().with_depth_buffer(24);
+
+        let display = Display::new(wb, cb, &event_loop).unwrap();
+
+        let vertex_buffer = VertexBuffer::new(&display, &self.vertices).unwrap();
+
+        let indices = NoIndices(PrimitiveType::Points);
+
+        let program = Program::from_source(
+            &display,
+            r##"#version 140
+uniform mat3 rotation;
+uniform vec3 center;
+uniform float radius;
+uniform float zoom;
+uniform vec2 aspect_scale;
+uniform float perspective;
+uniform float camera_distance;
+
+in vec3 position;
+in vec4 color;
+out vec4 v_color;
+
+void main() {
+    vec3 p = rotation * ((position - center) / radius);
+    float w = perspective > 0.0 ? (camera_distance - p.z) * perspective : 1.0;
+    gl_Position = vec4(p.xy * zoom * aspect_scale, -p.z * w, w);
+    v_color = vec4(color.rgb * (1.0 - 0.45 * (1.0 - p.z) / 2.0), color.a);
+}
+"##,
+            r##"#version 130
+in vec4 v_color;
+out vec4 color;
+void main() {
+    color = v_color;
+}
+"##,
+            None,
+        )
+        .unwrap();
+
+        let initial_camera = camera;
+        let mut camera = camera;
+        let (center, radius) = self.bounding_ball();
+        let clear_color = self.clear_color;
+        let mut mouse_down = false;
+        let mut mouse_last = (0.0, 0.0);
+
+        event_loop.run(move |ev, _, control_flow| {
+            *control_flow = ControlFlow::Wait;
+
+            match ev {
+                Event::WindowEvent { event, .. } => match event {
+                    WindowEvent::CloseRequested => {
+                        *control_flow = ControlFlow::Exit;
+                        return;
+                    }
+                    WindowEvent::MouseInput {
+                        state,
+                        button: MouseButton::Left,
+                        ..
+                    } => mouse_down = state == ElementState::Pressed,
+                    WindowEvent::CursorMoved { position, .. } => {
+                        if mouse_down {
+                            camera.rotate(
+                                (position.x - mouse_last.0) as f32 * 0.01,
+                                (position.y - mouse_last.1) as f32 * 0.01,
+                            );
+                            display.gl_window().window().request_redraw();
+                        }
+                        mouse_last = (position.x, position.y);
+                    }
+                    WindowEvent::MouseWheel {
+                        delta: MouseScrollDelta::LineDelta(_, y),
+                        ..
+                    } => {
+                        camera.zoom *= 1.1_f32.powf(y);
+                        display.gl_window().window().request_redraw();
+                    }
+                    WindowEvent::KeyboardInput { input, .. }
+                        if input.state == ElementState::Pressed =>
+                    {
+                        match input.virtual_keycode {
+                            Some(VirtualKeyCode::P) => {
+                                camera.projection = match camera.projection {
+                                    Projection::Orthographic => Projection::Perspective {
+                                        fov: 45_f32.to_radians(),
+                                    },
+                                    Projection::Perspective { .. } => Projection::Orthographic,
+                                }
+                            }
+                            Some(VirtualKeyCode::Space) => camera = initial_camera,
+                            _ => return,
+                        }
+                        display.gl_window().window().request_redraw();
+                    }
+                    _ => return,
+                },
+                Event::RedrawRequested(_) => (),
+                _ => return,
+            }
+
+            let (width, height) = display.get_framebuffer_dimensions();
+            let rotation = camera.rotation();
+            let uniforms = uniform! {
+                // GLSL matrices are column major
+                rotation: [0, 1, 2].map(|j| [rotation[0][j], rotation[1][j], rotation[2][j]]),
+                center: center,
+                radius: radius,
+                zoom: camera.zoom,
+                aspect_scale: Camera::aspect_scale(width as f32 / height as f32),
+                perspective: camera.perspective_factor(),
+                camera_distance: CAMERA_DISTANCE,
+            };
+
+            let mut target = display.draw();
+            target.clear_color_and_depth(clear_color, 1.0);
+
+            let p = DrawParameters {
+                point_size,
+                depth: Depth {
+                    test: DepthTest::IfLess,
+                    write: true,
+                    ..Default::default()
+                },
+                ..Default::default()
+            };
+            target
+                .draw(&vertex_buffer, indices, &program, &uniforms, &p)
+                .unwrap();
+            target.finish().unwrap();
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ndarray::array;
+
+    use crate::ifs::three_d::{operator_norm_3d, Camera, Ifs3d, IfsProgram3d, Projection};
+    use crate::utils::rng::seeded_rng;
+
+    fn tetrahedron() -> Ifs3d {
+        Ifs3d::new(array![
+            [0.5, 0.0, 0.0, 0.5, 0.0, 0.5, 0.0, 0.5, 0.0, 0.0, 0.5, 0.5, 0.25],
+            [0.5, 0.0, 0.0, 0.5, 0.0, 0.5, 0.0, -0.5, 0.0, 0.0, 0.5, -0.5, 0.25],
+            [0.5, 0.0, 0.0, -0.5, 0.0, 0.5, 0.0, 0.5, 0.0, 0.0, 0.5, -0.5, 0.25],
+            [0.5, 0.0, 0.0, -0.5, 0.0, 0.5, 0.0, -0.5, 0.0, 0.0, 0.5, 0.5, 0.25],
+        ])
+        .unwrap()
+    }
+
+    #[test]
+    fn norm() {
+        let row = array![0.2, 0.0, 0.0, 0.0, 0.0, 0.7, 0.0, 0.0, 0.0, 0.0, 0.3, 0.0, 1.0];
+        assert!((operator_norm_3d(row.view()) - 0.7).abs() < 1e-5);
+        assert!(tetrahedron().warnings().is_empty());
+    }
+
+    #[test]
+    fn projection() {
+        let camera = Camera {
+            yaw: 0.0,
+            pitch: 0.0,
+            zoom: 1.0,
+            projection: Projection::Orthographic,
+        };
+        assert_eq!(camera.project([0.5, 0.25, 1.0], 1.0), [0.5, 0.25, -1.0]);
+        // Wide viewports squeeze x so the ball stays round
+        assert_eq!(camera.project([0.5, 0.25, 1.0], 2.0), [0.25, 0.25, -1.0]);
+
+        // Rotating by 90 degrees about the vertical axis brings +x to the back
+        let turned = Camera {
+            yaw: std::f32::consts::FRAC_PI_2,
+            ..camera
+        };
+        let [x, _, depth] = turned.project([1.0, 0.0, 0.0], 1.0);
+        assert!(x.abs() < 1e-6 && (depth - 1.0).abs() < 1e-6);
+
+        // With perspective, far points move towards the center
+        let perspective = Camera {
+            projection: Projection::Perspective { fov: 1.0 },
+            ..camera
+        };
+        let near = perspective.project([0.5, 0.0, 1.0], 1.0);
+        let far = perspective.project([0.5, 0.0, -1.0], 1.0);
+        assert!(near[0] > far[0]);
+    }
+
+    #[test]
+    fn seeded_render_is_reproducible() {
+        let render = || {
+            let mut program = IfsProgram3d::with_rng(seeded_rng(Some(5)));
+            program.sample(&tetrahedron(), [0.0, 0.0, 0.0, 1.0], 5000);
+            program.render(64, 64, &Camera::default(), None)
+        };
+
+        let image = render();
+        assert_eq!(image, render());
+        assert!(image.data().chunks(4).any(|p| p != [255, 255, 255, 255]));
+    }
+}
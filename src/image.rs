@@ -0,0 +1,303 @@
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+/// Converts a floating point RGBA color, as used by the shaders, to 8 bits per channel.
+pub fn to_rgba8(color: [f32; 4]) -> [u8; 4] {
+    color.map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8)
+}
+
+/// An RGBA image with 8 bits per channel, stored row by row starting at the top left corner.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Image {
+    width: u32,
+    height: u32,
+    data: Vec<u8>,
+}
+
+impl Image {
+    pub fn new(width: u32, height: u32, clear_color: [f32; 4]) -> Self {
+        let pixel = to_rgba8(clear_color);
+        Self {
+            width,
+            height,
+            data: pixel
+                .iter()
+                .copied()
+                .cycle()
+                .take(width as usize * height as usize * 4)
+                .collect(),
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Raw RGBA bytes.
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    pub fn pixel(&self, x: u32, y: u32) -> [u8; 4] {
+        let i = (y as usize * self.width as usize + x as usize) * 4;
+        self.data[i..i + 4].try_into().unwrap()
+    }
+
+    /// Sets a pixel. Coordinates outside the image are ignored.
+    pub fn set_pixel(&mut self, x: i64, y: i64, color: [u8; 4]) {
+        if x < 0 || y < 0 || x >= self.width as i64 || y >= self.height as i64 {
+            return;
+        }
+
+        let i = ((y as usize * self.width as usize) + x as usize) * 4;
+        self.data[i..i + 4].copy_from_slice(&color);
+    }
+
+    /// Draws a point the way OpenGL rasterizes `PrimitiveType::Points`: a square `size` pixels
+    /// wide, centered on `(x, y)`, covering every pixel whose center lies inside it.
+    pub fn draw_point(&mut self, x: f32, y: f32, size: f32, color: [u8; 4]) {
+        let half = size / 2.0;
+        let x0 = (x - half - 0.5).ceil() as i64;
+        let x1 = (x + half - 0.5).ceil() as i64;
+        let y0 = (y - half - 0.5).ceil() as i64;
+        let y1 = (y + half - 0.5).ceil() as i64;
+
+        for py in y0..y1.max(y0 + 1) {
+            for px in x0..x1.max(x0 + 1) {
+                self.set_pixel(px, py, color);
+            }
+        }
+    }
+
+    /// Converts normalized device coordinates (-1.0 to 1.0, y pointing up) to pixel coordinates.
+    pub fn ndc_to_pixel(&self, x: f32, y: f32) -> (f32, f32) {
+        (
+            (x + 1.0) / 2.0 * self.width as f32,
+            (1.0 - y) / 2.0 * self.height as f32,
+        )
+    }
+
+    pub fn write_png<W: Write>(&self, w: W) -> io::Result<()> {
+        let mut encoder = png::Encoder::new(w, self.width, self.height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&self.data)?;
+        Ok(())
+    }
+
+    pub fn save_png<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        self.write_png(BufWriter::new(File::create(path)?))
+    }
+
+    /// Decodes a PNG file of any color type, converting it to RGBA.
+    pub fn read_png<R: Read>(r: R) -> io::Result<Self> {
+        let mut decoder = png::Decoder::new(r);
+        // Expand palettes and low bit depths, and reduce 16 bit channels to 8 bits
+        decoder.set_transformations(png::Transformations::normalize_to_color8());
+
+        let mut reader = decoder
+            .read_info()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        let mut buf = vec![0; reader.output_buffer_size()];
+        let info = reader
+            .next_frame(&mut buf)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        buf.truncate(info.buffer_size());
+
+        let data = match info.color_type {
+            png::ColorType::Rgba => buf,
+            png::ColorType::Rgb => buf
+                .chunks(3)
+                .flat_map(|p| [p[0], p[1], p[2], 255])
+                .collect(),
+            png::ColorType::GrayscaleAlpha => buf
+                .chunks(2)
+                .flat_map(|p| [p[0], p[0], p[0], p[1]])
+                .collect(),
+            png::ColorType::Grayscale => buf.iter().flat_map(|&g| [g, g, g, 255]).collect(),
+            png::ColorType::Indexed => unreachable!("palettes are expanded by the decoder"),
+        };
+
+        Ok(Self {
+            width: info.width,
+            height: info.height,
+            data,
+        })
+    }
+
+    pub fn load_png<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Self::read_png(BufReader::new(File::open(path)?))
+    }
+}
+
+/// Writes images as the frames of an animated GIF that loops forever. Colors are reduced to a
+/// palette of 256 per frame.
+pub struct GifWriter<W: Write> {
+    encoder: gif::Encoder<W>,
+    width: u16,
+    height: u16,
+    /// Time each frame is shown, in hundredths of a second.
+    delay: u16,
+}
+
+impl<W: Write> GifWriter<W> {
+    /// Starts an animation of `width` x `height` frames, each shown for `delay_ms` milliseconds.
+    /// GIF only supports delays in steps of 10 ms.
+    pub fn new(w: W, width: u32, height: u32, delay_ms: u32) -> io::Result<Self> {
+        let too_large = || io::Error::new(io::ErrorKind::InvalidInput, "GIF frames are too large");
+        let width = u16::try_from(width).map_err(|_| too_large())?;
+        let height = u16::try_from(height).map_err(|_| too_large())?;
+
+        let mut encoder = gif::Encoder::new(w, width, height, &[]).map_err(io::Error::other)?;
+        encoder
+            .set_repeat(gif::Repeat::Infinite)
+            .map_err(io::Error::other)?;
+
+        Ok(Self {
+            encoder,
+            width,
+            height,
+            delay: (delay_ms / 10).clamp(1, u16::MAX as u32) as u16,
+        })
+    }
+
+    /// Adds a frame, which must have the size given to [`GifWriter::new`].
+    pub fn write_frame(&mut self, image: &Image) -> io::Result<()> {
+        if (image.width, image.height) != (self.width as u32, self.height as u32) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "frame size does not match the animation",
+            ));
+        }
+
+        let mut data = image.data.clone();
+        let mut frame = gif::Frame::from_rgba_speed(self.width, self.height, &mut data, 10);
+        frame.delay = self.delay;
+        self.encoder.write_frame(&frame).map_err(io::Error::other)
+    }
+
+    /// Ends the animation. Dropping the writer does the same, but ignores errors.
+    pub fn finish(self) -> io::Result<()> {
+        self.encoder.into_inner()?.flush()
+    }
+}
+
+impl GifWriter<BufWriter<File>> {
+    pub fn create<P: AsRef<Path>>(
+        path: P,
+        width: u32,
+        height: u32,
+        delay_ms: u32,
+    ) -> io::Result<Self> {
+        Self::new(BufWriter::new(File::create(path)?), width, height, delay_ms)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use std::io::Cursor;
+    use std::path::PathBuf;
+
+    use crate::image::{GifWriter, Image};
+
+    fn golden_path(name: &str) -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("src/testdata")
+            .join(format!("{}.png", name))
+    }
+
+    /// Checks `image` against `src/testdata/<name>.png`. Running the tests with `UPDATE_GOLDEN`
+    /// set writes the image there instead.
+    pub fn assert_golden(image: &Image, name: &str) {
+        let path = golden_path(name);
+        if std::env::var_os("UPDATE_GOLDEN").is_some() {
+            image.save_png(&path).unwrap();
+            return;
+        }
+
+        let golden = Image::load_png(&path).unwrap();
+        assert_eq!(
+            (image.width(), image.height()),
+            (golden.width(), golden.height()),
+            "{} has the wrong size",
+            name
+        );
+        let differing = (0..golden.height())
+            .flat_map(|y| (0..golden.width()).map(move |x| (x, y)))
+            .filter(|&(x, y)| image.pixel(x, y) != golden.pixel(x, y))
+            .count();
+        assert_eq!(differing, 0, "{} differs in {} pixels", name, differing);
+    }
+
+    fn points() -> Image {
+        let mut image = Image::new(16, 12, [0.1, 0.1, 0.2, 1.0]);
+        image.draw_point(2.0, 2.0, 1.0, [255, 0, 0, 255]);
+        image.draw_point(6.5, 3.5, 2.0, [0, 255, 0, 255]);
+        image.draw_point(12.0, 4.0, 3.0, [0, 0, 255, 255]);
+        image.draw_point(4.3, 9.7, 4.5, [255, 255, 0, 255]);
+        // Partly outside the image
+        image.draw_point(15.5, 11.5, 3.0, [255, 255, 255, 255]);
+        image
+    }
+
+    fn gradient() -> Image {
+        let mut image = Image::new(16, 12, [0.0, 0.0, 0.0, 1.0]);
+        for y in 0..12 {
+            for x in 0..16 {
+                image.set_pixel(x, y, [x as u8 * 16, y as u8 * 20, 128, 255]);
+            }
+        }
+        image
+    }
+
+    #[test]
+    fn draw_points() {
+        assert_golden(&points(), "points");
+    }
+
+    #[test]
+    fn png_round_trip() {
+        let image = gradient();
+        let mut png = vec![];
+        image.write_png(&mut png).unwrap();
+        assert_eq!(Image::read_png(Cursor::new(png)).unwrap(), image);
+    }
+
+    #[test]
+    fn gif_frames() {
+        let frames = [points(), gradient()];
+        let mut gif = vec![];
+        let mut writer = GifWriter::new(&mut gif, 16, 12, 50).unwrap();
+        for frame in &frames {
+            writer.write_frame(frame).unwrap();
+        }
+        assert!(writer.write_frame(&Image::new(8, 8, [0.0; 4])).is_err());
+        writer.finish().unwrap();
+
+        let mut options = gif::DecodeOptions::new();
+        options.set_color_output(gif::ColorOutput::RGBA);
+        let mut decoder = options.read_info(Cursor::new(gif)).unwrap();
+        assert_eq!(decoder.repeat(), gif::Repeat::Infinite);
+
+        // Both frames have fewer than 256 colors, so they come back unchanged
+        for (i, name) in ["points", "gradient"].into_iter().enumerate() {
+            let frame = decoder.read_next_frame().unwrap().unwrap();
+            assert_eq!(frame.delay, 5);
+            let image = Image {
+                width: frame.width as u32,
+                height: frame.height as u32,
+                data: frame.buffer.to_vec(),
+            };
+            assert_eq!(image, frames[i]);
+            assert_golden(&image, name);
+        }
+        assert!(decoder.read_next_frame().unwrap().is_none());
+    }
+}
@@ -1,6 +1,12 @@
 pub mod args;
+pub mod cantor;
+pub mod dimension;
+pub mod escape_time;
 pub mod forest;
 pub mod ifs;
+pub mod image;
+pub mod lsystem;
 pub mod shader_builder;
+pub mod svg;
 pub mod utils;
 pub mod vertex;
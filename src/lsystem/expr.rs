@@ -0,0 +1,220 @@
+//! Arithmetic on the parameters of a module, for parametric rules.
+//!
+//! Expressions combine numbers and the formal parameters of a rule with `+ - * / ^`, unary
+//! minus and parentheses. Conditions also compare with `< <= > >= == !=`, which evaluate to 1 if
+//! they hold and to 0 otherwise.
+
+use std::iter::Peekable;
+use std::str::Chars;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Pow,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    Equal,
+    NotEqual,
+}
+
+impl BinaryOp {
+    fn apply(&self, a: f32, b: f32) -> f32 {
+        let truth = |holds: bool| if holds { 1.0 } else { 0.0 };
+        match self {
+            BinaryOp::Add => a + b,
+            BinaryOp::Sub => a - b,
+            BinaryOp::Mul => a * b,
+            BinaryOp::Div => a / b,
+            BinaryOp::Pow => a.powf(b),
+            BinaryOp::Less => truth(a < b),
+            BinaryOp::LessEqual => truth(a <= b),
+            BinaryOp::Greater => truth(a > b),
+            BinaryOp::GreaterEqual => truth(a >= b),
+            BinaryOp::Equal => truth(a == b),
+            BinaryOp::NotEqual => truth(a != b),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Expr {
+    Number(f32),
+    /// The formal parameter with this index.
+    Param(usize),
+    Neg(Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+}
+
+impl Expr {
+    /// Parses `s`, resolving identifiers to their index in `formals`.
+    pub fn parse(s: &str, formals: &[String]) -> Result<Self, String> {
+        let mut parser = Parser {
+            chars: s.chars().peekable(),
+            formals,
+        };
+        let expr = parser.comparison()?;
+        parser.skip_whitespace();
+        match parser.chars.next() {
+            None => Ok(expr),
+            Some(c) => Err(format!("unexpected '{}' in '{}'", c, s)),
+        }
+    }
+
+    /// Value of the expression with the formal parameters bound to `params`.
+    pub fn eval(&self, params: &[f32]) -> f32 {
+        match self {
+            Expr::Number(v) => *v,
+            Expr::Param(i) => params[*i],
+            Expr::Neg(e) => -e.eval(params),
+            Expr::Binary(op, a, b) => op.apply(a.eval(params), b.eval(params)),
+        }
+    }
+}
+
+/// Recursive descent parser, one method per precedence level.
+struct Parser<'a> {
+    chars: Peekable<Chars<'a>>,
+    formals: &'a [String],
+}
+
+impl<'a> Parser<'a> {
+    fn skip_whitespace(&mut self) {
+        while self.chars.next_if(|c| c.is_whitespace()).is_some() {}
+    }
+
+    fn comparison(&mut self) -> Result<Expr, String> {
+        let left = self.sum()?;
+        self.skip_whitespace();
+        let op = match self.chars.next_if(|c| "<>=!".contains(*c)) {
+            None => return Ok(left),
+            Some(c) => {
+                let equals = self.chars.next_if_eq(&'=').is_some();
+                match (c, equals) {
+                    ('<', false) => BinaryOp::Less,
+                    ('<', true) => BinaryOp::LessEqual,
+                    ('>', false) => BinaryOp::Greater,
+                    ('>', true) => BinaryOp::GreaterEqual,
+                    ('=', true) => BinaryOp::Equal,
+                    ('!', true) => BinaryOp::NotEqual,
+                    _ => return Err(format!("expected '{}='", c)),
+                }
+            }
+        };
+        let right = self.sum()?;
+        Ok(Expr::Binary(op, Box::new(left), Box::new(right)))
+    }
+
+    fn sum(&mut self) -> Result<Expr, String> {
+        let mut left = self.product()?;
+        loop {
+            self.skip_whitespace();
+            let op = match self.chars.next_if(|c| *c == '+' || *c == '-') {
+                Some('+') => BinaryOp::Add,
+                Some(_) => BinaryOp::Sub,
+                None => return Ok(left),
+            };
+            left = Expr::Binary(op, Box::new(left), Box::new(self.product()?));
+        }
+    }
+
+    fn product(&mut self) -> Result<Expr, String> {
+        let mut left = self.power()?;
+        loop {
+            self.skip_whitespace();
+            let op = match self.chars.next_if(|c| *c == '*' || *c == '/') {
+                Some('*') => BinaryOp::Mul,
+                Some(_) => BinaryOp::Div,
+                None => return Ok(left),
+            };
+            left = Expr::Binary(op, Box::new(left), Box::new(self.power()?));
+        }
+    }
+
+    /// `^` binds tighter than unary minus on its left and is right associative.
+    fn power(&mut self) -> Result<Expr, String> {
+        self.skip_whitespace();
+        if self.chars.next_if_eq(&'-').is_some() {
+            return Ok(Expr::Neg(Box::new(self.power()?)));
+        }
+
+        let base = self.atom()?;
+        self.skip_whitespace();
+        if self.chars.next_if_eq(&'^').is_some() {
+            let exponent = self.power()?;
+            return Ok(Expr::Binary(
+                BinaryOp::Pow,
+                Box::new(base),
+                Box::new(exponent),
+            ));
+        }
+        Ok(base)
+    }
+
+    fn atom(&mut self) -> Result<Expr, String> {
+        self.skip_whitespace();
+        match self.chars.peek().copied() {
+            Some('(') => {
+                self.chars.next();
+                let expr = self.comparison()?;
+                self.skip_whitespace();
+                match self.chars.next() {
+                    Some(')') => Ok(expr),
+                    _ => Err(String::from("missing ')'")),
+                }
+            }
+            Some(c) if c.is_ascii_digit() || c == '.' => {
+                let mut number = String::new();
+                while let Some(c) = self.chars.next_if(|c| c.is_ascii_digit() || *c == '.') {
+                    number.push(c);
+                }
+                number
+                    .parse()
+                    .map(Expr::Number)
+                    .map_err(|_| format!("invalid number '{}'", number))
+            }
+            Some(c) if c.is_alphabetic() || c == '_' => {
+                let mut name = String::new();
+                while let Some(c) = self.chars.next_if(|c| c.is_alphanumeric() || *c == '_') {
+                    name.push(c);
+                }
+                self.formals
+                    .iter()
+                    .position(|formal| *formal == name)
+                    .map(Expr::Param)
+                    .ok_or_else(|| format!("unknown parameter '{}'", name))
+            }
+            Some(c) => Err(format!("unexpected '{}'", c)),
+            None => Err(String::from("expression ends too early")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::lsystem::expr::Expr;
+
+    #[test]
+    fn evaluate() {
+        let formals = vec![String::from("x"), String::from("len")];
+        let eval = |s: &str| Expr::parse(s, &formals).unwrap().eval(&[2.0, 10.0]);
+
+        assert_eq!(eval("1 + 2 * 3"), 7.0);
+        assert_eq!(eval("(1 + 2) * 3"), 9.0);
+        assert_eq!(eval("len / x - 1"), 4.0);
+        assert_eq!(eval("-x ^ 2"), -4.0);
+        assert_eq!(eval("2 ^ 3 ^ 2"), 512.0);
+        assert_eq!(eval("x * 0.5 >= 1"), 1.0);
+        assert_eq!(eval("len < x"), 0.0);
+        assert_eq!(eval("x != 2"), 0.0);
+
+        assert!(Expr::parse("y + 1", &formals).is_err());
+        assert!(Expr::parse("(x + 1", &formals).is_err());
+        assert!(Expr::parse("x = 1", &formals).is_err());
+        assert!(Expr::parse("x 1", &formals).is_err());
+    }
+}
@@ -0,0 +1,448 @@
+//! Lindenmayer systems: strings of symbols that grow by rewriting every symbol at once.
+//!
+//! A definition lists the starting string and the rules that rewrite it, one directive per line.
+//! Blank lines and everything after a `#` are ignored:
+//!
+//! ```text
+//! # Koch curve
+//! axiom F
+//! angle 60
+//! iterations 4
+//! rule F -> F+F--F+F
+//! ```
+//!
+//! * `axiom <modules>` - the string before the first rewrite.
+//! * `angle <degrees>` - how far `+` and `-` turn the turtle, see [`turtle`] (default 90).
+//! * `heading <degrees>` - direction the turtle starts in, counterclockwise from the positive x
+//!   axis (default 0).
+//! * `iterations <n>` - number of rewrites (default 4).
+//! * `rule <predecessor> [: <condition>] -> <successor> [: <weight>]` - replaces every module
+//!   matching the predecessor by the successor.
+//!
+//! Every symbol is a single character and may carry parameters, e.g. `F(0.5)`. Rules for
+//! parametric modules name the parameters in the predecessor and compute new ones in the
+//! successor, see [`expr`] for the syntax:
+//!
+//! ```text
+//! rule A(s) : s > 0.1 -> F(s)[+A(s * 0.6)][-A(s * 0.6)]
+//! ```
+//!
+//! A rule only applies to modules with as many parameters as its predecessor, and only if its
+//! condition is nonzero. If several rules apply to a module, one of them is picked at random in
+//! proportion to their weights (default 1), which makes the system stochastic. Modules no rule
+//! applies to are copied as they are.
+
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use rand::distributions::{Distribution, WeightedIndex};
+use rand::Rng;
+
+use crate::ifs::format::{read_source, ParseError};
+use crate::lsystem::expr::Expr;
+
+pub mod expr;
+pub mod turtle;
+
+/// The bundled `.lsys` definitions by name, sorted alphabetically.
+const EXAMPLES: &[(&str, &str)] = &[
+    ("dragon", include_str!("examples/dragon.lsys")),
+    ("hilbert", include_str!("examples/hilbert.lsys")),
+    ("koch", include_str!("examples/koch.lsys")),
+    ("plant", include_str!("examples/plant.lsys")),
+    ("tree", include_str!("examples/tree.lsys")),
+];
+
+/// Names of the bundled definitions, sorted alphabetically.
+pub fn example_names() -> Vec<String> {
+    EXAMPLES.iter().map(|(name, _)| name.to_string()).collect()
+}
+
+/// A symbol with its parameters.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Module {
+    pub symbol: char,
+    pub params: Vec<f32>,
+}
+
+impl Module {
+    pub fn new(symbol: char) -> Self {
+        Self {
+            symbol,
+            params: vec![],
+        }
+    }
+}
+
+impl Display for Module {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.symbol)?;
+        if !self.params.is_empty() {
+            let params: Vec<String> = self.params.iter().map(|p| p.to_string()).collect();
+            write!(f, "({})", params.join(","))?;
+        }
+        Ok(())
+    }
+}
+
+/// A module in a successor, whose parameters are computed from the ones of the predecessor.
+#[derive(Clone, Debug, PartialEq)]
+struct ModuleTemplate {
+    symbol: char,
+    args: Vec<Expr>,
+}
+
+impl ModuleTemplate {
+    fn instantiate(&self, params: &[f32]) -> Module {
+        Module {
+            symbol: self.symbol,
+            params: self.args.iter().map(|arg| arg.eval(params)).collect(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Rule {
+    predecessor: char,
+    formals: Vec<String>,
+    condition: Option<Expr>,
+    successor: Vec<ModuleTemplate>,
+    weight: f32,
+}
+
+impl Rule {
+    fn applies_to(&self, module: &Module) -> bool {
+        self.predecessor == module.symbol
+            && self.formals.len() == module.params.len()
+            && self
+                .condition
+                .as_ref()
+                .is_none_or(|c| c.eval(&module.params) != 0.0)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct LSystem {
+    pub axiom: Vec<Module>,
+    pub rules: Vec<Rule>,
+    /// Turning angle in degrees.
+    pub angle: f32,
+    /// Initial heading in degrees.
+    pub heading: f32,
+    pub iterations: usize,
+}
+
+impl Default for LSystem {
+    fn default() -> Self {
+        Self {
+            axiom: vec![],
+            rules: vec![],
+            angle: 90.0,
+            heading: 0.0,
+            iterations: 4,
+        }
+    }
+}
+
+impl LSystem {
+    /// Loads a definition from a file path, or from the bundled examples if `name` is not a path
+    /// to an existing file.
+    pub fn load(name: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        Ok(read_source(name, EXAMPLES)?.parse()?)
+    }
+
+    /// Rewrites every module of `modules` once. `rng` picks between rules that apply to the same
+    /// module.
+    pub fn rewrite<R: Rng + ?Sized>(&self, modules: &[Module], rng: &mut R) -> Vec<Module> {
+        let mut ret = Vec::with_capacity(modules.len());
+        for module in modules {
+            let rules: Vec<&Rule> = self.rules.iter().filter(|r| r.applies_to(module)).collect();
+            let rule = match rules[..] {
+                [] => {
+                    ret.push(module.clone());
+                    continue;
+                }
+                [rule] => rule,
+                _ => {
+                    // Weights were checked to be positive when parsing
+                    let weights = WeightedIndex::new(rules.iter().map(|r| r.weight)).unwrap();
+                    rules[weights.sample(rng)]
+                }
+            };
+            ret.extend(
+                rule.successor
+                    .iter()
+                    .map(|template| template.instantiate(&module.params)),
+            );
+        }
+        ret
+    }
+
+    /// The axiom rewritten `iterations` times.
+    pub fn generate<R: Rng + ?Sized>(&self, iterations: usize, rng: &mut R) -> Vec<Module> {
+        let mut modules = self.axiom.clone();
+        for _ in 0..iterations {
+            modules = self.rewrite(&modules, rng);
+        }
+        modules
+    }
+}
+
+/// Parses a string of modules like `F(x * 2)[+F]`, with parameters computed from `formals`.
+fn parse_modules(s: &str, formals: &[String]) -> Result<Vec<ModuleTemplate>, String> {
+    let mut ret = vec![];
+    let mut chars = s.chars().filter(|c| !c.is_whitespace()).peekable();
+    while let Some(symbol) = chars.next() {
+        if "(),:".contains(symbol) {
+            return Err(format!("unexpected '{}'", symbol));
+        }
+
+        let mut args = vec![];
+        if chars.next_if_eq(&'(').is_some() {
+            // Split at commas outside of nested parentheses
+            let mut depth = 0;
+            let mut arg = String::new();
+            loop {
+                match chars.next() {
+                    None => return Err(format!("missing ')' after '{}'", symbol)),
+                    Some(')') if depth == 0 => break,
+                    Some(',') if depth == 0 => {
+                        args.push(Expr::parse(&arg, formals)?);
+                        arg.clear();
+                    }
+                    Some(c) => {
+                        match c {
+                            '(' => depth += 1,
+                            ')' => depth -= 1,
+                            _ => (),
+                        }
+                        arg.push(c);
+                    }
+                }
+            }
+            args.push(Expr::parse(&arg, formals)?);
+        }
+
+        ret.push(ModuleTemplate { symbol, args });
+    }
+    Ok(ret)
+}
+
+/// Parses a predecessor like `A(x, y)` into its symbol and formal parameters.
+fn parse_predecessor(s: &str) -> Result<(char, Vec<String>), String> {
+    let s = s.trim();
+    let mut chars = s.chars();
+    let symbol = chars
+        .next()
+        .ok_or_else(|| String::from("missing predecessor"))?;
+    let rest = chars.as_str().trim();
+    if rest.is_empty() {
+        return Ok((symbol, vec![]));
+    }
+
+    let formals = rest
+        .strip_prefix('(')
+        .and_then(|rest| rest.strip_suffix(')'))
+        .ok_or_else(|| format!("predecessor '{}' is not a single module", s))?;
+    let formals: Vec<String> = formals.split(',').map(|f| f.trim().to_owned()).collect();
+    for formal in &formals {
+        let mut chars = formal.chars();
+        let valid = chars.next().is_some_and(|c| c.is_alphabetic() || c == '_')
+            && chars.all(|c| c.is_alphanumeric() || c == '_');
+        if !valid {
+            return Err(format!("invalid parameter name '{}'", formal));
+        }
+    }
+    Ok((symbol, formals))
+}
+
+/// Parses everything after `rule`.
+fn parse_rule(s: &str) -> Result<Rule, String> {
+    let (left, right) = s
+        .split_once("->")
+        .ok_or_else(|| String::from("'rule' expects '<predecessor> -> <successor>'"))?;
+
+    let (predecessor, condition) = match left.split_once(':') {
+        Some((predecessor, condition)) => (predecessor, Some(condition)),
+        None => (left, None),
+    };
+    let (predecessor, formals) = parse_predecessor(predecessor)?;
+    let condition = condition.map(|c| Expr::parse(c, &formals)).transpose()?;
+
+    let (successor, weight) = match right.split_once(':') {
+        Some((successor, weight)) => {
+            let weight: f32 = weight
+                .trim()
+                .parse()
+                .map_err(|_| format!("invalid weight '{}'", weight.trim()))?;
+            if weight.is_nan() || weight <= 0.0 {
+                return Err(String::from("rule weights must be positive"));
+            }
+            (successor, weight)
+        }
+        None => (right, 1.0),
+    };
+
+    Ok(Rule {
+        predecessor,
+        successor: parse_modules(successor, &formals)?,
+        formals,
+        condition,
+        weight,
+    })
+}
+
+impl FromStr for LSystem {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut ret = LSystem::default();
+        let mut axiom = None;
+
+        for (i, line) in s.lines().enumerate() {
+            let line_no = i + 1;
+            let line = line.split('#').next().unwrap().trim();
+            let (directive, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+            let error = |message: String| ParseError {
+                line: line_no,
+                message,
+            };
+            let number = |rest: &str| {
+                rest.trim()
+                    .parse::<f32>()
+                    .map_err(|_| error(format!("invalid number '{}'", rest.trim())))
+            };
+
+            match directive {
+                "" => continue,
+                "axiom" => {
+                    let modules = parse_modules(rest, &[]).map_err(error)?;
+                    axiom = Some(modules.iter().map(|m| m.instantiate(&[])).collect());
+                }
+                "angle" => ret.angle = number(rest)?,
+                "heading" => ret.heading = number(rest)?,
+                "iterations" => {
+                    ret.iterations = rest
+                        .trim()
+                        .parse()
+                        .map_err(|_| error(format!("invalid number '{}'", rest.trim())))?;
+                }
+                "rule" => ret.rules.push(parse_rule(rest).map_err(error)?),
+                _ => return Err(error(format!("unknown directive '{}'", directive))),
+            }
+        }
+
+        ret.axiom = axiom.ok_or_else(|| ParseError {
+            line: s.lines().count(),
+            message: String::from("no axiom defined"),
+        })?;
+        Ok(ret)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::lsystem::{example_names, LSystem, Module};
+    use crate::utils::rng::seeded_rng;
+
+    fn to_string(modules: &[Module]) -> String {
+        modules.iter().map(|m| m.to_string()).collect()
+    }
+
+    #[test]
+    fn deterministic() {
+        let system: LSystem = "
+            axiom F
+            angle 60
+            rule F -> F+F--F+F
+        "
+        .parse()
+        .unwrap();
+        assert_eq!(system.angle, 60.0);
+
+        let mut rng = seeded_rng(Some(1));
+        assert_eq!(to_string(&system.generate(0, &mut rng)), "F");
+        assert_eq!(to_string(&system.generate(1, &mut rng)), "F+F--F+F");
+        assert_eq!(
+            system.generate(3, &mut rng).len(),
+            4 * 4 * 4 + 4 * (1 + 4 + 16)
+        );
+    }
+
+    #[test]
+    fn stochastic() {
+        let system: LSystem = "
+            axiom AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA
+            rule A -> B : 3
+            rule A -> C
+        "
+        .parse()
+        .unwrap();
+
+        let generated = to_string(&system.generate(1, &mut seeded_rng(Some(1))));
+        let b = generated.matches('B').count();
+        assert_eq!(b + generated.matches('C').count(), 40);
+        assert!((20..40).contains(&b), "{}", generated);
+
+        let again = to_string(&system.generate(1, &mut seeded_rng(Some(1))));
+        assert_eq!(generated, again);
+    }
+
+    #[test]
+    fn parametric() {
+        let system: LSystem = "
+            axiom A(1, 2)
+            rule A(s, t) : s < 4 -> F(s)[+A(s * 2, t - 1)]
+        "
+        .parse()
+        .unwrap();
+
+        let generated = system.generate(4, &mut seeded_rng(Some(1)));
+        // The condition stops the growth once s reaches 4
+        assert_eq!(to_string(&generated), "F(1)[+F(2)[+A(4,0)]]");
+    }
+
+    #[test]
+    fn parse_errors() {
+        let err = "axiom F\nrule F F".parse::<LSystem>().unwrap_err();
+        assert_eq!(err.line, 2);
+
+        let err = "axiom F\nrule A(x) -> A(y)".parse::<LSystem>().unwrap_err();
+        assert_eq!(err.line, 2);
+
+        let err = "axiom F(\n".parse::<LSystem>().unwrap_err();
+        assert_eq!(err.line, 1);
+
+        let err = "axiom F\nrule F -> G : 0".parse::<LSystem>().unwrap_err();
+        assert_eq!(err.line, 2);
+
+        let err = "rule F -> G".parse::<LSystem>().unwrap_err();
+        assert_eq!(err.message, "no axiom defined");
+    }
+
+    #[test]
+    fn examples_parse() {
+        let names = example_names();
+        for name in ["dragon", "hilbert", "koch", "plant"] {
+            assert!(names.contains(&name.to_owned()), "{:?}", names);
+        }
+        for name in names {
+            LSystem::load(&name).unwrap_or_else(|e| panic!("{}: {}", name, e));
+        }
+    }
+
+    #[test]
+    fn examples_are_bundled() {
+        let dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("src/lsystem/examples");
+        let mut files: Vec<String> = dir
+            .read_dir()
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "lsys"))
+            .map(|path| path.file_stem().unwrap().to_str().unwrap().to_owned())
+            .collect();
+        files.sort();
+
+        assert_eq!(example_names(), files);
+    }
+}
@@ -0,0 +1,145 @@
+//! Drawing the modules of an L-system with a turtle.
+//!
+//! The turtle walks the modules from left to right:
+//!
+//! * `F` and `G` - move forward one step, or as far as the first parameter says, drawing a line.
+//! * `f` - move forward without drawing.
+//! * `+` and `-` - turn left (counterclockwise) or right by the angle, or by the first parameter
+//!   in degrees.
+//! * `|` - turn around.
+//! * `[` and `]` - save the position and heading, and go back to the last saved one. This is how
+//!   branches are drawn.
+//!
+//! Every other symbol is ignored, so it can stand for something that is only rewritten.
+
+use crate::lsystem::{LSystem, Module};
+use crate::vertex::{Line, Vertex};
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Turtle {
+    /// Length of a step without parameters.
+    pub step: f32,
+    /// Turning angle in degrees.
+    pub angle: f32,
+    /// Initial heading in degrees, counterclockwise from the positive x axis.
+    pub heading: f32,
+}
+
+impl Turtle {
+    /// A turtle with the angles of `system` and unit steps.
+    pub fn for_system(system: &LSystem) -> Self {
+        Self {
+            step: 1.0,
+            angle: system.angle,
+            heading: system.heading,
+        }
+    }
+
+    /// Lines drawn by walking `modules`, starting at the origin. An unmatched `]` is ignored.
+    pub fn draw(&self, modules: &[Module]) -> Vec<Line> {
+        let mut lines = vec![];
+        let mut position = [0.0_f32, 0.0];
+        let mut heading = self.heading;
+        let mut stack = vec![];
+
+        for module in modules {
+            let param = module.params.first().copied();
+            match module.symbol {
+                'F' | 'G' | 'f' => {
+                    let length = param.unwrap_or(self.step);
+                    let (sin, cos) = heading.to_radians().sin_cos();
+                    let next = [position[0] + length * cos, position[1] + length * sin];
+                    if module.symbol != 'f' {
+                        lines.push(Line(position.into(), next.into()));
+                    }
+                    position = next;
+                }
+                '+' => heading += param.unwrap_or(self.angle),
+                '-' => heading -= param.unwrap_or(self.angle),
+                '|' => heading += 180.0,
+                '[' => stack.push((position, heading)),
+                ']' => {
+                    if let Some(saved) = stack.pop() {
+                        (position, heading) = saved;
+                    }
+                }
+                _ => (),
+            }
+        }
+        lines
+    }
+}
+
+/// Scales and moves `lines` uniformly so that they fill the square from -`extent` to `extent`
+/// along their longer side, centered in it. This is what the line pipeline of
+/// [`cantor`](crate::cantor) draws in normalized device coordinates.
+pub fn fit_lines(lines: &mut [Line], extent: f32) {
+    let positions = lines.iter().flat_map(|Line(a, b)| [a.position, b.position]);
+    let (mut x_min, mut x_max, mut y_min, mut y_max) = (f32::MAX, f32::MIN, f32::MAX, f32::MIN);
+    for [x, y] in positions {
+        (x_min, x_max) = (x_min.min(x), x_max.max(x));
+        (y_min, y_max) = (y_min.min(y), y_max.max(y));
+    }
+
+    let size = (x_max - x_min).max(y_max - y_min);
+    if lines.is_empty() || size <= 0.0 {
+        return;
+    }
+
+    let scale = 2.0 * extent / size;
+    let (cx, cy) = ((x_min + x_max) / 2.0, (y_min + y_max) / 2.0);
+    let fit = |v: &mut Vertex| {
+        let [x, y] = v.position;
+        v.position = [(x - cx) * scale, (y - cy) * scale];
+    };
+    for Line(a, b) in lines.iter_mut() {
+        fit(a);
+        fit(b);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::lsystem::turtle::{fit_lines, Turtle};
+    use crate::lsystem::LSystem;
+    use crate::utils::rng::seeded_rng;
+    use crate::vertex::Line;
+
+    fn close(a: [f32; 2], b: [f32; 2]) -> bool {
+        (a[0] - b[0]).abs() < 1e-5 && (a[1] - b[1]).abs() < 1e-5
+    }
+
+    #[test]
+    fn branches() {
+        let system: LSystem = "axiom F+F[+F]f(2)-F(0.5)".parse().unwrap();
+        let turtle = Turtle::for_system(&system);
+        let lines = turtle.draw(&system.axiom);
+
+        let ends: Vec<[f32; 2]> = lines.iter().map(|Line(_, b)| b.position).collect();
+        let expected = [[1.0, 0.0], [1.0, 1.0], [0.0, 1.0], [1.5, 3.0]];
+        assert_eq!(lines.len(), expected.len());
+        for (end, expected) in ends.iter().zip(expected) {
+            assert!(close(*end, expected), "{:?}", ends);
+        }
+        // The branch starts where the turtle was before it
+        assert!(close(lines[2].0.position, [1.0, 1.0]));
+        // f moves without drawing
+        assert!(close(lines[3].0.position, [1.0, 3.0]));
+    }
+
+    #[test]
+    fn fits_square() {
+        let system = LSystem::load("dragon").unwrap();
+        let modules = system.generate(8, &mut seeded_rng(Some(1)));
+        let mut lines = Turtle::for_system(&system).draw(&modules);
+        fit_lines(&mut lines, 0.9);
+
+        let coords: Vec<f32> = lines
+            .iter()
+            .flat_map(|Line(a, b)| [a.position, b.position])
+            .flatten()
+            .collect();
+        let max = coords.iter().fold(0.0_f32, |m, c| m.max(c.abs()));
+        assert!((max - 0.9).abs() < 1e-4, "{}", max);
+    }
+}
@@ -0,0 +1,224 @@
+//! Vector output for print-quality figures.
+//!
+//! Geometry is given in normalized device coordinates, like everything that is drawn with
+//! OpenGL, and scaled to a `width` x `height` document.
+
+use std::collections::{BTreeMap, HashSet};
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use crate::image::to_rgba8;
+use crate::vertex::Line;
+
+/// Color as `#rrggbb`, plus the alpha channel between 0.0 and 1.0.
+fn svg_color(color: [f32; 4]) -> (String, f32) {
+    let [r, g, b, _] = to_rgba8(color);
+    (
+        format!("#{:02x}{:02x}{:02x}", r, g, b),
+        color[3].clamp(0.0, 1.0),
+    )
+}
+
+fn opacity_attribute(name: &str, alpha: f32) -> String {
+    if alpha < 1.0 {
+        format!(" {}=\"{}\"", name, alpha)
+    } else {
+        String::new()
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Svg {
+    width: u32,
+    height: u32,
+    background: Option<[f32; 4]>,
+    /// Elements in the order they are drawn.
+    body: String,
+}
+
+impl Svg {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            background: None,
+            body: String::new(),
+        }
+    }
+
+    /// Fills the document with `color` before anything else. Without a background it is
+    /// transparent.
+    pub fn set_background(&mut self, color: [f32; 4]) {
+        self.background = Some(color);
+    }
+
+    /// Converts normalized device coordinates (-1.0 to 1.0, y pointing up) to document
+    /// coordinates, rounded to a hundredth of a pixel to keep the file small.
+    fn ndc_to_document(&self, x: f32, y: f32) -> (f32, f32) {
+        let round = |v: f32| (v * 100.0).round() / 100.0;
+        (
+            round((x + 1.0) / 2.0 * self.width as f32),
+            round((1.0 - y) / 2.0 * self.height as f32),
+        )
+    }
+
+    /// Adds line segments as a single path, `stroke_width` pixels wide.
+    pub fn add_lines(&mut self, lines: &[Line], color: [f32; 4], stroke_width: f32) {
+        let mut d = String::new();
+        for Line(a, b) in lines {
+            let (x0, y0) = self.ndc_to_document(a.x(), a.y());
+            let (x1, y1) = self.ndc_to_document(b.x(), b.y());
+            write!(d, "M{} {}L{} {}", x0, y0, x1, y1).unwrap();
+        }
+
+        let (stroke, alpha) = svg_color(color);
+        writeln!(
+            self.body,
+            "<path d=\"{}\" fill=\"none\" stroke=\"{}\" stroke-width=\"{}\"{}/>",
+            d,
+            stroke,
+            stroke_width,
+            opacity_attribute("stroke-opacity", alpha)
+        )
+        .unwrap();
+    }
+
+    /// Adds points as squares `size` pixels wide, like OpenGL draws them. Points of the same
+    /// color are collected into one path.
+    ///
+    /// With `merge`, points of the same color whose squares would start in the same pixel are
+    /// written only once. Pictures made of millions of points look the same, but the file stays a
+    /// reasonable size.
+    pub fn add_points<I>(&mut self, points: I, size: f32, merge: bool)
+    where
+        I: IntoIterator<Item = ([f32; 2], [f32; 4])>,
+    {
+        // Keeps the paths in order of first appearance, so layers sampled later end up on top
+        let mut paths: Vec<([f32; 4], String)> = vec![];
+        let mut path_of: BTreeMap<[u32; 4], usize> = BTreeMap::new();
+        let mut seen: HashSet<(usize, i64, i64)> = HashSet::new();
+
+        let half = size / 2.0;
+        for ([x, y], color) in points {
+            if !x.is_finite() || !y.is_finite() {
+                continue;
+            }
+
+            let (x, y) = self.ndc_to_document(x, y);
+            let (x, y) = (x - half, y - half);
+
+            let key = color.map(f32::to_bits);
+            let i = *path_of.entry(key).or_insert_with(|| {
+                paths.push((color, String::new()));
+                paths.len() - 1
+            });
+            if merge && !seen.insert((i, x.floor() as i64, y.floor() as i64)) {
+                continue;
+            }
+
+            let (x, y) = if merge {
+                (x.floor(), y.floor())
+            } else {
+                (x, y)
+            };
+            write!(paths[i].1, "M{} {}h{}v{}h-{}z", x, y, size, size, size).unwrap();
+        }
+
+        for (color, d) in paths {
+            let (fill, alpha) = svg_color(color);
+            writeln!(
+                self.body,
+                "<path d=\"{}\" fill=\"{}\"{}/>",
+                d,
+                fill,
+                opacity_attribute("fill-opacity", alpha)
+            )
+            .unwrap();
+        }
+    }
+
+    pub fn write_svg<W: Write>(&self, mut w: W) -> io::Result<()> {
+        writeln!(
+            w,
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{0}\" height=\"{1}\" \
+             viewBox=\"0 0 {0} {1}\" shape-rendering=\"crispEdges\">",
+            self.width, self.height
+        )?;
+        if let Some(color) = self.background {
+            let (fill, alpha) = svg_color(color);
+            writeln!(
+                w,
+                "<rect width=\"100%\" height=\"100%\" fill=\"{}\"{}/>",
+                fill,
+                opacity_attribute("fill-opacity", alpha)
+            )?;
+        }
+        w.write_all(self.body.as_bytes())?;
+        writeln!(w, "</svg>")
+    }
+
+    pub fn save_svg<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut w = BufWriter::new(File::create(path)?);
+        self.write_svg(&mut w)?;
+        w.flush()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::svg::Svg;
+    use crate::vertex::Line;
+
+    fn to_string(svg: &Svg) -> String {
+        let mut out = vec![];
+        svg.write_svg(&mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn lines() {
+        let mut svg = Svg::new(100, 50);
+        svg.set_background([1.0, 1.0, 1.0, 1.0]);
+        svg.add_lines(
+            &[Line([-1.0, 1.0].into(), [1.0, 1.0].into())],
+            [0.0, 0.0, 0.0, 0.5],
+            2.0,
+        );
+
+        let s = to_string(&svg);
+        assert!(s.starts_with("<svg "));
+        assert!(s.contains("viewBox=\"0 0 100 50\""));
+        assert!(s.contains("<rect width=\"100%\" height=\"100%\" fill=\"#ffffff\"/>"));
+        assert!(s.contains(
+            "<path d=\"M0 0L100 0\" fill=\"none\" stroke=\"#000000\" stroke-width=\"2\" \
+             stroke-opacity=\"0.5\"/>"
+        ));
+        assert!(s.trim_end().ends_with("</svg>"));
+    }
+
+    #[test]
+    fn merge_points() {
+        let red = [1.0, 0.0, 0.0, 1.0];
+        let blue = [0.0, 0.0, 1.0, 1.0];
+        let points = [
+            ([0.0, 0.0], red),
+            ([0.001, 0.001], red),
+            ([0.001, 0.001], blue),
+            ([0.5, 0.5], red),
+        ];
+
+        let mut svg = Svg::new(10, 10);
+        svg.add_points(points, 1.0, false);
+        assert_eq!(to_string(&svg).matches('M').count(), 4);
+
+        let mut svg = Svg::new(10, 10);
+        svg.add_points(points, 1.0, true);
+        let s = to_string(&svg);
+        assert_eq!(s.matches('M').count(), 3);
+        // One path per color, red first
+        assert!(s.find("#ff0000").unwrap() < s.find("#0000ff").unwrap());
+        assert_eq!(s.matches("<path").count(), 2);
+    }
+}
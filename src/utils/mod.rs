@@ -1 +1,3 @@
+pub mod parallel;
+pub mod rng;
 pub mod winit;
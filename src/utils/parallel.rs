@@ -0,0 +1,99 @@
+use std::num::NonZeroUsize;
+use std::ops::Range;
+use std::thread;
+
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+/// Number of threads the machine can run at once, or 1 if that is unknown.
+pub fn available_threads() -> usize {
+    thread::available_parallelism().map_or(1, NonZeroUsize::get)
+}
+
+/// Splits `iters` iterations as evenly as possible between `workers`.
+pub fn split_iterations(iters: u64, workers: usize) -> Vec<u64> {
+    let workers = workers.max(1) as u64;
+    (0..workers)
+        .map(|i| iters / workers + u64::from(i < iters % workers))
+        .collect()
+}
+
+/// Runs `work` on `workers` threads, each with its share of `iters` iterations and its own
+/// random number generator, and returns the results in worker order.
+///
+/// The generators are seeded from `rng` before any thread starts, so the results only depend on
+/// the state of `rng` and the number of workers, not on how the threads get scheduled.
+pub fn run_workers<R, T, F>(rng: &mut R, workers: usize, iters: u64, work: F) -> Vec<T>
+where
+    R: Rng + ?Sized,
+    T: Send,
+    F: Fn(&mut ChaCha8Rng, u64) -> T + Sync,
+{
+    run_worker_ranges(rng, workers, iters, |rng, range| {
+        work(rng, range.end - range.start)
+    })
+}
+
+/// Same as [`run_workers`], but tells every worker which of the `iters` iterations it runs, for
+/// work that depends on how far along the whole run it is.
+pub fn run_worker_ranges<R, T, F>(rng: &mut R, workers: usize, iters: u64, work: F) -> Vec<T>
+where
+    R: Rng + ?Sized,
+    T: Send,
+    F: Fn(&mut ChaCha8Rng, Range<u64>) -> T + Sync,
+{
+    let mut start = 0;
+    let jobs: Vec<(ChaCha8Rng, Range<u64>)> = split_iterations(iters, workers)
+        .into_iter()
+        .map(|iters| {
+            start += iters;
+            (ChaCha8Rng::from_seed(rng.gen()), start - iters..start)
+        })
+        .collect();
+
+    let work = &work;
+    thread::scope(|scope| {
+        let handles: Vec<_> = jobs
+            .into_iter()
+            .map(|(mut rng, range)| scope.spawn(move || work(&mut rng, range)))
+            .collect();
+        handles.into_iter().map(|h| h.join().unwrap()).collect()
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use rand::Rng;
+
+    use crate::utils::parallel::{run_worker_ranges, run_workers, split_iterations};
+    use crate::utils::rng::seeded_rng;
+
+    #[test]
+    fn split() {
+        assert_eq!(split_iterations(10, 3), vec![4, 3, 3]);
+        assert_eq!(split_iterations(2, 4), vec![1, 1, 0, 0]);
+        assert_eq!(split_iterations(5, 0), vec![5]);
+    }
+
+    #[test]
+    fn workers_are_reproducible() {
+        let run = || {
+            run_workers(&mut seeded_rng(Some(1)), 4, 100, |rng, iters| {
+                (iters, rng.gen::<u32>())
+            })
+        };
+
+        let results = run();
+        assert_eq!(results.len(), 4);
+        assert_eq!(results.iter().map(|r| r.0).sum::<u64>(), 100);
+        // Every worker gets its own stream
+        assert_ne!(results[0].1, results[1].1);
+        assert_eq!(results, run());
+    }
+
+    #[test]
+    fn worker_ranges() {
+        let ranges = run_worker_ranges(&mut seeded_rng(Some(1)), 3, 10, |_, range| range);
+        assert_eq!(ranges, vec![0..4, 4..7, 7..10]);
+    }
+}
@@ -0,0 +1,12 @@
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+
+/// Creates the random number generator used throughout the crate. With a seed the generator
+/// produces the same sequence on every run and every platform, otherwise it is seeded from the
+/// operating system.
+pub fn seeded_rng(seed: Option<u64>) -> ChaCha8Rng {
+    match seed {
+        Some(seed) => ChaCha8Rng::seed_from_u64(seed),
+        None => ChaCha8Rng::from_entropy(),
+    }
+}
@@ -1,3 +1,5 @@
+use std::iter;
+
 use glium::implement_vertex;
 
 #[derive(Copy, Clone)]
@@ -22,3 +24,12 @@ impl From<[f32; 2]> for Vertex {
         Vertex { position: x }
     }
 }
+
+/// A line segment, drawn with `PrimitiveType::LinesList`.
+pub struct Line(pub Vertex, pub Vertex);
+
+impl Line {
+    pub fn into_vertices(self) -> impl Iterator<Item = Vertex> {
+        iter::once(self.0).chain(iter::once(self.1))
+    }
+}